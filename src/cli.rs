@@ -1,5 +1,9 @@
 use std::env;
-use clap::Parser;
+use chrono::NaiveDateTime;
+use clap::{Parser, Subcommand};
+use route::Avoid;
+use route::export::render::Bbox;
+use route::routing::distances::DistanceMetric;
 use std::path::PathBuf;
 
 
@@ -18,12 +22,260 @@ pub struct Cli {
     /// Optional input file type in ["osm", "osm.pbf"]. default is "osm.pbf"
     #[arg(short,long)]
     pub itype: Option<String>,
+
+    /// Optional output file, format given by extension (".gpx" or ".geojson"). default is "./data/trace.gpx" (standard output for subcommands)
+    #[arg(short,long,global=true)]
+    pub output: Option<PathBuf>,
+
+    /// Optional online geocoder used when the address is not found offline, in ["nominatim", "photon", "none"]. default is "nominatim"
+    #[arg(short,long)]
+    pub geocoder: Option<String>,
+
+    /// Optional geocoder url (self-hosted instance). default is the public service
+    #[arg(long)]
+    pub geocoder_url: Option<String>,
+
+    /// Optional minimal size (in nodes) of a connected component: smaller islands are removed from the graph after loading
+    #[arg(long, value_name = "MIN_NODES")]
+    pub prune: Option<usize>,
+
+    /// Optional directory of elevation tiles (SRTM ".hgt" or GeoTIFF ".tif"), used for climbs and the bike / foot cost
+    #[arg(long, value_name = "DIR")]
+    pub dem: Option<PathBuf>,
+
+    /// Optional distance formula in ["haversine", "vincenty", "equirectangular"]. default is "haversine"
+    #[arg(long, value_name = "METRIC", value_parser = metric)]
+    pub distance: Option<DistanceMetric>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+
+#[derive(Subcommand, Debug, PartialEq)]
+pub enum Command {
+    /// Start an HTTP server with OSRM compatible endpoints (route, nearest, table, match)
+    Serve {
+        /// Listening address
+        #[arg(short, long, default_value = "127.0.0.1:5000")]
+        listen: String,
+
+        /// Number of threads handling the requests
+        #[arg(short, long, default_value_t = 4)]
+        threads: usize,
+    },
+
+    /// Print the graph summary
+    Info,
+
+    /// Check the graph data (missing nodes, zero-length segments, unreadable tags, ...) and report the problems
+    Validate {
+        /// Output format, in ["text", "geojson"]. default is given by the output file extension, text on standard output
+        #[arg(long, value_parser = ["text", "geojson"])]
+        format: Option<String>,
+    },
+
+    /// Print the nearest node of a point
+    Nearest {
+        #[arg(allow_negative_numbers = true)]
+        lat: f64,
+        #[arg(allow_negative_numbers = true)]
+        lon: f64,
+
+        /// Profile whose routable ways are searched, in ["car", "bike", "foot"]
+        #[arg(short, long, default_value = "car")]
+        profile: String,
+    },
+
+    /// Compute a path between two points, given as node id or "lat,lon"
+    Path {
+        #[arg(allow_hyphen_values = true)]
+        from: String,
+        #[arg(allow_hyphen_values = true)]
+        to: String,
+
+        /// Weight minimized, in ["distance", "time", "cost"]
+        #[arg(short, long, default_value = "distance", value_parser = ["distance", "time", "cost"])]
+        mode: String,
+
+        /// Profile, in ["car", "bike", "foot"]
+        #[arg(short, long, default_value = "car")]
+        profile: String,
+
+        /// Output format, in ["gpx", "geojson", "html", "polyline", "polyline6", "json"]. default is given by the output file extension, json on standard output
+        #[arg(long, value_parser = ["gpx", "geojson", "html", "polyline", "polyline6", "json"])]
+        format: Option<String>,
+
+        /// Departure time ("2026-10-19T08:00"), conditional restrictions are then applied and the GPX points get timestamps
+        #[arg(short, long, value_parser = datetime)]
+        depart: Option<NaiveDateTime>,
+
+        /// Ways to avoid, in ["toll", "motorway", "ferry", "unpaved", "tunnel", "bridge"], comma separated
+        #[arg(short, long, value_delimiter = ',', value_parser = avoid)]
+        avoid: Vec<Avoid>,
+
+        /// Name of the GPX track. default is "Itinéraire"
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Add the turn instructions to the GPX as a route (<rte>) of named points
+        #[arg(long)]
+        instructions: bool,
+
+        /// Add the start and end points to the GPX as waypoints (<wpt>)
+        #[arg(long)]
+        waypoints: bool,
+    },
+
+    /// Compute the path through the waypoints of a file, in order: GPX (<rtept> or <wpt>), GeoJSON points or CSV lat,lon[,name]
+    Import {
+        file: PathBuf,
+
+        /// Weight minimized, in ["distance", "time", "cost"]
+        #[arg(short, long, default_value = "distance", value_parser = ["distance", "time", "cost"])]
+        mode: String,
+
+        /// Profile, in ["car", "bike", "foot"]
+        #[arg(short, long, default_value = "car")]
+        profile: String,
+
+        /// Output format, in ["gpx", "geojson", "html", "polyline", "polyline6", "json"]. default is given by the output file extension, json on standard output
+        #[arg(long, value_parser = ["gpx", "geojson", "html", "polyline", "polyline6", "json"])]
+        format: Option<String>,
+
+        /// Departure time ("2026-10-19T08:00"), conditional restrictions are then applied and the GPX points get timestamps
+        #[arg(short, long, value_parser = datetime)]
+        depart: Option<NaiveDateTime>,
+
+        /// Ways to avoid, in ["toll", "motorway", "ferry", "unpaved", "tunnel", "bridge"], comma separated
+        #[arg(short, long, value_delimiter = ',', value_parser = avoid)]
+        avoid: Vec<Avoid>,
+
+        /// Name of the GPX track. default is "Itinéraire"
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Add the turn instructions to the GPX as a route (<rte>) of named points
+        #[arg(long)]
+        instructions: bool,
+
+        /// Add the start, end and intermediate points to the GPX as waypoints (<wpt>)
+        #[arg(long)]
+        waypoints: bool,
+    },
+
+    /// Draw the ways of an area and, given two points, the path between them, as SVG or PNG
+    Render {
+        /// Start of the path drawn, node id or "lat,lon"
+        #[arg(allow_hyphen_values = true, requires = "to")]
+        from: Option<String>,
+        /// End of the path drawn, node id or "lat,lon"
+        #[arg(allow_hyphen_values = true)]
+        to: Option<String>,
+
+        /// Weight minimized, in ["distance", "time", "cost"]
+        #[arg(short, long, default_value = "distance", value_parser = ["distance", "time", "cost"])]
+        mode: String,
+
+        /// Profile, in ["car", "bike", "foot"]
+        #[arg(short, long, default_value = "car")]
+        profile: String,
+
+        /// Image width in pixels
+        #[arg(long, default_value_t = 1024)]
+        width: u32,
+
+        /// Image height in pixels
+        #[arg(long, default_value_t = 768)]
+        height: u32,
+
+        /// Area drawn, "min_lon,min_lat,max_lon,max_lat". default is the path with a margin, or the whole graph
+        #[arg(long, allow_hyphen_values = true, value_parser = bbox)]
+        bbox: Option<Bbox>,
+
+        /// Output format, in ["svg", "png"]. default is given by the output file extension, svg on standard output
+        #[arg(long, value_parser = ["svg", "png"])]
+        format: Option<String>,
+    },
+
+    /// Geocode an address (offline index first, then the online geocoder)
+    Locate {
+        query: String,
+    },
+
+    /// Compute the paths listed in a csv file (id,from,to), one result line per path
+    Batch {
+        file: PathBuf,
+
+        /// Weight minimized, in ["distance", "time", "cost"]
+        #[arg(short, long, default_value = "distance", value_parser = ["distance", "time", "cost"])]
+        mode: String,
+
+        /// Profile, in ["car", "bike", "foot"]
+        #[arg(short, long, default_value = "car")]
+        profile: String,
+
+        /// Departure time of every path ("2026-10-19T08:00"), conditional restrictions are then applied
+        #[arg(short, long, value_parser = datetime)]
+        depart: Option<NaiveDateTime>,
+
+        /// Ways to avoid, in ["toll", "motorway", "ferry", "unpaved", "tunnel", "bridge"], comma separated
+        #[arg(short, long, value_delimiter = ',', value_parser = avoid)]
+        avoid: Vec<Avoid>,
+    },
+}
+
+
+///
+/// date et heure de départ : "2026-10-19T08:00", "2026-10-19 08:00" ou avec les secondes
+///
+pub fn datetime(s: &str) -> Result<NaiveDateTime, String> {
+    ["%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"].iter()
+        .find_map( |f| NaiveDateTime::parse_from_str( s, f ).ok() )
+        .ok_or( format!( "date invalide : {} (attendu AAAA-MM-JJTHH:MM)", s ) )
+}
+
+///
+/// type de voie à éviter
+///
+pub fn avoid(s: &str) -> Result<Avoid, String> {
+    Avoid::from_name( s ).ok_or( format!( "option d'évitement inconnue : {}", s ) )
+}
+
+///
+/// cadre de la carte : min_lon,min_lat,max_lon,max_lat
+///
+pub fn bbox(s: &str) -> Result<Bbox, String> {
+    Bbox::parse( s ).ok_or( format!( "cadre invalide : {} (attendu min_lon,min_lat,max_lon,max_lat)", s ) )
+}
+
+///
+/// formule de calcul des distances
+///
+pub fn metric(s: &str) -> Result<DistanceMetric, String> {
+    DistanceMetric::from_name( s ).ok_or( format!( "formule de distance inconnue : {}", s ) )
 }
 
 
 #[derive(Debug)]
 pub struct Datafiles {
     pub input_file: PathBuf,
+    pub output_file: PathBuf,
+    /// le fichier de sortie a été donné sur la ligne de commande
+    pub output_given: bool,
+    pub geocoder: String,
+    pub geocoder_url: Option<String>,
+    pub cache_file: PathBuf,
+    /// historique des commandes du menu
+    pub history_file: PathBuf,
+    /// lieux nommés du menu
+    pub places_file: PathBuf,
+    /// taille minimale des composantes gardées au chargement
+    pub prune: Option<usize>,
+    /// répertoire des tuiles d'altitude
+    pub dem: Option<PathBuf>,
+    /// formule de distance des arcs
+    pub distance: DistanceMetric,
 }
 
 
@@ -34,8 +286,47 @@ impl Datafiles {
         if let Some(filename) = f { fpath.push( filename ) } else { fpath.push("St_Brieuc-Loudéac") };
         if let Some(ext) = e { fpath.set_extension( ext.as_str() ) } else { fpath.set_extension( "osm.pbf" ) };
         Self {
-            input_file: fpath
+            input_file: fpath,
+            output_file: PathBuf::from( "./data/trace.gpx" ),
+            output_given: false,
+            geocoder: "nominatim".to_string(),
+            geocoder_url: None,
+            cache_file: PathBuf::from( "./data/geocoder_cache.json" ),
+            history_file: PathBuf::from( "./data/history.txt" ),
+            places_file: PathBuf::from( "./data/places.json" ),
+            prune: None,
+            dem: None,
+            distance: DistanceMetric::default(),
+        }
+    }
+
+    pub fn with_output(mut self, o: Option<PathBuf>) -> Self {
+        if let Some(output) = o {
+            self.output_file = output;
+            self.output_given = true;
         }
+        self
+    }
+
+    pub fn with_geocoder(mut self, g: Option<String>, url: Option<String>) -> Self {
+        if let Some(geocoder) = g { self.geocoder = geocoder; }
+        self.geocoder_url = url;
+        self
+    }
+
+    pub fn with_prune(mut self, min: Option<usize>) -> Self {
+        self.prune = min;
+        self
+    }
+
+    pub fn with_dem(mut self, dir: Option<PathBuf>) -> Self {
+        self.dem = dir;
+        self
+    }
+
+    pub fn with_distance(mut self, m: Option<DistanceMetric>) -> Self {
+        if let Some(metric) = m { self.distance = metric; }
+        self
     }
 
     pub fn get_ifilepath(&self) -> &PathBuf {
@@ -46,12 +337,68 @@ impl Datafiles {
         self.input_file.as_os_str().to_str().expect("all must be right").to_string()
     }
 
+    pub fn get_ofilepath(&self) -> &PathBuf {
+        &self.output_file
+    }
+
+    ///
+    /// fichier de sortie s'il a été donné, sortie standard sinon (sous-commandes)
+    ///
+    pub fn get_output(&self) -> Option<&PathBuf> {
+        if self.output_given { Some( &self.output_file ) } else { None }
+    }
+
+    pub fn get_geocoder(&self) -> &str {
+        self.geocoder.as_str()
+    }
+
+    pub fn get_geocoder_url(&self) -> Option<&str> {
+        self.geocoder_url.as_deref()
+    }
+
+    pub fn get_cachefilepath(&self) -> &PathBuf {
+        &self.cache_file
+    }
+
+    pub fn get_historyfilepath(&self) -> &PathBuf {
+        &self.history_file
+    }
+
+    pub fn get_placesfilepath(&self) -> &PathBuf {
+        &self.places_file
+    }
+
+    pub fn get_prune(&self) -> Option<usize> {
+        self.prune
+    }
+
+    pub fn get_dem(&self) -> Option<&PathBuf> {
+        self.dem.as_ref()
+    }
+
+    pub fn get_distance(&self) -> DistanceMetric {
+        self.distance
+    }
+
 }
 
-pub fn get_input_filename() -> String {
+pub fn get_datafiles() -> Datafiles {
+    get_command().0
+}
+
+pub fn get_command() -> (Datafiles, Option<Command>) {
     let cli = Cli::parse();
-    let df = Datafiles::new( cli.filename, cli.itype );
-    df.get_ifile_str()
+    let df = Datafiles::new( cli.filename, cli.itype )
+        .with_output( cli.output )
+        .with_geocoder( cli.geocoder, cli.geocoder_url )
+        .with_prune( cli.prune )
+        .with_dem( cli.dem )
+        .with_distance( cli.distance );
+    (df, cli.command)
+}
+
+pub fn get_input_filename() -> String {
+    get_datafiles().get_ifile_str()
 }
 
 
@@ -66,4 +413,75 @@ mod cli_tests {
         assert_eq!( &r, df.get_ifilepath() );
         assert_eq!( "/mnt/vg1-data/Code/Rust/route/data/StBrieuc.osm.pbf".to_string(), df.get_ifile_str() );
     }
+
+    #[test]
+    fn output_df() {
+        let df = Datafiles::new( None, None );
+        assert_eq!( &PathBuf::from("./data/trace.gpx"), df.get_ofilepath() );
+        assert_eq!( None, df.get_output() );
+        let df = df.with_output( Some( PathBuf::from("/tmp/route.geojson") ) );
+        assert_eq!( &PathBuf::from("/tmp/route.geojson"), df.get_ofilepath() );
+        assert_eq!( Some( &PathBuf::from("/tmp/route.geojson") ), df.get_output() );
+    }
+
+    #[test]
+    fn serve_command() {
+        let cli = Cli::parse_from( ["route", "-f", "Bretagne", "serve", "--listen", "0.0.0.0:8080"] );
+        assert_eq!( Some( Command::Serve { listen: "0.0.0.0:8080".to_string(), threads: 4 } ), cli.command );
+        assert_eq!( None, Cli::parse_from( ["route"] ).command );
+    }
+
+    #[test]
+    fn scripting_commands() {
+        let cli = Cli::parse_from( ["route", "nearest", "48.40631", "-2.81467"] );
+        assert_eq!( Some( Command::Nearest { lat: 48.40631, lon: -2.81467, profile: "car".to_string() } ), cli.command );
+
+        let cli = Cli::parse_from( ["route", "path", "--mode", "time", "10748130358", "48.4067,-2.8145", "--format", "gpx", "-o", "/tmp/p.gpx"] );
+        assert_eq!( Some( Command::Path { from: "10748130358".to_string(), to: "48.4067,-2.8145".to_string(),
+            mode: "time".to_string(), profile: "car".to_string(), format: Some( "gpx".to_string() ), depart: None, avoid: Vec::new(),
+            name: None, instructions: false, waypoints: false } ), cli.command );
+        assert_eq!( Some( PathBuf::from("/tmp/p.gpx") ), cli.output );
+
+        let cli = Cli::parse_from( ["route", "batch", "q.csv", "--depart", "2026-10-19T08:00"] );
+        let Some( Command::Batch { depart, .. } ) = cli.command else { panic!( "batch attendu" ) };
+        assert_eq!( datetime( "2026-10-19 08:00:00" ).ok(), depart );
+        assert!( Cli::try_parse_from( ["route", "path", "1", "2", "--depart", "demain"] ).is_err() );
+        let cli = Cli::parse_from( ["route", "path", "1", "2", "--avoid", "toll,motorway", "-a", "ferry"] );
+        let Some( Command::Path { avoid, .. } ) = cli.command else { panic!( "path attendu" ) };
+        assert_eq!( vec![Avoid::Toll, Avoid::Motorway, Avoid::Ferry], avoid );
+        assert!( Cli::try_parse_from( ["route", "path", "1", "2", "--avoid", "toll,cows"] ).is_err() );
+        let cli = Cli::parse_from( ["route", "path", "1", "2", "--format", "polyline6", "--name", "Tournée", "--instructions", "--waypoints"] );
+        let Some( Command::Path { format, name, instructions, waypoints, .. } ) = cli.command else { panic!( "path attendu" ) };
+        assert_eq!( ( Some( "polyline6".to_string() ), Some( "Tournée".to_string() ), true, true ), ( format, name, instructions, waypoints ) );
+
+        assert!( Cli::try_parse_from( ["route", "path", "1", "2", "--format", "kml"] ).is_err() );
+        assert!( Cli::try_parse_from( ["route", "batch", "q.csv", "--mode", "fast"] ).is_err() );
+    }
+
+    #[test]
+    fn geocoder_df() {
+        let df = Datafiles::new( None, None );
+        assert_eq!( "nominatim", df.get_geocoder() );
+        assert_eq!( None, df.get_geocoder_url() );
+        let df = df.with_geocoder( Some( "photon".to_string() ), Some( "http://localhost:2322".to_string() ) );
+        assert_eq!( "photon", df.get_geocoder() );
+        assert_eq!( Some("http://localhost:2322"), df.get_geocoder_url() );
+    }
+
+    #[test]
+    fn render_command() {
+        let cli = Cli::parse_from( ["route", "render", "--bbox", "-2.9,48.3,-2.7,48.5", "--width", "800", "-o", "/tmp/map.png"] );
+        assert_eq!( Some( Command::Render { from: None, to: None, mode: "distance".to_string(), profile: "car".to_string(), width: 800, height: 768,
+            bbox: Bbox::parse( "-2.9,48.3,-2.7,48.5" ), format: None } ), cli.command );
+        assert!( Cli::try_parse_from( ["route", "render", "10748130358"] ).is_err() );
+        assert!( Cli::try_parse_from( ["route", "render", "--bbox", "-2.7,48.3,-2.9,48.5"] ).is_err() );
+    }
+
+    #[test]
+    fn distance_option() {
+        let cli = Cli::parse_from( ["route", "--distance", "vincenty", "info"] );
+        assert_eq!( Some( DistanceMetric::Vincenty ), cli.distance );
+        assert_eq!( DistanceMetric::Haversine, Datafiles::new( None, None ).with_distance( None ).get_distance() );
+        assert!( Cli::try_parse_from( ["route", "--distance", "manhattan", "info"] ).is_err() );
+    }
 }
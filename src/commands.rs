@@ -0,0 +1,488 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use route::{Avoid, EngineError, Metric, Profile, Route, RouteOptions, RoutingEngine};
+use route::export::{ExportError, Format, geojson, render};
+use route::export::gpx_file::GpxOptions;
+use route::export::output::write_route;
+use route::graphe::Graph;
+use route::graphe::elevation::Climb;
+use route::graphe::validate::validate;
+use route::routing::geocoder::{Chain, GeoError, Geocoder, remote};
+use route::routing::geocoder::local::Local;
+use route::routing::instructions::instructions;
+use route::routing::waypoints::{ImportError, read_waypoints, route_waypoints};
+
+use crate::cli::{Command, Datafiles};
+
+
+// codes de sortie des sous-commandes (2 est aussi celui de clap pour une ligne de commande invalide)
+pub const SUCCESS: i32 = 0;
+pub const FAILURE: i32 = 1;
+pub const USAGE: i32 = 2;
+pub const NOT_FOUND: i32 = 3;
+
+
+
+#[derive(Debug)]
+pub enum CommandError {
+    Usage(String),
+    NotFound(String),
+    Io(io::Error),
+    Export(ExportError),
+    Geo(GeoError),
+}
+
+impl CommandError {
+    ///
+    /// code de sortie du programme
+    ///
+    pub fn code(&self) -> i32 {
+        match self {
+            CommandError::Usage(_) => USAGE,
+            CommandError::NotFound(_) => NOT_FOUND,
+            _ => FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            CommandError::Usage(s) => write!( f, "{}", s ),
+            CommandError::NotFound(s) => write!( f, "{}", s ),
+            CommandError::Io(e) => write!( f, "entrée / sortie impossible : {}", e ),
+            CommandError::Export(e) => write!( f, "{}", e ),
+            CommandError::Geo(e) => write!( f, "{}", e ),
+        }
+    }
+}
+
+impl From<io::Error> for CommandError {
+    fn from(e: io::Error) -> Self {
+        CommandError::Io(e)
+    }
+}
+
+impl From<ExportError> for CommandError {
+    fn from(e: ExportError) -> Self {
+        CommandError::Export(e)
+    }
+}
+
+impl From<GeoError> for CommandError {
+    fn from(e: GeoError) -> Self {
+        CommandError::Geo(e)
+    }
+}
+
+impl From<EngineError> for CommandError {
+    fn from(e: EngineError) -> Self {
+        match e {
+            EngineError::Load(_) => CommandError::Usage( e.to_string() ),
+            _ => CommandError::NotFound( e.to_string() ),
+        }
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        CommandError::Export( ExportError::Json( e ) )
+    }
+}
+
+
+///
+/// exécution d'une sous-commande non interactive, résultat sur la sortie standard ou dans le
+/// fichier --output, erreurs sur la sortie d'erreur ; renvoie le code de sortie du programme
+///
+pub fn run( engine: &RoutingEngine, df: &Datafiles, command: Command ) -> i32 {
+    // le fichier de sortie n'est créé qu'en cas de succès, pour ne pas écraser un résultat précédent
+    let result = match df.get_output() {
+        Some(path) => {
+            let mut buffer: Vec<u8> = Vec::new();
+            execute( engine, df, command, &mut buffer ).and_then( |()| fs::write( path, buffer ).map_err( CommandError::from ) )
+        },
+        None => execute( engine, df, command, &mut io::stdout().lock() ),
+    };
+    match result {
+        Ok(()) => SUCCESS,
+        Err(e) => {
+            eprintln!( "Erreur : {}", e );
+            e.code()
+        },
+    }
+}
+
+///
+/// exécution d'une sous-commande, résultat écrit dans `out`
+///
+pub fn execute( engine: &RoutingEngine, df: &Datafiles, command: Command, out: &mut dyn Write ) -> Result<(), CommandError> {
+    let g = engine.graph();
+    match command {
+        Command::Serve { .. } => Err( CommandError::Usage( "serve n'est pas une commande de script".to_string() ) ),
+        Command::Info => {
+            writeln!( out, "{}", g )?;
+            writeln!( out, "composantes fortement connexes :" )?;
+            for profile in [Profile::Car, Profile::Bicycle, Profile::Foot] {
+                writeln!( out, "\t{} : {}", profile, engine.components( profile ) )?;
+            }
+            Ok(())
+        },
+        Command::Validate { format } => {
+            let report = validate( g );
+            let geojson = match format.as_deref() {
+                Some(f) => f == "geojson",
+                None => df.get_output().is_some_and( |path| path.extension().is_some_and( |e| e == "geojson" ) ),
+            };
+            if geojson { serde_json::to_writer_pretty( &mut *out, &geojson::report( &report ) )?; }
+            else { write!( out, "{}", report )?; }
+            writeln!( out )?;
+            Ok(())
+        },
+        Command::Nearest { lat, lon, profile } => {
+            let snap = engine.nearest( profile_of( &profile )?, lat, lon ).ok_or( CommandError::from( EngineError::NoSegment( lat, lon ) ) )?;
+            writeln!( out, "{}\t{:.2}\t{}\t{}", snap.node, snap.distance, snap.lat, snap.lon )?;
+            Ok(())
+        },
+        Command::Path { from, to, mode, profile, format, depart, avoid, name, instructions, waypoints } => {
+            let (profile, metric) = ( profile_of( &profile )?, metric_of( &mode )? );
+            let options = RouteOptions { depart, avoid };
+            let r = engine.route_nodes_with( profile, metric, point( engine, profile, &from )?, point( engine, profile, &to )?, &options )?;
+            let unsatisfied = Avoid::unsatisfied( &options.avoid, g, &r );
+            let gpx = GpxOptions { name, depart, instructions, waypoints, vias: Vec::new() };
+            let json = output_format( df, format.as_deref() ).is_none();
+            write_path( df, g, &r, format.as_deref(), &gpx, summary( g, &r, metric, profile, &unsatisfied ), out )?;
+            if !json && !unsatisfied.is_empty() { eprintln!( "Avertissement : {} non évité(s)", names( &unsatisfied, "," ) ); }
+            Ok(())
+        },
+        Command::Import { file, mode, profile, format, depart, avoid, name, instructions, waypoints } => {
+            let (profile, metric) = ( profile_of( &profile )?, metric_of( &mode )? );
+            let points = read_waypoints( &file ).map_err( |e| match e {
+                ImportError::Io(e) => CommandError::Io( e ),
+                e => CommandError::Usage( e.to_string() ),
+            } )?;
+            let tour = route_waypoints( engine, profile, metric, &points, &RouteOptions { depart, avoid } ).map_err( |e| match e {
+                ImportError::Engine(e) => CommandError::from( e ),
+                e => CommandError::NotFound( e.to_string() ),
+            } )?;
+            let r = tour.itinerary.route();
+            let stops = &tour.itinerary.waypoints;
+            let vias: Vec<i64> = stops.iter().skip( 1 ).take( stops.len().saturating_sub( 2 ) ).map( |s| s.node ).collect();
+            let gpx = GpxOptions { name, depart, instructions, waypoints, vias };
+            let mut json = summary( g, &r, metric, profile, &tour.itinerary.unsatisfied );
+            json["waypoints"] = points.iter().enumerate().map( |(i, p)| {
+                let snapped = tour.kept.iter().position( |k| *k == i ).map( |k| &stops[k] );
+                let error = tour.errors.iter().find( |(e, _)| *e == i ).map( |(_, e)| e.to_string() );
+                json!( { "name": p.name, "location": [p.lon, p.lat], "node": snapped.map( |s| s.node ),
+                    "distance": snapped.map( |s| s.distance ), "error": error } )
+            } ).collect();
+            write_path( df, g, &r, format.as_deref(), &gpx, json, out )?;
+            for (i, e) in tour.errors.iter() {
+                eprintln!( "Avertissement : point {} ({}) écarté : {}", i + 1, points[*i], e );
+            }
+            if !tour.errors.is_empty() {
+                return Err( CommandError::NotFound( format!( "{} point(s) sur {} hors du réseau", tour.errors.len(), points.len() ) ) );
+            }
+            Ok(())
+        },
+        Command::Render { from, to, mode, profile, width, height, bbox, format } => {
+            let r = match ( from, to ) {
+                ( Some(from), Some(to) ) => {
+                    let (profile, metric) = ( profile_of( &profile )?, metric_of( &mode )? );
+                    Some( engine.route_nodes_with( profile, metric, point( engine, profile, &from )?, point( engine, profile, &to )?, &RouteOptions::default() )? )
+                },
+                _ => None,
+            };
+            let map = render::render( g, r.as_ref(), width, height, bbox ).map_err( |e| CommandError::Usage( e.to_string() ) )?;
+            let png = match format.as_deref() {
+                Some(f) => f == "png",
+                None => df.get_output().is_some_and( |path| render::is_png( path ) ),
+            };
+            if png { out.write_all( &map.png()? )?; }
+            else { out.write_all( map.svg().as_bytes() )?; }
+            Ok(())
+        },
+        Command::Locate { query } => {
+            let mut geocoders: Vec<Box<dyn Geocoder>> = vec![Box::new( Local::new( &g.addresses, 5 ) )];
+            if df.get_geocoder() != "none" {
+                geocoders.push( remote( df.get_geocoder(), df.get_geocoder_url(), Some( df.get_cachefilepath() ) )? );
+            }
+            let hits = Chain::new( geocoders ).search( &query )?;
+            if hits.is_empty() { return Err( CommandError::NotFound( format!( "aucun résultat pour \"{}\"", query ) ) ); }
+            for h in hits.iter() {
+                writeln!( out, "{}\t{}\t{}", h.lat, h.lon, h.label )?;
+            }
+            Ok(())
+        },
+        Command::Batch { file, mode, profile, depart, avoid } => {
+            batch( engine, &file, metric_of( &mode )?, profile_of( &profile )?, &RouteOptions { depart, avoid }, out )
+        },
+    }
+}
+
+
+fn profile_of( name: &str ) -> Result<Profile, CommandError> {
+    Profile::from_name( name ).ok_or( CommandError::Usage( format!( "profil inconnu : {}", name ) ) )
+}
+
+fn metric_of( name: &str ) -> Result<Metric, CommandError> {
+    Metric::from_name( name ).ok_or( CommandError::Usage( format!( "mode inconnu : {}", name ) ) )
+}
+
+// format donné, ou déduit de l'extension du fichier de sortie ; None pour le résumé json
+fn output_format( df: &Datafiles, format: Option<&str> ) -> Option<Format> {
+    match ( format, df.get_output() ) {
+        ( Some(f), _ ) => Format::from_name( f ),
+        ( None, Some(path) ) => Format::of( path ).ok(),
+        ( None, None ) => None,
+    }
+}
+
+// itinéraire au format demandé, ou son résumé json
+fn write_path( df: &Datafiles, g: &Graph, r: &Route, format: Option<&str>, gpx: &GpxOptions, json: Value, out: &mut dyn Write ) -> Result<(), CommandError> {
+    match output_format( df, format ) {
+        Some(f) => { write_route( g, r, f, gpx, &mut *out )?; },
+        None => { serde_json::to_writer_pretty( &mut *out, &json )?; writeln!( out )?; },
+    }
+    Ok(())
+}
+
+// "toll,tunnel"
+fn names( avoid: &[Avoid], separator: &str ) -> String {
+    avoid.iter().map( |a| a.to_string() ).collect::<Vec<String>>().join( separator )
+}
+
+// noeud donné par son id ou par ses coordonnées "lat,lon" (noeud routable le plus proche)
+fn point( engine: &RoutingEngine, profile: Profile, s: &str ) -> Result<i64, CommandError> {
+    if let Ok(id) = s.trim().parse::<i64>() {
+        return if engine.directed( profile ).contains_key( &id ) { Ok(id) }
+            else { Err( CommandError::NotFound( format!( "noeud {} absent du graphe {}", id, profile ) ) ) };
+    }
+    let invalid = || CommandError::Usage( format!( "point invalide : {} (id de noeud ou \"lat,lon\")", s ) );
+    let (lat, lon) = s.split_once( [',', ' '] ).ok_or_else( invalid )?;
+    let (lat, lon) = ( lat.trim().parse::<f64>().map_err( |_| invalid() )?, lon.trim().parse::<f64>().map_err( |_| invalid() )? );
+    engine.nearest( profile, lat, lon ).map( |p| p.node ).ok_or( CommandError::from( EngineError::NoSegment( lat, lon ) ) )
+}
+
+// itinéraire au format json : longueur, durée, dénivelés (null sans MNT), noeuds, ways, géométrie et étapes
+fn summary( g: &Graph, r: &Route, metric: Metric, profile: Profile, unsatisfied: &[Avoid] ) -> Value {
+    let elevation = Climb::of( g, &r.nodes ).map( |c| json!( {
+        "ascent": c.ascent,
+        "descent": c.descent,
+        "max_grade": c.max_grade,
+        "min": c.min,
+        "max": c.max,
+    } ) );
+    json!( {
+        "profile": profile.to_string(),
+        "mode": metric.to_string(),
+        "unsatisfied": unsatisfied.iter().map( |a| a.to_string() ).collect::<Vec<String>>(),
+        "distance": r.distance,
+        "duration": r.time,
+        "elevation": elevation,
+        "nodes": r.nodes,
+        "ways": r.ways,
+        "coordinates": r.coords.iter().map( |(lat, lon)| json!( [lon, lat] ) ).collect::<Vec<Value>>(),
+        "instructions": instructions( g, r ).iter().map( |i| json!( {
+            "text": i.text(),
+            "distance": i.distance,
+            "duration": i.duration,
+            "location": [i.location.1, i.location.0],
+            "ferry": i.ferry,
+        } ) ).collect::<Vec<Value>>(),
+    } )
+}
+
+///
+/// itinéraires listés dans un fichier csv "id,from,to" (points : id de noeud ou "lat lon"),
+/// une ligne de résultat par itinéraire : id,from,to,distance,duration,status ; le status est "ok",
+/// ou "unsatisfied:toll;tunnel" quand des voies à éviter sont empruntées faute d'alternative
+///
+fn batch( engine: &RoutingEngine, file: &Path, metric: Metric, profile: Profile, options: &RouteOptions, out: &mut dyn Write ) -> Result<(), CommandError> {
+    let content = fs::read_to_string( file )?;
+    let mut failed = 0;
+    let mut total = 0;
+
+    writeln!( out, "id,from,to,distance,duration,status" )?;
+    for line in content.lines().map( |l| l.trim() ) {
+        // lignes vides, commentaires et entête
+        if line.is_empty() || line.starts_with( '#' ) || line.starts_with( "id," ) { continue; }
+        total += 1;
+        let fields: Vec<&str> = line.split( ',' ).map( |f| f.trim() ).collect();
+        let result = match fields.as_slice() {
+            [_, from, to, ..] => point( engine, profile, from ).and_then( |a| point( engine, profile, to ).map( |b| (a, b) ) )
+                .and_then( |(a, b)| engine.route_nodes_with( profile, metric, a, b, options ).map( |r| (a, b, r) ).map_err( CommandError::from ) ),
+            _ => Err( CommandError::Usage( format!( "ligne invalide : {}", line ) ) ),
+        };
+        match result {
+            Ok((a, b, r)) => {
+                let unsatisfied = Avoid::unsatisfied( &options.avoid, engine.graph(), &r );
+                let status = if unsatisfied.is_empty() { "ok".to_string() } else { format!( "unsatisfied:{}", names( &unsatisfied, ";" ) ) };
+                writeln!( out, "{},{},{},{:.2},{:.2},{}", fields[0], a, b, r.distance, r.time, status )?
+            },
+            Err(e) => {
+                failed += 1;
+                writeln!( out, "{},,,,,\"{}\"", fields[0], e.to_string().replace( '"', "'" ) )?;
+            },
+        }
+    }
+    if failed > 0 { return Err( CommandError::NotFound( format!( "{} itinéraire(s) sur {} en échec", failed, total ) ) ); }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod commands_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    fn line() -> RoutingEngine {
+        let rue = &[("highway", "residential"), ("name", "Rue A")];
+        RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99), (4, 49.0, -1.0), (5, 49.0, -1.005)],
+                                        &[(10, &[1, 2, 3], rue), (11, &[4, 5], rue)] ) )
+    }
+
+    fn exec( g: &RoutingEngine, command: Command ) -> (Result<(), CommandError>, String) {
+        let mut out: Vec<u8> = Vec::new();
+        let r = execute( g, &Datafiles::new( None, None ), command, &mut out );
+        (r, String::from_utf8( out ).unwrap())
+    }
+
+    fn path( from: &str, to: &str, format: Option<&str> ) -> Command {
+        Command::Path { from: from.to_string(), to: to.to_string(), mode: "time".to_string(),
+            profile: "car".to_string(), format: format.map( |f| f.to_string() ), depart: None, avoid: Vec::new(),
+            name: None, instructions: false, waypoints: false }
+    }
+
+    #[test]
+    fn path_formats_and_errors() {
+        let g = line();
+        let (r, out) = exec( &g, path( "1", "48.0001,-1.9901", None ) );
+        assert!( r.is_ok() );
+        let v: Value = serde_json::from_str( &out ).unwrap();
+        assert_eq!( json!( [1, 2, 3] ), v["nodes"] );
+        assert_eq!( json!( [] ), v["unsatisfied"] );
+        assert_eq!( "Arrivée", v["instructions"][1]["text"] );
+
+        let (r, out) = exec( &g, path( "1", "3", Some( "gpx" ) ) );
+        assert!( r.is_ok() );
+        assert!( out.contains( "<trkpt" ) );
+        let (_, out) = exec( &g, path( "1", "3", Some( "geojson" ) ) );
+        assert!( out.contains( "FeatureCollection" ) );
+        let (_, out) = exec( &g, path( "1", "3", Some( "polyline" ) ) );
+        assert_eq!( Some( vec![(48.0, -2.0), (48.0, -1.995), (48.0, -1.99)] ), route::export::polyline::decode( out.trim(), 5 ) );
+        let (_, out) = exec( &g, path( "1", "3", Some( "html" ) ) );
+        assert!( out.starts_with( "<!DOCTYPE html>" ) && out.contains( "Arrivée" ) );
+
+        let (r, _) = exec( &g, path( "1", "4", None ) );
+        assert_eq!( NOT_FOUND, r.unwrap_err().code() );
+        let (r, _) = exec( &g, path( "1", "rue", None ) );
+        assert_eq!( USAGE, r.unwrap_err().code() );
+        let (r, _) = exec( &g, Command::Path { from: "1".to_string(), to: "3".to_string(), mode: "time".to_string(),
+            profile: "avion".to_string(), format: None, depart: None, avoid: Vec::new(),
+            name: None, instructions: false, waypoints: false } );
+        assert_eq!( USAGE, r.unwrap_err().code() );
+
+        let (r, out) = exec( &g, Command::Nearest { lat: 48.0, lon: -1.9951, profile: "car".to_string() } );
+        assert!( r.is_ok() );
+        assert!( out.starts_with( "2\t" ) );
+    }
+
+    #[test]
+    fn nearest_by_profile() {
+        // un sentier passe plus près du point que la rue
+        let g = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.0005, -2.0), (4, 48.0005, -1.99)],
+                                                &[(10, &[1, 2], &[("highway", "residential")]), (11, &[3, 4], &[("highway", "footway")])] ) );
+
+        let (_, out) = exec( &g, Command::Nearest { lat: 48.0004, lon: -2.0, profile: "car".to_string() } );
+        assert!( out.starts_with( "1\t" ) );
+        let (_, out) = exec( &g, Command::Nearest { lat: 48.0004, lon: -2.0, profile: "foot".to_string() } );
+        assert!( out.starts_with( "3\t" ) );
+    }
+
+    #[test]
+    fn batch_file() {
+        let g = line();
+        let file = std::env::temp_dir().join( format!( "route_batch_{}.csv", std::process::id() ) );
+        fs::write( &file, "id,from,to\n# commentaire\na,1,3\nb,48.0 -2.0,49.0 -1.005\nc,1\n" ).unwrap();
+        let (r, out) = exec( &g, Command::Batch { file: file.clone(), mode: "distance".to_string(), profile: "car".to_string(), depart: None, avoid: Vec::new() } );
+        fs::remove_file( &file ).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!( 4, lines.len() );
+        assert!( lines[1].starts_with( "a,1,3," ) && lines[1].ends_with( ",ok" ) );
+        assert!( lines[2].starts_with( "b,,,,,\"pas d'itinéraire" ) );
+        assert!( lines[3].starts_with( "c,,,,,\"ligne invalide" ) );
+        assert_eq!( NOT_FOUND, r.unwrap_err().code() );
+
+        let (r, _) = exec( &g, Command::Batch { file: file.clone(), mode: "distance".to_string(), profile: "car".to_string(), depart: None, avoid: Vec::new() } );
+        assert_eq!( FAILURE, r.unwrap_err().code() );
+    }
+
+    #[test]
+    fn import_file() {
+        let g = line();
+        let file = std::env::temp_dir().join( format!( "route_import_{}.csv", std::process::id() ) );
+        let import = |format: Option<&str>| Command::Import { file: file.clone(), mode: "distance".to_string(), profile: "car".to_string(),
+            format: format.map( |f| f.to_string() ), depart: None, avoid: Vec::new(), name: None, instructions: false, waypoints: true };
+        fs::write( &file, "lat,lon,name\n48.0,-2.0,A\n40.0,10.0,Loin\n48.0,-1.995,B\n48.0,-1.99,C\n" ).unwrap();
+        let (r, out) = exec( &g, import( None ) );
+        assert_eq!( NOT_FOUND, r.unwrap_err().code() );
+        let v: Value = serde_json::from_str( &out ).unwrap();
+        assert_eq!( json!( [1, 2, 3] ), v["nodes"] );
+        assert_eq!( "Loin", v["waypoints"][1]["name"] );
+        assert!( v["waypoints"][1]["node"].is_null() && v["waypoints"][1]["error"].is_string() );
+        assert_eq!( 2, v["waypoints"][2]["node"] );
+        // étape intermédiaire en <wpt>
+        let (_, out) = exec( &g, import( Some( "gpx" ) ) );
+        assert_eq!( 3, out.matches( "<wpt" ).count() );
+
+        fs::write( &file, "48.0,-2.0\n40.0,10.0\n" ).unwrap();
+        let (r, _) = exec( &g, import( None ) );
+        assert_eq!( NOT_FOUND, r.unwrap_err().code() );
+        fs::write( &file, "48.0,-2.0\nrue\n" ).unwrap();
+        let (r, _) = exec( &g, import( None ) );
+        fs::remove_file( &file ).unwrap();
+        assert_eq!( USAGE, r.unwrap_err().code() );
+        let (r, _) = exec( &g, import( None ) );
+        assert_eq!( FAILURE, r.unwrap_err().code() );
+    }
+
+    #[test]
+    fn render_svg() {
+        let g = line();
+        let render = |from: Option<&str>, width: u32| Command::Render { from: from.map( |f| f.to_string() ), to: from.map( |_| "3".to_string() ),
+            mode: "distance".to_string(), profile: "car".to_string(), width, height: 200, bbox: None, format: None };
+        let (r, out) = exec( &g, render( Some( "1" ), 300 ) );
+        assert!( r.is_ok() );
+        // la way 10 et sa bordure (la way 11 est hors cadre), l'itinéraire et ses deux marqueurs
+        assert!( out.starts_with( "<svg" ) && out.contains( r#"width="300""# ) );
+        assert_eq!( 3, out.matches( "<polyline" ).count() );
+        assert_eq!( 2, out.matches( "<circle" ).count() );
+        let (r, out) = exec( &g, render( None, 300 ) );
+        assert!( r.is_ok() && !out.contains( "<circle" ) && out.matches( "<polyline" ).count() == 4 );
+        let (r, _) = exec( &g, render( None, 0 ) );
+        assert_eq!( USAGE, r.unwrap_err().code() );
+    }
+
+    #[test]
+    fn output_only_on_success() {
+        let g = line();
+        let file = std::env::temp_dir().join( format!( "route_output_{}.json", std::process::id() ) );
+        let df = Datafiles::new( None, None ).with_output( Some( file.clone() ) );
+        // pas de fichier créé pour une commande en échec, pas d'écrasement d'un résultat précédent
+        assert_eq!( NOT_FOUND, run( &g, &df, path( "1", "4", None ) ) );
+        assert!( !file.exists() );
+        assert_eq!( SUCCESS, run( &g, &df, path( "1", "3", None ) ) );
+        let out = fs::read_to_string( &file ).unwrap();
+        assert!( out.contains( "\"distance\"" ) );
+        assert_ne!( SUCCESS, run( &g, &df, path( "1", "4", None ) ) );
+        assert_eq!( out, fs::read_to_string( &file ).unwrap() );
+        fs::remove_file( &file ).unwrap();
+    }
+}
@@ -0,0 +1,686 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::collections::HashMap;
+
+use chrono::{Duration, NaiveDateTime};
+
+use crate::graphe::Graph;
+use crate::graphe::components::{Components, prune_islands};
+use crate::graphe::elevation::{Dem, set_elevation};
+use crate::graphe::elements::Edge;
+use crate::graphe::index::{Projection, SpatialIndex};
+use crate::graphe::reader::load_osm;
+use crate::routing::avoid::{Avoid, AVOID_PENALTY};
+use crate::routing::conditional::Conditions;
+use crate::routing::dijkstra::{one_to_all, shortest_path_at, shortest_path_with};
+use crate::routing::distances::DistanceMetric;
+use crate::routing::matching::Matcher;
+use crate::routing::profile::Profile;
+use crate::routing::route::Route;
+
+
+// rayon (m) de recherche initial d'un noeud routable, élargi jusqu'à MAX_SNAP
+const SNAP: f64 = 100.0;
+const MAX_SNAP: f64 = 12800.0;
+
+const PROFILES: [Profile; 3] = [Profile::Car, Profile::Bicycle, Profile::Foot];
+
+
+#[derive(Debug)]
+pub enum EngineError {
+    /// fichier osm.pbf illisible
+    Load(osmpbf::Error),
+    /// noeud absent du graphe orienté du profil
+    UnknownNode(i64),
+    /// aucune voie praticable près du point (lat, lon)
+    NoSegment(f64, f64),
+    /// pas de chemin entre les deux noeuds
+    NoRoute(i64, i64),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            EngineError::Load(e) => write!( f, "lecture du fichier impossible : {}", e ),
+            EngineError::UnknownNode(id) => write!( f, "noeud {} absent du graphe", id ),
+            EngineError::NoSegment(lat, lon) => write!( f, "aucune voie près de {},{}", lat, lon ),
+            EngineError::NoRoute(a, b) => write!( f, "pas d'itinéraire entre {} et {}", a, b ),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<osmpbf::Error> for EngineError {
+    fn from(e: osmpbf::Error) -> Self {
+        EngineError::Load(e)
+    }
+}
+
+
+///
+/// grandeur minimisée par le calcul d'itinéraire
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// longueur (m)
+    Distance,
+    /// durée (s)
+    Time,
+    /// longueur pondérée par la préférence du profil pour chaque type de voie
+    Cost,
+}
+
+impl Metric {
+    ///
+    /// grandeur à partir de son nom ("distance", "time" ou "cost")
+    ///
+    pub fn from_name( s: &str ) -> Option<Metric> {
+        match s {
+            "distance" => Some(Metric::Distance),
+            "time" => Some(Metric::Time),
+            "cost" => Some(Metric::Cost),
+            _ => None,
+        }
+    }
+
+    ///
+    /// nom attendu par routing::dijkstra
+    ///
+    pub fn name( &self ) -> &'static str {
+        match self {
+            Metric::Distance => "distance",
+            Metric::Time => "time",
+            Metric::Cost => "cost",
+        }
+    }
+
+    ///
+    /// valeur de la grandeur pour un arc
+    ///
+    pub fn of( &self, e: &Edge ) -> f64 {
+        match self {
+            Metric::Distance => e.distance,
+            Metric::Time => e.time,
+            Metric::Cost => e.cost,
+        }
+    }
+
+    ///
+    /// poids d'un arc pour le calcul d'itinéraire : sa valeur et sa pénalité de routage (cf. Edge::penalty),
+    /// que le coût comprend déjà
+    ///
+    pub fn weight( &self, e: &Edge ) -> f64 {
+        match self {
+            Metric::Distance => e.distance + e.penalty_distance(),
+            Metric::Time => e.time + e.penalty,
+            Metric::Cost => e.cost,
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{}", self.name() )
+    }
+}
+
+
+///
+/// point rattaché à un noeud du graphe orienté d'un profil
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snap {
+    pub node: i64,
+    /// coordonnées du noeud
+    pub lat: f64,
+    pub lon: f64,
+    /// distance (m) entre le point demandé et le noeud
+    pub distance: f64,
+    /// voie du segment le plus proche et son nom (vide si elle n'en a pas)
+    pub way: i64,
+    pub name: String,
+}
+
+///
+/// itinéraire par plusieurs points : les points rattachés au graphe et une étape par couple de points
+///
+#[derive(Debug, Clone, Default)]
+pub struct Itinerary {
+    pub waypoints: Vec<Snap>,
+    pub legs: Vec<Route>,
+    /// voies à éviter (cf. RouteOptions) que l'itinéraire emprunte faute d'alternative
+    pub unsatisfied: Vec<Avoid>,
+}
+
+impl Itinerary {
+    ///
+    /// les étapes mises bout à bout, le noeud commun à deux étapes n'étant compté qu'une fois
+    ///
+    pub fn route( &self ) -> Route {
+        let mut all = Route::default();
+        for leg in self.legs.iter() {
+            let skip = if all.nodes.is_empty() { 0 } else { 1 };
+            all.nodes.extend( leg.nodes.iter().skip( skip ) );
+            all.coords.extend( leg.coords.iter().skip( skip ) );
+            all.edges.extend( leg.edges.iter().cloned() );
+            for w in leg.ways.iter() {
+                if all.ways.last() != Some(w) { all.ways.push( *w ); }
+            }
+            all.distance += leg.distance;
+            all.time += leg.time;
+        }
+        all
+    }
+}
+
+///
+/// options d'un itinéraire : heure de départ (restrictions conditionnelles) et types de voies à éviter
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RouteOptions {
+    pub depart: Option<NaiveDateTime>,
+    pub avoid: Vec<Avoid>,
+}
+
+///
+/// matrice des durées (s) et distances (m) des itinéraires les plus rapides,
+/// None si la destination n'est pas accessible depuis la source
+///
+#[derive(Debug, Clone, Default)]
+pub struct Matrix {
+    pub sources: Vec<Snap>,
+    pub destinations: Vec<Snap>,
+    pub durations: Vec<Vec<Option<f64>>>,
+    pub distances: Vec<Vec<Option<f64>>>,
+}
+
+///
+/// zone accessible en un temps donné depuis un point
+///
+#[derive(Debug, Clone)]
+pub struct Isochrone {
+    pub origin: Snap,
+    /// temps (s) maximal
+    pub limit: f64,
+    /// noeuds atteints : durée (s) et distance (m) depuis l'origine
+    pub reached: HashMap<i64, (f64, f64)>,
+    /// enveloppe convexe (lat, lon) des noeuds atteints, fermée (premier point = dernier point)
+    pub polygon: Vec<(f64, f64)>,
+}
+
+
+// graphe orienté : arcs sortants de chaque noeud
+type Directed = HashMap<i64, Vec<Edge>>;
+
+///
+/// façade du calcul d'itinéraire : le graphe, son index spatial et les graphes orientés de chaque
+/// profil (construits à la première utilisation). Les méthodes renvoient des données, sans affichage,
+/// et peuvent être appelées depuis plusieurs threads.
+///
+/// ```no_run
+/// use route::{Metric, Profile, RoutingEngine};
+///
+/// let engine = RoutingEngine::load( "./data/St_Brieuc-Loudéac.osm.pbf" ).unwrap();
+/// let it = engine.route( Profile::Car, Metric::Time, &[(48.4063, -2.8150), (48.4067, -2.8145)] ).unwrap();
+/// println!( "{:.0} m en {:.0} s", it.route().distance, it.route().time );
+/// ```
+///
+pub struct RoutingEngine {
+    graph: Graph,
+    index: SpatialIndex,
+    directed: [OnceLock<HashMap<i64, Vec<Edge>>>; 3],
+    conditional: [OnceLock<(Directed, Conditions)>; 3],
+    components: [OnceLock<Components>; 3],
+}
+
+impl RoutingEngine {
+    ///
+    /// lecture du fichier osm.pbf
+    ///
+    pub fn load<P: AsRef<Path>>( path: P ) -> Result<Self, EngineError> {
+        let g = load_osm( &path.as_ref().to_string_lossy() )?;
+        Ok( Self::new( g ) )
+    }
+
+    ///
+    /// moteur sur un graphe déjà construit
+    ///
+    pub fn new( graph: Graph ) -> Self {
+        let index = SpatialIndex::new( &graph );
+        Self { graph, index, directed: Default::default(), conditional: Default::default(), components: Default::default() }
+    }
+
+    pub fn graph( &self ) -> &Graph {
+        &self.graph
+    }
+
+    pub fn index( &self ) -> &SpatialIndex {
+        &self.index
+    }
+
+    ///
+    /// map matching sur le graphe orienté du profil et l'index du moteur
+    ///
+    pub fn matcher( &self, profile: Profile ) -> Matcher<'_> {
+        Matcher::new( &self.graph, self.directed( profile ), &self.index )
+    }
+
+    ///
+    /// graphe orienté du profil (cf. Graph::get_directed_for)
+    ///
+    pub fn directed( &self, profile: Profile ) -> &HashMap<i64, Vec<Edge>> {
+        let i = PROFILES.iter().position( |p| *p == profile ).unwrap();
+        self.directed[i].get_or_init( || self.graph.get_directed_for( &profile ) )
+    }
+
+    ///
+    /// moteur sans les îlots de moins de `min` noeuds (cf. components::prune_islands),
+    /// et le nombre de ways supprimées
+    ///
+    pub fn prune_islands( self, min: usize ) -> (Self, usize) {
+        let mut graph = self.graph;
+        let removed = prune_islands( &mut graph, min );
+        ( Self::new( graph ), removed )
+    }
+
+    ///
+    /// moteur dont les noeuds ont l'altitude du modèle numérique de terrain (cf. elevation::set_elevation) :
+    /// les graphes orientés vélo et piéton tiennent compte de la pente. Renvoie aussi le nombre de noeuds
+    /// couverts par le MNT
+    ///
+    pub fn with_elevation( self, dem: &Dem ) -> (Self, usize) {
+        let mut graph = self.graph;
+        let count = set_elevation( &mut graph, dem );
+        ( Self::new( graph ), count )
+    }
+
+    ///
+    /// moteur dont les longueurs des arcs et les distances au point le plus proche sont calculées
+    /// avec la formule donnée (cf. Graph::set_metric)
+    ///
+    pub fn with_metric( self, metric: DistanceMetric ) -> Self {
+        let mut graph = self.graph;
+        graph.set_metric( metric );
+        Self::new( graph )
+    }
+
+    ///
+    /// composantes fortement connexes du graphe orienté du profil
+    ///
+    pub fn components( &self, profile: Profile ) -> &Components {
+        let i = PROFILES.iter().position( |p| *p == profile ).unwrap();
+        self.components[i].get_or_init( || Components::new( self.directed( profile ) ) )
+    }
+
+    ///
+    /// graphe orienté du profil avec les voies à restrictions conditionnelles dans les deux sens,
+    /// et ces voies (cf. Graph::get_conditional_for)
+    ///
+    pub fn conditional( &self, profile: Profile ) -> &(Directed, Conditions) {
+        let i = PROFILES.iter().position( |p| *p == profile ).unwrap();
+        self.conditional[i].get_or_init( || ( self.graph.get_conditional_for( &profile ), Conditions::new( &self.graph, profile ) ) )
+    }
+
+    ///
+    /// noeud routable le plus proche du point : extrémité la plus proche du segment le plus proche,
+    /// dans un rayon élargi de 100 m à 12,8 km. Un noeud de la composante principale est préféré
+    /// à un îlot (parking privé, impasse à sens unique, ...) s'il est trouvé en élargissant
+    /// une fois de plus le rayon
+    ///
+    pub fn nearest( &self, profile: Profile, lat: f64, lon: f64 ) -> Option<Snap> {
+        let adj = self.directed( profile );
+        let components = self.components( profile );
+        let node = |p: &Projection| if p.frac < 0.5 { p.segment.from } else { p.segment.to };
+        let mut radius = SNAP;
+        let mut island: Option<(i64, Projection)> = None;
+        while radius <= MAX_SNAP {
+            let found: Vec<(i64, Projection)> = self.index.within( lat, lon, radius ).into_iter()
+                .map( |p| (node( &p ), p) )
+                .filter( |(n, _)| adj.contains_key( n ) )
+                .collect();
+            if let Some((n, p)) = found.iter().find( |(n, _)| components.is_main( *n ) ) { return Some( self.snap( lat, lon, *n, p ) ); }
+            if island.is_some() { break; }
+            island = found.first().copied();
+            radius *= 4.0;
+        }
+        island.map( |(n, p)| self.snap( lat, lon, n, &p ) )
+    }
+
+    fn snap( &self, lat: f64, lon: f64, node: i64, p: &Projection ) -> Snap {
+        let n = &self.graph.tnodes[&node];
+        Snap {
+            node, lat: n.lat(), lon: n.lon(),
+            distance: self.graph.metric().distance( lat, lon, n.lat(), n.lon() ),
+            way: p.segment.way,
+            name: Route::way_name( &self.graph, p.segment.way ).unwrap_or_default(),
+        }
+    }
+
+    ///
+    /// au plus `number` segments praticables les plus proches du point, du plus proche au plus éloigné
+    ///
+    pub fn nearest_segments( &self, profile: Profile, lat: f64, lon: f64, number: usize ) -> Vec<Projection> {
+        let adj = self.directed( profile );
+        let mut radius = SNAP;
+        let mut found = Vec::new();
+        while found.len() < number && radius <= MAX_SNAP {
+            found = self.index.within( lat, lon, radius ).into_iter()
+                .filter( |p| adj.contains_key( &p.segment.from ) || adj.contains_key( &p.segment.to ) )
+                .take( number )
+                .collect();
+            radius *= 4.0;
+        }
+        found
+    }
+
+    ///
+    /// itinéraire entre deux noeuds du graphe
+    ///
+    pub fn route_nodes( &self, profile: Profile, metric: Metric, from: i64, to: i64 ) -> Result<Route, EngineError> {
+        let adj = self.directed( profile );
+        for n in [from, to] {
+            if !adj.contains_key( &n ) { return Err( EngineError::UnknownNode( n ) ); }
+        }
+        // les arêtes retenues par dijkstra, pas celles retrouvées entre noeuds successifs (cf. Route::from_nodes)
+        let edges = shortest_path_at( metric.name(), adj, from, to, |_, e, _| Some( e.clone() ) )
+            .ok_or( EngineError::NoRoute( from, to ) )?;
+        Ok( Route::from_edges( &self.graph, from, edges ) )
+    }
+
+    ///
+    /// itinéraire entre deux noeuds pour un départ à l'heure donnée : les restrictions conditionnelles
+    /// (access, oneway, maxspeed, ... :conditional) sont celles en vigueur à l'heure d'arrivée sur chaque voie
+    ///
+    pub fn route_nodes_at( &self, profile: Profile, metric: Metric, from: i64, to: i64, departure: NaiveDateTime ) -> Result<Route, EngineError> {
+        self.route_nodes_with( profile, metric, from, to, &RouteOptions { depart: Some(departure), ..Default::default() } )
+    }
+
+    ///
+    /// itinéraire entre deux noeuds suivant les options : heure de départ et voies à éviter. Les voies
+    /// à éviter sont exclues, ou seulement pénalisées si l'itinéraire ne peut pas s'en passer
+    /// (cf. Avoid::unsatisfied pour savoir lesquelles ont été empruntées)
+    ///
+    pub fn route_nodes_with( &self, profile: Profile, metric: Metric, from: i64, to: i64, options: &RouteOptions ) -> Result<Route, EngineError> {
+        let (adj, conditions) = match options.depart {
+            Some(_) => { let (adj, c) = self.conditional( profile ); ( adj, Some(c).filter( |c| !c.is_empty() ) ) },
+            None => ( self.directed( profile ), None ),
+        };
+        if conditions.is_none() && options.avoid.is_empty() { return self.route_nodes( profile, metric, from, to ); }
+        for n in [from, to] {
+            if !adj.contains_key( &n ) { return Err( EngineError::UnknownNode( n ) ); }
+        }
+
+        let at = |node: i64, e: &Edge, elapsed: f64| match ( conditions, options.depart ) {
+            ( Some(c), Some(departure) ) => c.edge_at( &self.graph, node, e, departure + Duration::milliseconds( (elapsed * 1000.0) as i64 ) ),
+            _ => Some( e.clone() ),
+        };
+        let avoided = |e: &Edge| Avoid::any( &options.avoid, &self.graph, e.way );
+        let edges = shortest_path_at( metric.name(), adj, from, to, |n, e, t| at( n, e, t ).filter( |e| !avoided( e ) ) )
+            .or_else( || if options.avoid.is_empty() { None } else {
+                shortest_path_with( metric.name(), adj, from, to, at, |e| if avoided( e ) { AVOID_PENALTY } else { 1.0 } )
+            } )
+            .ok_or( EngineError::NoRoute( from, to ) )?;
+        Ok( Route::from_edges( &self.graph, from, edges ) )
+    }
+
+    ///
+    /// itinéraire passant par les points (lat, lon) dans l'ordre
+    ///
+    pub fn route( &self, profile: Profile, metric: Metric, points: &[(f64, f64)] ) -> Result<Itinerary, EngineError> {
+        self.route_with( profile, metric, points, &RouteOptions::default() )
+    }
+
+    ///
+    /// itinéraire passant par les points dans l'ordre pour un départ à l'heure donnée,
+    /// chaque étape partant à l'heure d'arrivée de la précédente
+    ///
+    pub fn route_at( &self, profile: Profile, metric: Metric, points: &[(f64, f64)], departure: NaiveDateTime ) -> Result<Itinerary, EngineError> {
+        self.route_with( profile, metric, points, &RouteOptions { depart: Some(departure), ..Default::default() } )
+    }
+
+    ///
+    /// itinéraire passant par les points dans l'ordre suivant les options ; les voies à éviter
+    /// qui n'ont pu l'être sont données dans Itinerary::unsatisfied
+    ///
+    pub fn route_with( &self, profile: Profile, metric: Metric, points: &[(f64, f64)], options: &RouteOptions ) -> Result<Itinerary, EngineError> {
+        self.route_snapped( profile, metric, self.snap_all( profile, points )?, options )
+    }
+
+    ///
+    /// itinéraire passant dans l'ordre par des points déjà rattachés au graphe (cf. snap_each)
+    ///
+    pub fn route_snapped( &self, profile: Profile, metric: Metric, waypoints: Vec<Snap>, options: &RouteOptions ) -> Result<Itinerary, EngineError> {
+        let mut legs = Vec::with_capacity( waypoints.len() );
+        let mut leg_options = options.clone();
+        for pair in waypoints.windows( 2 ) {
+            let leg = self.route_nodes_with( profile, metric, pair[0].node, pair[1].node, &leg_options )?;
+            leg_options.depart = leg_options.depart.map( |d| d + Duration::milliseconds( (leg.time * 1000.0) as i64 ) );
+            legs.push( leg );
+        }
+        let mut it = Itinerary { waypoints, legs, unsatisfied: Vec::new() };
+        it.unsatisfied = Avoid::unsatisfied( &options.avoid, &self.graph, &it.route() );
+        Ok(it)
+    }
+
+    ///
+    /// durées et distances des itinéraires les plus rapides de chaque source vers chaque destination
+    ///
+    pub fn matrix( &self, profile: Profile, sources: &[(f64, f64)], destinations: &[(f64, f64)] ) -> Result<Matrix, EngineError> {
+        let adj = self.directed( profile );
+        let mut m = Matrix { sources: self.snap_all( profile, sources )?, destinations: self.snap_all( profile, destinations )?, ..Default::default() };
+        for s in m.sources.iter() {
+            let reached = one_to_all( Metric::Time.name(), adj, s.node, f64::INFINITY );
+            m.durations.push( m.destinations.iter().map( |d| reached.get( &d.node ).map( |c| c.0 ) ).collect() );
+            m.distances.push( m.destinations.iter().map( |d| reached.get( &d.node ).map( |c| c.1 ) ).collect() );
+        }
+        Ok(m)
+    }
+
+    ///
+    /// noeuds accessibles en moins de `seconds` secondes depuis le point, et leur enveloppe convexe
+    ///
+    pub fn isochrone( &self, profile: Profile, lat: f64, lon: f64, seconds: f64 ) -> Result<Isochrone, EngineError> {
+        let origin = self.nearest( profile, lat, lon ).ok_or( EngineError::NoSegment( lat, lon ) )?;
+        let reached = one_to_all( Metric::Time.name(), self.directed( profile ), origin.node, seconds );
+        let points: Vec<(f64, f64)> = reached.keys()
+            .filter_map( |n| self.graph.tnodes.get( n ) )
+            .map( |n| (n.lat(), n.lon()) )
+            .collect();
+        Ok( Isochrone { origin, limit: seconds, reached, polygon: convex_hull( points ) } )
+    }
+
+    fn snap_all( &self, profile: Profile, points: &[(f64, f64)] ) -> Result<Vec<Snap>, EngineError> {
+        self.snap_each( profile, points ).into_iter().collect()
+    }
+
+    ///
+    /// rattachement de chaque point au graphe du profil, avec une erreur par point hors du réseau
+    ///
+    pub fn snap_each( &self, profile: Profile, points: &[(f64, f64)] ) -> Vec<Result<Snap, EngineError>> {
+        points.iter()
+            .map( |(lat, lon)| self.nearest( profile, *lat, *lon ).ok_or( EngineError::NoSegment( *lat, *lon ) ) )
+            .collect()
+    }
+}
+
+
+///
+/// enveloppe convexe (parcours de Andrew) d'une liste de (lat, lon), fermée
+///
+pub fn convex_hull( mut points: Vec<(f64, f64)> ) -> Vec<(f64, f64)> {
+    points.sort_by( |a, b| a.0.total_cmp( &b.0 ).then( a.1.total_cmp( &b.1 ) ) );
+    points.dedup();
+    if points.len() < 3 {
+        if let Some(first) = points.first().copied() { points.push( first ); }
+        return points;
+    }
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.1 - o.1) * (b.0 - o.0) - (a.0 - o.0) * (b.1 - o.1);
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity( points.len() * 2 );
+    // partie basse puis partie haute, en x = longitude et y = latitude
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Box<dyn Iterator<Item = &(f64, f64)>> = if pass == 0 { Box::new( points.iter() ) } else { Box::new( points.iter().rev() ) };
+        for p in iter {
+            while hull.len() >= start + 2 && cross( hull[hull.len()-2], hull[hull.len()-1], *p ) <= 0.0 { hull.pop(); }
+            hull.push( *p );
+        }
+        hull.pop();
+    }
+    hull.push( hull[0] );
+    hull
+}
+
+
+#[cfg(test)]
+mod engine_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    // rue A vers l'est (1 -> 2 -> 3), rue B vers le nord (3 -> 4), un chemin piéton 4 -> 5, une rue isolée 6 -> 7
+    fn town() -> RoutingEngine {
+        RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99), (4, 48.005, -1.99), (5, 48.01, -1.99),
+                                          (6, 48.1, -2.0), (7, 48.1, -1.99)],
+                                        &[(10, &[1, 2, 3], &[("highway", "residential"), ("name", "Rue A")]),
+                                          (11, &[3, 4], &[("highway", "residential"), ("name", "Rue B")]),
+                                          (12, &[4, 5], &[("highway", "footway")]),
+                                          (13, &[6, 7], &[("highway", "residential"), ("name", "Rue C")])] ) )
+    }
+
+    #[test]
+    fn nearest_and_route() {
+        let e = town();
+        let s = e.nearest( Profile::Car, 48.0001, -1.9949 ).unwrap();
+        assert_eq!( (2, 10, "Rue A".to_string()), (s.node, s.way, s.name) );
+        // le chemin piéton n'est pas praticable en voiture
+        assert_eq!( 4, e.nearest( Profile::Car, 48.01, -1.99 ).unwrap().node );
+        assert_eq!( 5, e.nearest( Profile::Foot, 48.01, -1.99 ).unwrap().node );
+        assert!( e.nearest( Profile::Car, 40.0, 10.0 ).is_none() );
+
+        let it = e.route( Profile::Foot, Metric::Distance, &[(48.0, -2.0), (48.01, -1.99), (48.0, -1.99)] ).unwrap();
+        assert_eq!( 2, it.legs.len() );
+        let r = it.route();
+        assert_eq!( vec![1, 2, 3, 4, 5, 4, 3], r.nodes );
+        assert_eq!( vec![10, 11, 12, 11], r.ways );
+        assert!( ( r.distance - it.legs[0].distance - it.legs[1].distance ).abs() < 1e-9 );
+
+        assert!( matches!( e.route( Profile::Car, Metric::Time, &[(48.0, -2.0), (48.1, -2.0)] ), Err(EngineError::NoRoute(1, 6)) ) );
+        assert!( matches!( e.route_nodes( Profile::Car, Metric::Time, 1, 5 ), Err(EngineError::UnknownNode(5)) ) );
+        assert!( matches!( e.route( Profile::Car, Metric::Time, &[(48.0, -2.0), (40.0, 10.0)] ), Err(EngineError::NoSegment(..)) ) );
+        assert_eq!( Some(Metric::Cost), Metric::from_name( "cost" ) );
+    }
+
+    #[test]
+    fn parallel_ways() {
+        // deux voies entre les mêmes noeuds : l'itinéraire garde l'arête choisie par dijkstra
+        let e = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99)],
+                                                &[(20, &[1, 2], &[("highway", "residential")]), (21, &[1, 2], &[("highway", "primary")])] ) );
+        let fastest = e.directed( Profile::Car )[&1].iter().map( |edge| edge.time ).fold( f64::INFINITY, f64::min );
+        let r = e.route_nodes( Profile::Car, Metric::Time, 1, 2 ).unwrap();
+        assert_eq!( vec![21], r.ways );
+        assert!( ( r.time - fastest ).abs() < 1e-9 );
+        let r = e.route_nodes( Profile::Car, Metric::Time, 1, 1 ).unwrap();
+        assert!( r.edges.is_empty() && r.nodes == vec![1] );
+    }
+
+    #[test]
+    fn matrix_and_isochrone() {
+        let e = town();
+        let m = e.matrix( Profile::Car, &[(48.0, -2.0)], &[(48.0, -1.99), (48.1, -2.0)] ).unwrap();
+        let d = e.graph().tways[&10].len();
+        assert!( ( m.distances[0][0].unwrap() - d ).abs() < 1e-6 );
+        assert!( ( m.durations[0][0].unwrap() - d / (50.0 / 3.6) ).abs() < 1e-6 );
+        assert_eq!( None, m.durations[0][1] );
+
+        // rues A et B : 742 m puis 556 m, soit 93 s à 50 km/h ; le noeud 2 est sur le bord 1 - 3
+        let iso = e.isochrone( Profile::Car, 48.0, -2.0, 100.0 ).unwrap();
+        assert_eq!( 1, iso.origin.node );
+        let mut reached: Vec<i64> = iso.reached.keys().copied().collect();
+        reached.sort();
+        assert_eq!( vec![1, 2, 3, 4], reached );
+        assert_eq!( 4, iso.polygon.len() );
+        assert_eq!( iso.polygon.first(), iso.polygon.last() );
+    }
+
+    #[test]
+    fn conditional_route() {
+        // 1 -> 2 (Rue A, 54 s), puis 2 -> 3 par la rue de l'école fermée aux heures d'entrée
+        // et limitée à 30 km/h l'après-midi, ou par le détour 2 -> 4 -> 3
+        let e = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.0, -1.98), (4, 48.005, -1.985)],
+                                                &[(10, &[1, 2], &[("highway", "residential")]),
+                                                  (11, &[2, 3], &[("highway", "residential"),
+                                                      ("access:conditional", "no @ (Mo-Fr 07:30-09:00)"), ("maxspeed:conditional", "30 @ (Mo-Fr 16:00-18:00)")]),
+                                                  (12, &[2, 4, 3], &[("highway", "residential")])] ) );
+
+        // lundi 19 octobre 2026
+        let monday = |hms: &str| NaiveDateTime::parse_from_str( &format!( "2026-10-19 {}", hms ), "%Y-%m-%d %H:%M:%S" ).unwrap();
+        let nodes = |at: &str| e.route_nodes_at( Profile::Car, Metric::Time, 1, 3, monday( at ) ).unwrap().nodes;
+        assert_eq!( vec![1, 2, 3], nodes( "10:00:00" ) );
+        assert_eq!( vec![1, 2, 4, 3], nodes( "08:00:00" ) );
+        // parti avant la fermeture, arrivé à l'école après 07:30
+        assert_eq!( vec![1, 2, 4, 3], nodes( "07:29:30" ) );
+        assert_eq!( vec![1, 2, 3], nodes( "07:28:30" ) );
+
+        let d = e.graph().tways[&11].len();
+        let time = |at: &str| e.route_nodes_at( Profile::Car, Metric::Distance, 2, 3, monday( at ) ).unwrap().time;
+        assert!( ( time( "10:00:00" ) - d / (50.0 / 3.6) ).abs() < 1e-6 );
+        assert!( ( time( "17:00:00" ) - d / (30.0 / 3.6) ).abs() < 1e-6 );
+        assert!( matches!( e.route_nodes_at( Profile::Car, Metric::Time, 2, 3, monday( "08:00:00" ) ).map( |r| r.nodes ), Ok(n) if n == vec![2, 4, 3] ) );
+        // sans heure de départ, les voies conditionnelles gardent leurs tags de base
+        assert_eq!( vec![1, 2, 3], e.route_nodes( Profile::Car, Metric::Time, 1, 3 ).unwrap().nodes );
+    }
+
+    #[test]
+    fn avoid_route() {
+        // autoroute à péage 1 -> 2 -> 3, route départementale 1 -> 4 -> 3, puis un tunnel 3 -> 5 sans alternative
+        let e = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.0, -1.98), (4, 48.005, -1.99), (5, 48.0, -1.97)],
+                                                &[(10, &[1, 2, 3], &[("highway", "motorway"), ("toll", "yes")]),
+                                                  (11, &[1, 4, 3], &[("highway", "secondary")]),
+                                                  (12, &[3, 5], &[("highway", "secondary"), ("tunnel", "yes")])] ) );
+        let points = [(48.0, -2.0), (48.0, -1.98)];
+
+        assert_eq!( vec![1, 2, 3], e.route( Profile::Car, Metric::Time, &points ).unwrap().route().nodes );
+        let options = RouteOptions { avoid: vec![Avoid::Toll, Avoid::Bridge], ..Default::default() };
+        let it = e.route_with( Profile::Car, Metric::Time, &points, &options ).unwrap();
+        assert_eq!( vec![1, 4, 3], it.route().nodes );
+        assert!( it.unsatisfied.is_empty() );
+
+        // le tunnel est emprunté faute d'alternative, et signalé
+        let options = RouteOptions { avoid: vec![Avoid::Motorway, Avoid::Tunnel], ..Default::default() };
+        let it = e.route_with( Profile::Car, Metric::Time, &[(48.0, -2.0), (48.0, -1.97)], &options ).unwrap();
+        assert_eq!( vec![1, 4, 3, 5], it.route().nodes );
+        assert_eq!( vec![Avoid::Tunnel], it.unsatisfied );
+        let r = e.route_nodes_with( Profile::Car, Metric::Distance, 1, 5, &options ).unwrap();
+        assert!( ( r.distance - e.graph().tways[&11].len() - e.graph().tways[&12].len() ).abs() < 1e-6 );
+    }
+
+    #[test]
+    fn snap_to_main_component() {
+        // parking isolé au sud, rue principale 1,1 km plus au nord
+        let e = RoutingEngine::new( test_graph( &[(1, 48.01, -2.004), (2, 48.01, -1.996), (3, 48.014, -1.996), (8, 48.0, -2.0005), (9, 48.0, -1.9995)],
+                                                &[(10, &[1, 2, 3], &[("highway", "residential")]), (20, &[8, 9], &[("highway", "service")])] ) );
+
+        let c = e.components( Profile::Car );
+        assert_eq!( (2, 3), (c.len(), c.main_size()) );
+        assert!( !c.is_main( 8 ) );
+        // parking à 450 m, rue à 670 m : trouvés dans le même rayon, la rue est préférée
+        assert_eq!( 1, e.nearest( Profile::Car, 48.004, -2.001 ).unwrap().node );
+        // sans la rue dans le rayon suivant, le parking reste accessible
+        assert_eq!( 9, e.nearest( Profile::Car, 48.0, -1.9994 ).unwrap().node );
+
+        let (e, removed) = e.prune_islands( 3 );
+        assert_eq!( 1, removed );
+        assert_eq!( 1, e.components( Profile::Car ).len() );
+    }
+
+    #[test]
+    fn hull() {
+        let square = vec![(0.0, 0.0), (1.0, 0.0), (0.5, 0.5), (1.0, 1.0), (0.0, 1.0), (0.2, 0.7)];
+        let h = convex_hull( square );
+        assert_eq!( 5, h.len() );
+        assert!( !h.contains( &(0.5, 0.5) ) && !h.contains( &(0.2, 0.7) ) );
+        assert_eq!( vec![(1.0, 2.0), (1.0, 2.0)], convex_hull( vec![(1.0, 2.0)] ) );
+        assert!( convex_hull( Vec::new() ).is_empty() );
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+use std::path::Path;
+
+use gpx::errors::GpxError;
+
+pub mod geojson;
+pub mod gpx_file;
+pub mod html;
+pub mod output;
+pub mod polyline;
+pub mod render;
+
+
+#[derive(Debug)]
+pub enum ExportError {
+    Io(std::io::Error),
+    Gpx(GpxError),
+    Json(serde_json::Error),
+    Image(String),
+    Unsupported(String),
+    MissingNode(i64),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            ExportError::Io(e) => write!( f, "écriture impossible : {}", e ),
+            ExportError::Gpx(e) => write!( f, "erreur gpx : {}", e ),
+            ExportError::Json(e) => write!( f, "erreur json : {}", e ),
+            ExportError::Image(s) => write!( f, "erreur image : {}", s ),
+            ExportError::Unsupported(s) => write!( f, "format non géré : {}", s ),
+            ExportError::MissingNode(id) => write!( f, "noeud {} absent du graphe", id ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        ExportError::Io(e)
+    }
+}
+
+impl From<GpxError> for ExportError {
+    fn from(e: GpxError) -> Self {
+        ExportError::Gpx(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        ExportError::Json(e)
+    }
+}
+
+
+///
+/// format de sortie
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Gpx,
+    GeoJson,
+    Html,
+    /// encoded polyline (Google) avec 5 ou 6 décimales
+    Polyline(u32),
+}
+
+impl Format {
+    ///
+    /// "gpx", "geojson", "html", "polyline" ou "polyline6"
+    ///
+    pub fn from_name( name: &str ) -> Option<Format> {
+        match name {
+            "gpx" => Some( Format::Gpx ),
+            "geojson" => Some( Format::GeoJson ),
+            "html" => Some( Format::Html ),
+            "polyline" => Some( Format::Polyline(5) ),
+            "polyline6" => Some( Format::Polyline(6) ),
+            _ => None,
+        }
+    }
+
+    ///
+    /// format déduit de l'extension du fichier (".gpx", ".geojson" ou ".json", ".html" ou ".htm",
+    /// ".polyline", ".polyline6"), les autres sont refusées
+    ///
+    pub fn of( path: &Path ) -> Result<Format, ExportError> {
+        match path.extension().and_then( |e| e.to_str() ) {
+            Some("json") => Some( Format::GeoJson ),
+            Some("htm") => Some( Format::Html ),
+            Some(e) => Format::from_name( e ),
+            None => None,
+        }.ok_or( ExportError::Unsupported( format!( "{} (.gpx, .geojson, .html, .polyline ou .polyline6)", path.display() ) ) )
+    }
+}
+
+
+#[cfg(test)]
+mod export_tests {
+    use super::*;
+
+    #[test]
+    fn format_from_extension() {
+        let of = |p: &str| Format::of( Path::new( p ) ).ok();
+        assert_eq!( Some( Format::Gpx ), of( "./data/trace.gpx" ) );
+        assert_eq!( Some( Format::GeoJson ), of( "./data/trace.geojson" ) );
+        assert_eq!( Some( Format::GeoJson ), of( "route.json" ) );
+        assert_eq!( Some( Format::Html ), of( "./data/route.html" ) );
+        assert_eq!( Some( Format::Html ), of( "./data/route.htm" ) );
+        assert_eq!( Some( Format::Polyline(5) ), of( "route.polyline" ) );
+        assert_eq!( Some( Format::Polyline(6) ), Format::from_name( "polyline6" ) );
+        // extension inconnue ou absente
+        assert!( matches!( Format::of( Path::new( "route.kml" ) ), Err( ExportError::Unsupported(_) ) ) );
+        assert_eq!( None, of( "./data/route" ) );
+    }
+}
@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::path::Path;
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::engine::Isochrone;
+use crate::export::ExportError;
+use crate::graphe::Graph;
+use crate::graphe::elements::{TNode, TWay};
+use crate::graphe::validate::Report;
+use crate::routing::route::Route;
+
+
+///
+/// position GeoJSON : [lon, lat]
+///
+fn position( lat: f64, lon: f64 ) -> Value {
+    json!( [lon, lat] )
+}
+
+fn tags( t: &HashMap<String, String> ) -> Value {
+    json!( t )
+}
+
+///
+/// collection de features
+///
+pub fn collection( features: Vec<Value> ) -> Value {
+    json!( { "type": "FeatureCollection", "features": features } )
+}
+
+///
+/// un point avec ses propriétés
+///
+pub fn point( lat: f64, lon: f64, properties: Value ) -> Value {
+    json!( {
+        "type": "Feature",
+        "geometry": { "type": "Point", "coordinates": position( lat, lon ) },
+        "properties": properties,
+    } )
+}
+
+///
+/// une ligne (liste de (lat, lon)) avec ses propriétés
+///
+pub fn line( coords: &[(f64, f64)], properties: Value ) -> Value {
+    let coordinates: Vec<Value> = coords.iter().map( |(lat, lon)| position( *lat, *lon ) ).collect();
+    json!( {
+        "type": "Feature",
+        "geometry": { "type": "LineString", "coordinates": coordinates },
+        "properties": properties,
+    } )
+}
+
+///
+/// un polygone (anneau fermé de (lat, lon)) avec ses propriétés
+///
+pub fn polygon( ring: &[(f64, f64)], properties: Value ) -> Value {
+    let coordinates: Vec<Value> = ring.iter().map( |(lat, lon)| position( *lat, *lon ) ).collect();
+    json!( {
+        "type": "Feature",
+        "geometry": { "type": "Polygon", "coordinates": [coordinates] },
+        "properties": properties,
+    } )
+}
+
+///
+/// un TNode sous forme de feature Point
+///
+pub fn node_feature( id: i64, n: &TNode ) -> Value {
+    point( n.lat(), n.lon(), json!( { "id": id, "tags": tags( n.tags() ), "ways": n.ways() } ) )
+}
+
+///
+/// une TWay sous forme de feature LineString
+///
+pub fn way_feature( g: &Graph, id: i64, w: &TWay ) -> Value {
+    let coords: Vec<(f64, f64)> = w.refs().iter()
+        .filter_map( |n| g.tnodes.get( n ) )
+        .map( |n| (n.lat(), n.lon()) )
+        .collect();
+    line( &coords, json!( { "id": id, "length": w.len(), "tags": tags( w.tags() ), "nodes": w.refs() } ) )
+}
+
+///
+/// un itinéraire : LineString avec distance (m), temps (s) et ways empruntées (traversées en bac signalées)
+///
+pub fn route_feature( g: &Graph, r: &Route ) -> Value {
+    let ways: Vec<Value> = r.ways.iter()
+        .map( |w| json!( { "id": w, "name": Route::way_name( g, *w ), "ferry": Route::is_ferry( g, *w ) } ) )
+        .collect();
+    line( &r.coords, json!( {
+        "distance": r.distance,
+        "time": r.time,
+        "ways": ways,
+        "nodes": r.nodes,
+    } ) )
+}
+
+///
+/// sélection de TNode (cf. commande show)
+///
+pub fn nodes( g: &Graph, ids: &[i64] ) -> Value {
+    collection( ids.iter().filter_map( |id| g.tnodes.get( id ).map( |n| node_feature( *id, n ) ) ).collect() )
+}
+
+///
+/// sélection de TWay (cf. commande show)
+///
+pub fn ways( g: &Graph, ids: &[i64] ) -> Value {
+    collection( ids.iter().filter_map( |id| g.tways.get( id ).map( |w| way_feature( g, *id, w ) ) ).collect() )
+}
+
+///
+/// l'ensemble des voies du graphe filtré
+///
+pub fn graph( g: &Graph ) -> Value {
+    collection( g.tways.iter().map( |(id, w)| way_feature( g, *id, w ) ).collect() )
+}
+
+///
+/// un itinéraire seul dans une collection
+///
+pub fn route( g: &Graph, r: &Route ) -> Value {
+    collection( vec![route_feature( g, r )] )
+}
+
+///
+/// une isochrone : son enveloppe et son point de départ
+///
+pub fn isochrone( iso: &Isochrone ) -> Value {
+    collection( vec![
+        polygon( &iso.polygon, json!( { "limit": iso.limit, "nodes": iso.reached.len() } ) ),
+        point( iso.origin.lat, iso.origin.lon, json!( { "id": iso.origin.node, "name": iso.origin.name } ) ),
+    ] )
+}
+
+///
+/// les problèmes d'un rapport de validation situés par une position
+///
+pub fn report( r: &Report ) -> Value {
+    collection( r.problems.iter()
+        .filter_map( |p| p.position.map( |(lat, lon)| point( lat, lon, json!( {
+            "check": p.check.to_string(),
+            "way": p.way,
+            "node": p.node,
+            "detail": p.detail,
+        } ) ) ) )
+        .collect() )
+}
+
+///
+/// écriture du document GeoJSON dans un fichier
+///
+pub fn save( value: &Value, filename: &Path ) -> Result<(), ExportError> {
+    let f = File::create( filename )?;
+    serde_json::to_writer_pretty( f, value )?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod geojson_tests {
+    use super::*;
+    use crate::graphe::reader::read_osm;
+
+    #[test]
+    fn export_ways_and_nodes() {
+        let g = read_osm( "./data/St_Brieuc-Loudéac.osm.pbf" );
+        let w = ways( &g, &[863113118, 42] );
+
+        assert_eq!( "FeatureCollection", w["type"] );
+        assert_eq!( 1, w["features"].as_array().unwrap().len() );
+        let f = &w["features"][0];
+        assert_eq!( "LineString", f["geometry"]["type"] );
+        assert_eq!( 35, f["geometry"]["coordinates"].as_array().unwrap().len() );
+        assert_eq!( "primary", f["properties"]["tags"]["highway"] );
+
+        let n = nodes( &g, &[2403564257] );
+        let p = &n["features"][0]["geometry"];
+        assert_eq!( "Point", p["type"] );
+        assert_eq!( g.tnodes[&2403564257].lon(), p["coordinates"][0] );
+        assert_eq!( g.tnodes[&2403564257].lat(), p["coordinates"][1] );
+
+        assert_eq!( g.tways.len(), graph( &g )["features"].as_array().unwrap().len() );
+    }
+
+    #[test]
+    fn export_route() {
+        let r = Route { nodes: vec![1, 2], coords: vec![(48.0, -2.0), (48.0, -1.99)], distance: 742.0, time: 53.4, ways: vec![7], edges: Vec::new() };
+        let g = Graph::new( "test".to_string(), HashMap::new(), HashMap::new() );
+        let f = &route( &g, &r )["features"][0];
+
+        assert_eq!( json!( [[-2.0, 48.0], [-1.99, 48.0]] ), f["geometry"]["coordinates"] );
+        assert_eq!( 742.0, f["properties"]["distance"] );
+        assert_eq!( 53.4, f["properties"]["time"] );
+        assert_eq!( 7, f["properties"]["ways"][0]["id"] );
+        assert_eq!( Value::Null, f["properties"]["ways"][0]["name"] );
+    }
+}
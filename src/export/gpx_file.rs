@@ -0,0 +1,195 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{Duration, Local, NaiveDateTime, TimeZone};
+use gpx::{Gpx,GpxVersion,Metadata,Time,Track,TrackSegment, Waypoint,write};
+use geo_types::{Point, Rect, coord};
+use time::OffsetDateTime;
+
+use crate::export::ExportError;
+use crate::export::html::{distance, duration};
+use crate::graphe::Graph;
+use crate::routing::instructions::instructions;
+use crate::routing::route::Route;
+
+
+///
+/// contenu du gpx d'un itinéraire, en plus de la trace
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpxOptions {
+    /// nom du document et de la trace ("Itinéraire" par défaut)
+    pub name: Option<String>,
+    /// heure (locale) de départ : les points sont horodatés d'après la durée de parcours
+    pub depart: Option<NaiveDateTime>,
+    /// <rte> dont les <rtept> sont les manœuvres, nommées par leur libellé
+    pub instructions: bool,
+    /// <wpt> de départ, d'arrivée et des étapes
+    pub waypoints: bool,
+    /// noeuds des étapes intermédiaires
+    pub vias: Vec<i64>,
+}
+
+
+///
+/// enregistrer la liste des noeuds sous forme de trace gpx
+///
+pub fn save_nodes( g: &Graph, nodes: &[i64], filename: &Path ) -> Result<(), ExportError> {
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for v in nodes.iter() {
+        let n = g.tnodes.get( v ).ok_or( ExportError::MissingNode( *v ) )?;
+        points.push( (n.lat(), n.lon()) );
+    }
+    save_points( &points, filename )
+}
+
+///
+/// enregistrer un itinéraire sous forme de trace gpx
+///
+pub fn save_route( g: &Graph, route: &Route, filename: &Path ) -> Result<(), ExportError> {
+    write_route( g, route, File::create( filename )? )
+}
+
+///
+/// écrire un itinéraire sous forme de trace gpx : une trace par portion, les traversées en bac
+/// étant des traces de type "ferry" portant le nom de la liaison ; les points ont l'altitude des
+/// noeuds (<ele>) quand elle est connue
+///
+pub fn write_route<W: Write>( g: &Graph, route: &Route, w: W ) -> Result<(), ExportError> {
+    write_route_with( g, route, &GpxOptions::default(), w )
+}
+
+///
+/// écrire un itinéraire en gpx suivant les options : trace nommée avec la distance et la durée en
+/// description, points horodatés depuis l'heure de départ, manœuvres en <rte> et <wpt> de départ,
+/// d'arrivée et des étapes
+///
+pub fn write_route_with<W: Write>( g: &Graph, route: &Route, options: &GpxOptions, w: W ) -> Result<(), ExportError> {
+    let elevations: Vec<Option<f64>> = route.nodes.iter().map( |n| g.tnodes.get( n ).and_then( |n| n.ele() ) ).collect();
+    // instant de passage à chaque point
+    let mut elapsed: Vec<f64> = vec![0.0];
+    for i in 1..route.coords.len() {
+        elapsed.push( elapsed[i - 1] + route.edges.get( i - 1 ).map( |e| e.time ).unwrap_or( 0.0 ) );
+    }
+    let at = |i: usize| options.depart.and_then( |d| timestamp( d, elapsed.get( i ).copied().unwrap_or( route.time ) ) );
+    let point = |i: usize| {
+        let (lat, lon) = route.coords[i];
+        let mut pt = Waypoint::new( Point::new( lon, lat ) );
+        pt.elevation = elevations.get( i ).copied().flatten();
+        pt.time = at( i );
+        pt
+    };
+    let name = options.name.clone().unwrap_or( "Itinéraire".to_string() );
+    let description = format!( "{}, {}", distance( route.distance ), duration( route.time ) );
+
+    let mut tracks: Vec<Track> = route.sections( g ).into_iter().map( |(from, to, ferry)| {
+        let mut t = Track::new();
+        let mut segment = TrackSegment::new();
+        segment.points = ( from..=to ).map( point ).collect();
+        t.segments.push( segment );
+        if ferry {
+            t.type_ = Some( "ferry".to_string() );
+            let name = route.edges.get( from ).and_then( |e| Route::way_name( g, e.way ) );
+            t.name = Some( name.map( |n| format!( "Bac : {}", n ) ).unwrap_or( "Bac".to_string() ) );
+        }
+        t
+    } ).collect();
+    if let Some(t) = tracks.iter_mut().find( |t| t.name.is_none() ) {
+        t.name = Some( name.clone() );
+        t.description = Some( description.clone() );
+    }
+
+    let mut data : Gpx = Gpx { version: GpxVersion::Gpx11, tracks, ..Default::default() };
+    if options.instructions && !route.coords.is_empty() {
+        let mut rte = gpx::Route::new();
+        rte.name = Some( name.clone() );
+        rte.description = Some( description.clone() );
+        rte.points = instructions( g, route ).iter().map( |i| {
+            let mut pt = point( i.from.min( route.coords.len() - 1 ) );
+            pt.name = Some( i.text() );
+            if i.maneuver != "arrive" { pt.description = Some( distance( i.distance ) ); }
+            pt
+        } ).collect();
+        data.routes.push( rte );
+    }
+    if options.waypoints && !route.coords.is_empty() {
+        let mut stops: Vec<(usize, String)> = vec![(0, "Départ".to_string())];
+        let mut from = 0;
+        for (n, via) in options.vias.iter().enumerate() {
+            if let Some(i) = route.nodes.iter().skip( from ).position( |id| id == via ).map( |i| i + from ) {
+                stops.push( (i, format!( "Étape {}", n + 1 )) );
+                from = i;
+            }
+        }
+        stops.push( (route.coords.len() - 1, "Arrivée".to_string()) );
+        data.waypoints = stops.into_iter().map( |(i, label)| {
+            let mut pt = point( i );
+            pt.name = Some( label );
+            pt
+        } ).collect();
+    }
+    write_gpx( data, Some( name ), Some( description ), options.depart.and_then( |d| timestamp( d, 0.0 ) ), w )
+}
+
+// instant de passage (UTC pour le gpx) à `seconds` du départ en heure locale
+fn timestamp( depart: NaiveDateTime, seconds: f64 ) -> Option<Time> {
+    let t = Local.from_local_datetime( &depart ).earliest()? + Duration::milliseconds( ( seconds * 1000.0 ).round() as i64 );
+    OffsetDateTime::from_unix_timestamp( t.timestamp() ).ok().map( Time::from )
+}
+
+///
+/// enregistrer une liste de coordonnées (lat, lon) sous forme de trace gpx
+///
+pub fn save_points( points: &[(f64, f64)], filename: &Path ) -> Result<(), ExportError> {
+    write_points( points, File::create( filename )? )
+}
+
+///
+/// écrire une liste de coordonnées (lat, lon) sous forme de trace gpx (fichier, sortie standard, ...)
+///
+pub fn write_points<W: Write>( points: &[(f64, f64)], w: W ) -> Result<(), ExportError> {
+    write_tracks( vec![track( points )], w )
+}
+
+// une trace d'un seul segment
+fn track( points: &[(f64, f64)] ) -> Track {
+    let mut trkseg: TrackSegment = TrackSegment::new();
+    for (lat, lon) in points.iter() {
+        trkseg.points.push( Waypoint::new( Point::new( *lon, *lat ) ) );
+    }
+    let mut track: Track = Track::new();
+    track.segments.push( trkseg );
+    track
+}
+
+// document gpx des traces, avec leur emprise dans les métadonnées
+fn write_tracks<W: Write>( tracks: Vec<Track>, w: W ) -> Result<(), ExportError> {
+    write_gpx( Gpx { version: GpxVersion::Gpx11, tracks, ..Default::default() }, None, None, None, w )
+}
+
+// document gpx avec ses métadonnées : nom, description, date et emprise des traces
+fn write_gpx<W: Write>( mut data: Gpx, name: Option<String>, description: Option<String>, time: Option<Time>, w: W ) -> Result<(), ExportError> {
+
+    let mut lat_min: f64 = 95.0;
+    let mut lat_max: f64 = -95.0;
+    let mut lon_min: f64 = 180.0;
+    let mut lon_max: f64 = -180.0;
+
+    for pt in data.tracks.iter().flat_map( |t| t.segments.iter() ).flat_map( |s| s.points.iter() ) {
+        let (lat, lon) = (pt.point().y(), pt.point().x());
+        if lat < lat_min { lat_min = lat; }
+        if lat > lat_max { lat_max = lat; }
+        if lon < lon_min { lon_min = lon; }
+        if lon > lon_max { lon_max = lon; }
+    }
+    let mut meta: Metadata = Metadata { name, description, time, ..Default::default() };
+    let rect = Rect::new(
+        coord! { x: lon_min, y: lat_min},
+        coord! { x: lon_max, y: lat_max},
+    );
+    meta.bounds = Some( rect );
+    data.metadata = Some( meta );
+    write(&data, w)?;
+    Ok(())
+}
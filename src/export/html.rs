@@ -0,0 +1,168 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::export::ExportError;
+use crate::export::render::render;
+use crate::graphe::Graph;
+use crate::graphe::elevation::Climb;
+use crate::routing::instructions::instructions;
+use crate::routing::route::Route;
+
+
+// taille de la carte embarquée, mise à l'échelle de la page par le navigateur
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 900;
+
+const STYLE: &str = r#"
+body { margin: 0; display: flex; height: 100vh; font-family: sans-serif; font-size: 14px; }
+aside { width: 320px; overflow-y: auto; padding: 0 12px; border-right: 1px solid #ccc; }
+h1 { font-size: 18px; }
+.summary { font-weight: bold; }
+ol { padding-left: 20px; }
+li { padding: 4px 0; cursor: pointer; }
+li:hover, li.selected { background: #e8eefa; }
+li span { color: #666; float: right; }
+main { flex: 1; position: relative; overflow: hidden; background: #f2efe9; }
+main svg { width: 100%; height: 100%; cursor: grab; touch-action: none; }
+main svg polyline { vector-effect: non-scaling-stroke; }
+button { position: absolute; top: 8px; right: 8px; }
+"#;
+
+// déplacement (glisser), zoom (molette) et centrage sur une étape cliquée, par le viewBox du svg
+const SCRIPT: &str = r##"
+const svg = document.querySelector( "main svg" );
+const vb = svg.viewBox.baseVal;
+const initial = [vb.x, vb.y, vb.width, vb.height];
+const toSvg = ( e ) => new DOMPoint( e.clientX, e.clientY ).matrixTransform( svg.getScreenCTM().inverse() );
+svg.addEventListener( "wheel", ( e ) => {
+    e.preventDefault();
+    const p = toSvg( e );
+    const k = e.deltaY > 0 ? 1.25 : 0.8;
+    vb.x = p.x - ( p.x - vb.x ) * k;
+    vb.y = p.y - ( p.y - vb.y ) * k;
+    vb.width *= k;
+    vb.height *= k;
+}, { passive: false } );
+let drag = null;
+svg.addEventListener( "pointerdown", ( e ) => { drag = toSvg( e ); svg.setPointerCapture( e.pointerId ); } );
+svg.addEventListener( "pointermove", ( e ) => {
+    if ( !drag ) return;
+    const p = toSvg( e );
+    vb.x -= p.x - drag.x;
+    vb.y -= p.y - drag.y;
+} );
+svg.addEventListener( "pointerup", () => { drag = null; } );
+document.querySelector( "button" ).addEventListener( "click", () => { [vb.x, vb.y, vb.width, vb.height] = initial; } );
+const focus = document.createElementNS( "http://www.w3.org/2000/svg", "circle" );
+focus.setAttribute( "r", "9" );
+focus.setAttribute( "fill", "none" );
+focus.setAttribute( "stroke", "#000" );
+focus.setAttribute( "stroke-width", "2" );
+for ( const li of document.querySelectorAll( "li" ) ) {
+    li.addEventListener( "click", () => {
+        document.querySelectorAll( "li.selected" ).forEach( ( s ) => s.classList.remove( "selected" ) );
+        li.classList.add( "selected" );
+        const x = Number( li.dataset.x ), y = Number( li.dataset.y );
+        focus.setAttribute( "cx", x );
+        focus.setAttribute( "cy", y );
+        svg.appendChild( focus );
+        vb.x = x - vb.width / 2;
+        vb.y = y - vb.height / 2;
+    } );
+}
+"##;
+
+
+///
+/// durée lisible : "1 h 05 min", "12 min", "40 s"
+///
+pub fn duration( seconds: f64 ) -> String {
+    let s = seconds.round() as u64;
+    match ( s / 3600, ( s % 3600 ) / 60 ) {
+        ( 0, 0 ) => format!( "{} s", s ),
+        ( 0, m ) => format!( "{} min", m ),
+        ( h, m ) => format!( "{} h {:02} min", h, m ),
+    }
+}
+
+///
+/// distance lisible : mètres sous le kilomètre, kilomètres au-delà
+///
+pub fn distance( meters: f64 ) -> String {
+    if meters < 1000.0 { format!( "{:.0} m", meters ) } else { format!( "{:.1} km", meters / 1000.0 ) }
+}
+
+fn escape( s: &str ) -> String {
+    s.replace( '&', "&amp;" ).replace( '<', "&lt;" ).replace( '>', "&gt;" ).replace( '"', "&quot;" )
+}
+
+///
+/// enregistrer un itinéraire sous forme de page html autonome
+///
+pub fn save_route( g: &Graph, route: &Route, filename: &Path ) -> Result<(), ExportError> {
+    write_route( g, route, File::create( filename )? )
+}
+
+///
+/// écrire un itinéraire sous forme de page html autonome, sans accès réseau ni serveur de tuiles :
+/// carte svg des voies autour de l'itinéraire (déplacement et zoom), résumé distance / durée
+/// (et dénivelé avec un MNT) et liste des étapes, un clic sur une étape la centrant sur la carte
+///
+pub fn write_route<W: Write>( g: &Graph, route: &Route, mut w: W ) -> Result<(), ExportError> {
+    let map = render( g, Some( route ), WIDTH, HEIGHT, None )?;
+    let mut summary = format!( "{} · {}", distance( route.distance ), duration( route.time ) );
+    if let Some(climb) = Climb::of( g, &route.nodes ) {
+        summary.push_str( &format!( " · {}", climb ) );
+    }
+
+    writeln!( w, "<!DOCTYPE html>" )?;
+    writeln!( w, r#"<html lang="fr"><head><meta charset="utf-8"><title>Itinéraire</title><style>{}</style></head>"#, STYLE )?;
+    writeln!( w, "<body><aside><h1>Itinéraire</h1>" )?;
+    writeln!( w, r#"<p class="summary">{}</p><ol>"#, escape( &summary ) )?;
+    for i in instructions( g, route ).iter() {
+        let (x, y) = map.project( i.location.0, i.location.1 );
+        let step = if i.maneuver == "arrive" { String::new() } else { format!( "<span>{}</span>", distance( i.distance ) ) };
+        writeln!( w, r#"<li data-x="{:.1}" data-y="{:.1}">{}{}</li>"#, x, y, escape( &i.text() ), step )?;
+    }
+    writeln!( w, "</ol></aside><main><button>Recentrer</button>" )?;
+    write!( w, "{}", map.svg() )?;
+    writeln!( w, "</main><script>{}</script></body></html>", SCRIPT )?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod html_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    #[test]
+    fn readable() {
+        assert_eq!( "40 s", duration( 40.2 ) );
+        assert_eq!( "12 min", duration( 750.0 ) );
+        assert_eq!( "1 h 05 min", duration( 3900.0 ) );
+        assert_eq!( "350 m", distance( 350.4 ) );
+        assert_eq!( "12.3 km", distance( 12345.0 ) );
+    }
+
+    #[test]
+    fn route_page() {
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.99)],
+                            &[(10, &[1, 2], &[("highway", "residential"), ("name", "Rue <A>")]),
+                              (11, &[2, 3], &[("highway", "residential"), ("name", "Rue B")])] );
+        let adj = g.get_directed_for( &crate::routing::profile::Profile::Car );
+        let r = Route::from_nodes( &g, &adj, vec![1, 2, 3] );
+
+        let mut out: Vec<u8> = Vec::new();
+        write_route( &g, &r, &mut out ).unwrap();
+        let page = String::from_utf8( out ).unwrap();
+        assert!( page.starts_with( "<!DOCTYPE html>" ) && page.trim_end().ends_with( "</html>" ) );
+        // aucune ressource externe
+        assert!( !page.contains( "src=" ) && !page.contains( "href=" ) );
+        assert!( page.contains( "<svg" ) && page.contains( "<script>" ) );
+        assert!( page.contains( "Partir sur Rue &lt;A&gt;" ) && page.contains( "Tourner à gauche sur Rue B" ) );
+        assert_eq!( 3, page.matches( "<li " ).count() );
+        assert!( page.contains( &distance( r.distance ) ) );
+    }
+}
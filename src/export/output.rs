@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::export::{ExportError, Format, geojson, gpx_file, html, polyline};
+use crate::export::gpx_file::GpxOptions;
+use crate::graphe::Graph;
+use crate::routing::route::Route;
+
+
+///
+/// écrire un itinéraire dans le format donné, les options ne valant que pour le gpx
+///
+pub fn write_route<W: Write>( g: &Graph, route: &Route, format: Format, options: &GpxOptions, mut w: W ) -> Result<(), ExportError> {
+    match format {
+        Format::Gpx => gpx_file::write_route_with( g, route, options, w ),
+        Format::GeoJson => Ok( serde_json::to_writer_pretty( w, &geojson::route( g, route ) )? ),
+        Format::Html => html::write_route( g, route, w ),
+        Format::Polyline(precision) => Ok( writeln!( w, "{}", polyline::encode( &route.coords, precision ) )? ),
+    }
+}
+
+///
+/// enregistrer un itinéraire dans le fichier choisi, au format déduit de son extension
+///
+pub fn save_route( g: &Graph, route: &Route, options: &GpxOptions, path: &Path ) -> Result<(), ExportError> {
+    let format = Format::of( path )?;
+    write_route( g, route, format, options, File::create( path )? )
+}
+
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use crate::graphe::testing::test_graph;
+    use crate::routing::profile::Profile;
+
+    fn corner() -> (Graph, Route) {
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.99)],
+                            &[(10, &[1, 2], &[("highway", "residential"), ("name", "Rue A")]),
+                              (11, &[2, 3], &[("highway", "residential"), ("name", "Rue B")])] );
+        let adj = g.get_directed_for( &Profile::Car );
+        let r = Route::from_nodes( &g, &adj, vec![1, 2, 3] );
+        (g, r)
+    }
+
+    fn gpx( g: &Graph, r: &Route, options: &GpxOptions ) -> String {
+        let mut out: Vec<u8> = Vec::new();
+        write_route( g, r, Format::Gpx, options, &mut out ).unwrap();
+        String::from_utf8( out ).unwrap()
+    }
+
+    #[test]
+    fn polyline_precision() {
+        let (g, r) = corner();
+        for precision in [5, 6] {
+            let mut out: Vec<u8> = Vec::new();
+            write_route( &g, &r, Format::Polyline(precision), &GpxOptions::default(), &mut out ).unwrap();
+            let coords = polyline::decode( String::from_utf8( out ).unwrap().trim(), precision ).unwrap();
+            assert_eq!( vec![(48.0, -2.0), (48.0, -1.99), (48.01, -1.99)], coords );
+        }
+    }
+
+    #[test]
+    fn gpx_variants() {
+        let (g, r) = corner();
+        // trace seule, avec son nom et sa distance
+        let plain = gpx( &g, &r, &GpxOptions::default() );
+        assert!( plain.contains( "<trk>" ) && plain.contains( "<name>Itinéraire</name>" ) );
+        assert!( !plain.contains( "<rte>" ) && !plain.contains( "<wpt" ) && !plain.contains( "<time>" ) );
+
+        let options = GpxOptions { name: Some( "Tournée".to_string() ), depart: NaiveDate::from_ymd_opt( 2026, 10, 19 ).unwrap().and_hms_opt( 12, 0, 0 ),
+            instructions: true, waypoints: true, vias: vec![2] };
+        let full = gpx( &g, &r, &options );
+        assert!( full.contains( "<name>Tournée</name>" ) );
+        assert_eq!( 1, full.matches( "<rte>" ).count() );
+        assert_eq!( 3, full.matches( "<rtept" ).count() );
+        assert!( full.contains( "<name>Tourner à gauche sur Rue B</name>" ) );
+        assert_eq!( 3, full.matches( "<wpt" ).count() );
+        assert!( full.contains( "<name>Étape 1</name>" ) && full.contains( "<name>Arrivée</name>" ) );
+        // métadonnées, 3 points de trace, 3 manœuvres, 3 étapes
+        assert_eq!( 10, full.matches( "<time>" ).count() );
+
+        let parsed = ::gpx::read( full.as_bytes() ).unwrap();
+        let times: Vec<time::OffsetDateTime> = parsed.tracks[0].segments[0].points.iter().map( |p| p.time.unwrap().into() ).collect();
+        let elapsed = ( times[2] - times[0] ).whole_seconds() as f64;
+        assert!( ( elapsed - r.time ).abs() <= 1.0 );
+    }
+}
@@ -0,0 +1,76 @@
+///
+/// encodage "encoded polyline" (Google, OSRM) d'une suite de (lat, lon)
+/// avec 5 (polyline) ou 6 (polyline6) décimales
+///
+pub fn encode( coords: &[(f64, f64)], precision: u32 ) -> String {
+    let factor = 10f64.powi( precision as i32 );
+    let mut result = String::new();
+    let (mut plat, mut plon) = (0i64, 0i64);
+    for (lat, lon) in coords.iter() {
+        let (ilat, ilon) = ( (lat * factor).round() as i64, (lon * factor).round() as i64 );
+        push( ilat - plat, &mut result );
+        push( ilon - plon, &mut result );
+        (plat, plon) = (ilat, ilon);
+    }
+    result
+}
+
+// une valeur signée : décalage, inversion si négative, puis blocs de 5 bits
+fn push( v: i64, out: &mut String ) {
+    let mut v = if v < 0 { !(v << 1) } else { v << 1 };
+    while v >= 0x20 {
+        out.push( ( ( 0x20 | (v & 0x1f) ) + 63 ) as u8 as char );
+        v >>= 5;
+    }
+    out.push( ( v + 63 ) as u8 as char );
+}
+
+///
+/// décodage d'une polyline, None si la chaîne est invalide
+///
+pub fn decode( s: &str, precision: u32 ) -> Option<Vec<(f64, f64)>> {
+    let factor = 10f64.powi( precision as i32 );
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    let mut coords = Vec::new();
+    let (mut lat, mut lon) = (0i64, 0i64);
+    while i < bytes.len() {
+        lat += value( bytes, &mut i )?;
+        lon += value( bytes, &mut i )?;
+        coords.push( (lat as f64 / factor, lon as f64 / factor) );
+    }
+    Some( coords )
+}
+
+// une valeur signée lue à partir de la position i
+fn value( bytes: &[u8], i: &mut usize ) -> Option<i64> {
+    let (mut result, mut shift) = (0i64, 0);
+    loop {
+        let b = ( *bytes.get( *i )? as i64 ) - 63;
+        if !(0..64).contains( &b ) || shift > 60 { return None; }
+        *i += 1;
+        result |= (b & 0x1f) << shift;
+        shift += 5;
+        if b < 0x20 { break; }
+    }
+    Some( if result & 1 == 1 { !(result >> 1) } else { result >> 1 } )
+}
+
+
+#[cfg(test)]
+mod polyline_tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        // exemple de la documentation Google
+        let coords = [(38.5, -120.2), (40.7, -120.95), (43.252, -126.453)];
+        assert_eq!( "_p~iF~ps|U_ulLnnqC_mqNvxq`@", encode( &coords, 5 ) );
+        assert_eq!( Some( coords.to_vec() ), decode( "_p~iF~ps|U_ulLnnqC_mqNvxq`@", 5 ) );
+
+        let p6 = encode( &[(48.406389, -2.815077)], 6 );
+        assert_eq!( Some( vec![(48.406389, -2.815077)] ), decode( &p6, 6 ) );
+        assert_eq!( "", encode( &[], 5 ) );
+        assert_eq!( None, decode( "_p~iF~ps|", 5 ) );
+    }
+}
@@ -0,0 +1,326 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use tiny_skia::{FillRule, LineCap, LineJoin, Paint, PathBuilder, Pixmap, Stroke, StrokeDash, Transform};
+
+use crate::export::ExportError;
+use crate::graphe::Graph;
+use crate::routing::profile::is_ferry;
+use crate::routing::route::Route;
+
+
+// marge autour de l'itinéraire quand le cadre n'est pas donné
+const MARGIN: f64 = 0.1;
+
+const BACKGROUND: Rgba = Rgba( 0xf2, 0xef, 0xe9, 0xff );
+const CASING: Rgba = Rgba( 0xa0, 0xa0, 0xa0, 0xff );
+const ROUTE: Pen = Pen { colour: Rgba( 0x1e, 0x64, 0xc8, 0xcc ), width: 5.0, dash: false };
+const START: Rgba = Rgba( 0x2c, 0xa0, 0x2c, 0xff );
+const END: Rgba = Rgba( 0xd6, 0x27, 0x28, 0xff );
+const MARKER_RADIUS: f32 = 6.0;
+
+
+///
+/// cadre de la carte en degrés
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bbox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl Bbox {
+    ///
+    /// cadre "min_lon,min_lat,max_lon,max_lat" (ordre GeoJSON)
+    ///
+    pub fn parse( s: &str ) -> Option<Bbox> {
+        let v: Vec<f64> = s.split( ',' ).map( |x| x.trim().parse::<f64>() ).collect::<Result<Vec<f64>, _>>().ok()?;
+        match v.as_slice() {
+            [min_lon, min_lat, max_lon, max_lat] if min_lon < max_lon && min_lat < max_lat =>
+                Some( Bbox { min_lat: *min_lat, min_lon: *min_lon, max_lat: *max_lat, max_lon: *max_lon } ),
+            _ => None,
+        }
+    }
+
+    ///
+    /// plus petit cadre contenant les points (lat, lon)
+    ///
+    pub fn around<I: IntoIterator<Item = (f64, f64)>>( points: I ) -> Option<Bbox> {
+        points.into_iter().fold( None, |b: Option<Bbox>, (lat, lon)| match b {
+            None => Some( Bbox { min_lat: lat, min_lon: lon, max_lat: lat, max_lon: lon } ),
+            Some(b) => Some( Bbox { min_lat: b.min_lat.min( lat ), min_lon: b.min_lon.min( lon ), max_lat: b.max_lat.max( lat ), max_lon: b.max_lon.max( lon ) } ),
+        } )
+    }
+
+    ///
+    /// cadre agrandi de `ratio` de sa taille de chaque côté (au moins 0.001° pour un point seul)
+    ///
+    pub fn grow( &self, ratio: f64 ) -> Bbox {
+        let dlat = ( ( self.max_lat - self.min_lat ) * ratio ).max( 0.001 );
+        let dlon = ( ( self.max_lon - self.min_lon ) * ratio ).max( 0.001 );
+        Bbox { min_lat: self.min_lat - dlat, min_lon: self.min_lon - dlon, max_lat: self.max_lat + dlat, max_lon: self.max_lon + dlon }
+    }
+
+    pub fn intersects( &self, other: &Bbox ) -> bool {
+        self.min_lat <= other.max_lat && other.min_lat <= self.max_lat && self.min_lon <= other.max_lon && other.min_lon <= self.max_lon
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba( pub u8, pub u8, pub u8, pub u8 );
+
+impl Rgba {
+    fn hex( &self ) -> String {
+        format!( "#{:02x}{:02x}{:02x}", self.0, self.1, self.2 )
+    }
+}
+
+///
+/// trait : couleur, épaisseur en pixels, pointillés
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pen {
+    pub colour: Rgba,
+    pub width: f32,
+    pub dash: bool,
+}
+
+impl Pen {
+    const fn new( r: u8, g: u8, b: u8, width: f32, dash: bool ) -> Pen {
+        Pen { colour: Rgba( r, g, b, 0xff ), width, dash }
+    }
+}
+
+///
+/// style d'une voie d'après sa classe : rang de dessin (les grandes routes au-dessus) et trait,
+/// les chemins et les bacs sont en pointillés sans bordure
+///
+pub fn style( highway: &str ) -> (u8, Pen) {
+    match highway.trim_end_matches( "_link" ) {
+        "motorway" => ( 7, Pen::new( 0xe8, 0x92, 0xa2, 5.0, false ) ),
+        "trunk" => ( 6, Pen::new( 0xf9, 0xb2, 0x9c, 4.5, false ) ),
+        "primary" => ( 5, Pen::new( 0xfc, 0xd6, 0xa4, 4.0, false ) ),
+        "secondary" => ( 4, Pen::new( 0xf7, 0xfa, 0xbf, 3.5, false ) ),
+        "tertiary" => ( 3, Pen::new( 0xff, 0xff, 0xff, 3.0, false ) ),
+        "residential" | "unclassified" | "living_street" | "road" => ( 2, Pen::new( 0xff, 0xff, 0xff, 2.5, false ) ),
+        "cycleway" => ( 1, Pen::new( 0x00, 0x00, 0xff, 1.0, true ) ),
+        "footway" | "path" | "pedestrian" | "steps" | "bridleway" => ( 1, Pen::new( 0xfa, 0x80, 0x72, 1.0, true ) ),
+        "ferry" => ( 1, Pen::new( 0x6a, 0x8c, 0xd5, 1.5, true ) ),
+        _ => ( 1, Pen::new( 0xff, 0xff, 0xff, 1.5, false ) ),
+    }
+}
+
+
+/// suite de points en pixels
+pub type Polyline = Vec<(f32, f32)>;
+
+///
+/// élément dessiné, en pixels
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Line( Polyline, Pen ),
+    Marker( (f32, f32), Rgba ),
+}
+
+
+///
+/// carte du graphe dans un cadre, projection équirectangulaire centrée sur le cadre, rendue en
+/// SVG ou en PNG (tiny-skia), sans serveur de tuiles
+///
+#[derive(Debug)]
+pub struct Map {
+    pub width: u32,
+    pub height: u32,
+    pub bbox: Bbox,
+    pub shapes: Vec<Shape>,
+    // échelle (pixels par degré de latitude), facteur des longitudes et décalage pour centrer
+    scale: f64,
+    cos: f64,
+    offset: (f64, f64),
+}
+
+impl Map {
+    pub fn new( width: u32, height: u32, bbox: Bbox ) -> Self {
+        let cos = ( ( bbox.min_lat + bbox.max_lat ) / 2.0 ).to_radians().cos();
+        let ( dx, dy ) = ( ( bbox.max_lon - bbox.min_lon ) * cos, bbox.max_lat - bbox.min_lat );
+        let scale = ( width as f64 / dx ).min( height as f64 / dy );
+        let offset = ( ( width as f64 - dx * scale ) / 2.0, ( height as f64 - dy * scale ) / 2.0 );
+        Self { width, height, bbox, shapes: Vec::new(), scale, cos, offset }
+    }
+
+    ///
+    /// position en pixels (origine en haut à gauche)
+    ///
+    pub fn project( &self, lat: f64, lon: f64 ) -> (f32, f32) {
+        ( ( self.offset.0 + ( lon - self.bbox.min_lon ) * self.cos * self.scale ) as f32,
+          ( self.offset.1 + ( self.bbox.max_lat - lat ) * self.scale ) as f32 )
+    }
+
+    ///
+    /// voies du graphe qui touchent le cadre, bordures d'abord puis par classe
+    ///
+    pub fn roads( &mut self, g: &Graph ) {
+        let mut ways: Vec<(u8, Pen, Polyline)> = Vec::new();
+        for w in g.tways.values() {
+            let coords: Vec<(f64, f64)> = w.refs().iter().filter_map( |id| g.tnodes.get( id ) ).map( |n| (n.lat(), n.lon()) ).collect();
+            if coords.len() < 2 || !Bbox::around( coords.iter().copied() ).is_some_and( |b| b.intersects( &self.bbox ) ) { continue; }
+            let highway = if is_ferry( w.tags() ) { "ferry" } else { w.tags().get( "highway" ).map( |h| h.as_str() ).unwrap_or( "" ) };
+            let (rank, pen) = style( highway );
+            ways.push( (rank, pen, coords.iter().map( |(lat, lon)| self.project( *lat, *lon ) ).collect()) );
+        }
+        ways.sort_by_key( |w| w.0 );
+        for (_, pen, points) in ways.iter().filter( |w| !w.1.dash ) {
+            self.shapes.push( Shape::Line( points.clone(), Pen { colour: CASING, width: pen.width + 1.5, dash: false } ) );
+        }
+        self.shapes.extend( ways.into_iter().map( |(_, pen, points)| Shape::Line( points, pen ) ) );
+    }
+
+    ///
+    /// itinéraire et marqueurs de départ et d'arrivée
+    ///
+    pub fn route( &mut self, r: &Route ) {
+        let points: Polyline = r.coords.iter().map( |(lat, lon)| self.project( *lat, *lon ) ).collect();
+        if let ( Some(start), Some(end) ) = ( points.first().copied(), points.last().copied() ) {
+            self.shapes.push( Shape::Line( points, ROUTE ) );
+            self.shapes.push( Shape::Marker( start, START ) );
+            self.shapes.push( Shape::Marker( end, END ) );
+        }
+    }
+
+    pub fn svg( &self ) -> String {
+        let mut s = String::new();
+        let _ = writeln!( s, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#, w = self.width, h = self.height );
+        let _ = writeln!( s, r#"<rect width="100%" height="100%" fill="{}"/>"#, BACKGROUND.hex() );
+        for shape in self.shapes.iter() {
+            match shape {
+                Shape::Line( points, pen ) => {
+                    let points: Vec<String> = points.iter().map( |(x, y)| format!( "{:.1},{:.1}", x, y ) ).collect();
+                    let dash = if pen.dash { r#" stroke-dasharray="4 3""# } else { "" };
+                    let opacity = if pen.colour.3 < 0xff { format!( r#" stroke-opacity="{:.2}""#, pen.colour.3 as f32 / 255.0 ) } else { String::new() };
+                    let _ = writeln!( s, r#"<polyline points="{}" fill="none" stroke="{}"{} stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"{}/>"#,
+                        points.join( " " ), pen.colour.hex(), opacity, pen.width, dash );
+                },
+                Shape::Marker( (x, y), fill ) => {
+                    let _ = writeln!( s, r##"<circle cx="{:.1}" cy="{:.1}" r="{}" fill="{}" stroke="#ffffff" stroke-width="2"/>"##, x, y, MARKER_RADIUS, fill.hex() );
+                },
+            }
+        }
+        s.push_str( "</svg>\n" );
+        s
+    }
+
+    pub fn png( &self ) -> Result<Vec<u8>, ExportError> {
+        let mut pixmap = Pixmap::new( self.width, self.height )
+            .ok_or( ExportError::Image( format!( "taille invalide {}x{}", self.width, self.height ) ) )?;
+        pixmap.fill( colour( BACKGROUND ) );
+        let paint = |c: Rgba| {
+            let mut p = Paint::default();
+            p.set_color( colour( c ) );
+            p.anti_alias = true;
+            p
+        };
+        for shape in self.shapes.iter() {
+            match shape {
+                Shape::Line( points, pen ) => {
+                    let mut pb = PathBuilder::new();
+                    pb.move_to( points[0].0, points[0].1 );
+                    for (x, y) in points.iter().skip( 1 ) { pb.line_to( *x, *y ); }
+                    let Some(path) = pb.finish() else { continue };
+                    let stroke = Stroke { width: pen.width, line_cap: LineCap::Round, line_join: LineJoin::Round,
+                        dash: if pen.dash { StrokeDash::new( vec![4.0, 3.0], 0.0 ) } else { None }, ..Default::default() };
+                    pixmap.stroke_path( &path, &paint( pen.colour ), &stroke, Transform::identity(), None );
+                },
+                Shape::Marker( (x, y), fill ) => {
+                    let Some(path) = PathBuilder::from_circle( *x, *y, MARKER_RADIUS ) else { continue };
+                    pixmap.fill_path( &path, &paint( *fill ), FillRule::Winding, Transform::identity(), None );
+                    let stroke = Stroke { width: 2.0, ..Default::default() };
+                    pixmap.stroke_path( &path, &paint( Rgba( 0xff, 0xff, 0xff, 0xff ) ), &stroke, Transform::identity(), None );
+                },
+            }
+        }
+        pixmap.encode_png().map_err( |e| ExportError::Image( e.to_string() ) )
+    }
+}
+
+fn colour( c: Rgba ) -> tiny_skia::Color {
+    tiny_skia::Color::from_rgba8( c.0, c.1, c.2, c.3 )
+}
+
+
+///
+/// carte des voies et de l'itinéraire ; sans cadre, celui de l'itinéraire avec une marge, ou celui du graphe
+///
+pub fn render( g: &Graph, route: Option<&Route>, width: u32, height: u32, bbox: Option<Bbox> ) -> Result<Map, ExportError> {
+    let bbox = bbox
+        .or_else( || route.and_then( |r| Bbox::around( r.coords.iter().copied() ) ).map( |b| b.grow( MARGIN ) ) )
+        .or_else( || Bbox::around( g.tnodes.values().map( |n| (n.lat(), n.lon()) ) ) )
+        .ok_or( ExportError::Image( "graphe vide".to_string() ) )?;
+    if width == 0 || height == 0 { return Err( ExportError::Image( format!( "taille invalide {}x{}", width, height ) ) ); }
+    let mut map = Map::new( width, height, bbox );
+    map.roads( g );
+    if let Some(r) = route { map.route( r ); }
+    Ok( map )
+}
+
+///
+/// l'extension .png donne une image PNG, sinon du SVG
+///
+pub fn is_png( path: &Path ) -> bool {
+    path.extension().is_some_and( |e| e.eq_ignore_ascii_case( "png" ) )
+}
+
+///
+/// enregistrer la carte, au format déduit de l'extension du fichier
+///
+pub fn save( map: &Map, path: &Path ) -> Result<(), ExportError> {
+    if is_png( path ) { fs::write( path, map.png()? )?; }
+    else { fs::write( path, map.svg() )?; }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod render_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    fn cross() -> Graph {
+        // la way 12 est hors du cadre de l'itinéraire
+        test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.0, -1.98), (4, 48.01, -1.99), (5, 47.0, -1.0), (6, 47.0, -1.01)],
+                    &[(10, &[1, 2, 3], &[("highway", "primary")]),
+                      (11, &[2, 4], &[("highway", "footway")]),
+                      (12, &[5, 6], &[("highway", "residential")])] )
+    }
+
+    #[test]
+    fn bbox() {
+        assert_eq!( Some( Bbox { min_lat: 48.0, min_lon: -2.1, max_lat: 48.5, max_lon: -1.5 } ), Bbox::parse( "-2.1,48.0,-1.5,48.5" ) );
+        assert_eq!( None, Bbox::parse( "-1.5,48.0,-2.1,48.5" ) );
+        assert_eq!( None, Bbox::parse( "-2.1,48.0,-1.5" ) );
+        let b = Bbox::around( [(48.0, -2.0), (48.2, -1.8)] ).unwrap().grow( 0.5 );
+        assert!( ( b.min_lat - 47.9 ).abs() < 1e-9 && ( b.max_lon + 1.7 ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn render_route() {
+        let g = cross();
+        let r = Route { nodes: vec![1, 2, 3], coords: vec![(48.0, -2.0), (48.0, -1.99), (48.0, -1.98)], ..Default::default() };
+        let map = render( &g, Some( &r ), 400, 300, None ).unwrap();
+        // départ à gauche, arrivée à droite, à mi-hauteur
+        let ( start, end ) = ( map.project( 48.0, -2.0 ), map.project( 48.0, -1.98 ) );
+        assert!( start.0 < end.0 && ( start.1 - 150.0 ).abs() < 1e-3 && ( end.1 - 150.0 ).abs() < 1e-3 );
+        // bordure et trait du primary, trait du footway, itinéraire et marqueurs ; la way 12 est hors cadre
+        assert_eq!( 6, map.shapes.len() );
+        assert_eq!( Shape::Marker( end, END ), map.shapes[5] );
+        let svg = map.svg();
+        assert!( svg.starts_with( "<svg" ) && svg.contains( "stroke-dasharray" ) && svg.contains( &ROUTE.colour.hex() ) );
+        let png = map.png().unwrap();
+        assert_eq!( b"\x89PNG", &png[..4] );
+        assert!( render( &g, None, 0, 300, None ).is_err() );
+    }
+}
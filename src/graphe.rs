@@ -1,17 +1,39 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use osmpbf::{DenseNode, Way };
+use crate::graphe::address::AddressIndex;
+use crate::graphe::boundary::Boundaries;
 use crate::graphe::elements::{TNodeType, Edge, TNode, TWay};
-use crate::routing::distances::distance_haversine;
+use crate::graphe::elevation::grade;
+use crate::graphe::validate::Problem;
+use crate::routing::conditional::has_conditional;
+use crate::routing::distances::{DistanceMetric, distance_haversine};
+use crate::routing::profile::{Access, Profile, RESTRICTED, ferry_duration, is_ferry};
 
 pub mod reader;
 pub mod elements;
+pub mod index;
+pub mod address;
+pub mod boundary;
+pub mod components;
+pub mod validate;
+pub mod elevation;
+#[cfg(test)]
+pub(crate) mod testing;
 
 
 pub struct Graph {
     filename: String,
     pub tnodes: HashMap<i64, TNode>,
     pub tways: HashMap<i64, TWay>,
+    /// géocodeur hors ligne (cf. reader::read_osm)
+    pub addresses: AddressIndex,
+    /// communes (cf. reader::read_osm)
+    pub boundaries: Boundaries,
+    /// ways écartées à la lecture : highway non géré, noeuds absents (cf. validate)
+    pub rejected: Vec<Problem>,
+    /// formule de distance des arcs et des ways
+    metric: DistanceMetric,
 }
 
 
@@ -20,7 +42,22 @@ impl Graph {
     /// create new graph from tnodes and tways collections
     ///
     pub fn new( f: String, ip: HashMap<i64, TNode>, ib: HashMap<i64, TWay> ) -> Self {
-        Self { filename: f, tnodes: ip, tways: ib }
+        Self { filename: f, tnodes: ip, tways: ib, addresses: AddressIndex::default(), boundaries: Boundaries::default(), rejected: Vec::new(), metric: DistanceMetric::default() }
+    }
+
+    ///
+    /// distance formula used for the edges and the ways length (haversine by default)
+    ///
+    pub fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    ///
+    /// change the distance formula, the ways length is computed again
+    ///
+    pub fn set_metric(&mut self, metric: DistanceMetric) {
+        self.metric = metric;
+        for w in self.tways.values_mut() { w.measure( &self.tnodes, metric ); }
     }
 
     ///
@@ -28,13 +65,11 @@ impl Graph {
     ///
     pub fn clean(&mut self) {
         for ( _i, e ) in self.tways.iter() {
-            let mut count: usize = 0;
             let mut lastid: &i64 = &0;
-            for n in e.refs().iter() {
+            for (count, n) in e.refs().iter().enumerate() {
                 lastid = n;
                 if count == 0 { self.tnodes.get_mut(lastid).expect("n must be in hashmap").set_type( TNodeType::EndNode ); }
                 else { self.tnodes.get_mut(lastid).expect("n must be in hashmap").set_type( TNodeType::MiddleNode ); }
-                count += 1;
             }
             self.tnodes.get_mut(lastid).expect("n must be in hashmap").set_type( TNodeType::EndNode );
         }
@@ -45,73 +80,103 @@ impl Graph {
     /// make a directed graph that can be used by Dijkstra shortest_path function (see dijkstra.rs)
     ///
     pub fn get_directed(&self) -> HashMap<i64,Vec<Edge>> {
+        self.get_directed_for( &Profile::Car )
+    }
+
+    ///
+    /// make a directed graph for the given profile : ways not usable by the profile are skipped
+    /// and the cost of each edge is its distance weighted by the profile preference.
+    /// Nodes the profile can't pass (barrier, access=no) have no edge, and the time penalty of
+    /// passable barriers (lift_gate, toll_booth, ...) is added to the edges reaching them.
+    /// Ways are kept following the access tags of the profile (access -> vehicle -> motor_vehicle
+    /// -> motorcar, ...) ; entering a destination / private zone costs a routing penalty (see profile::RESTRICTED),
+    /// kept apart from the travel time (see Edge::penalty)
+    /// Ferries (route=ferry) take the time of their duration tag, or go at profile::FERRY_SPEED
+    /// When the nodes have an elevation (see elevation::set_elevation), the time and cost of each edge
+    /// are weighted by the grade factor of the profile (see Profile::grade_factor)
+    ///
+    pub fn get_directed_for(&self, profile: &Profile) -> HashMap<i64,Vec<Edge>> {
+        self.directed( profile, false )
+    }
+
+    ///
+    /// make a directed graph for the given profile where the ways carrying conditional restrictions
+    /// (access:conditional, oneway:conditional, ...) get their edges in both directions, whatever
+    /// their tags : they are evaluated at the time of passage (see routing::conditional::Conditions)
+    ///
+    pub fn get_conditional_for(&self, profile: &Profile) -> HashMap<i64,Vec<Edge>> {
+        self.directed( profile, true )
+    }
+
+    fn directed(&self, profile: &Profile, conditional: bool) -> HashMap<i64,Vec<Edge>> {
         let mut graph: HashMap<i64,Vec<Edge>> = HashMap::new();
-        let mut maxspeed: f64 = 0.0;
+        let open = self.open_nodes( profile );
+
+        for (k, w) in self.tways.iter() {
+            // voie à restrictions conditionnelles : tous les arcs, réévalués à l'heure de passage
+            let variable = conditional && has_conditional( w.tags() );
+            // vitesse de base du profil sur la voie et droit de passage, None si la voie lui est fermée
+            let Some(usage) = profile.way( w.tags() ).or( variable.then( || (profile.fallback_speed(), Access::Yes) ) ) else { continue };
+            // on traite les voies a double sens en enregistrant l'arc contraire
+            let both = variable || ! profile.oneway( w.tags() );
 
-        for (_k, w) in self.tways.iter() {
             // pour chacun des segments composant la voie (way)
-    	    for (key, v) in w.tags() {
-        		if key == "highway" {
-        		    match v.as_str() {
-                        "motorway" => { maxspeed = 130.0 },
-            			"trunk" |
-            			"primary" => { maxspeed = 110.0 },
-            			"secondary" => { maxspeed = 80.0 },
-                        "primary_link" |
-                        "trunk_link" =>  { maxspeed = 70.0 },
-            			"road" |
-                        "tertiary_link" |
-                        "secondary_link" |
-                        "residential" |
-                        "unclassified" |
-            			"tertiary"  => { maxspeed = 50.0 },
-                        &_ => {}
-        	       }
+            for s in w.refs().windows( 2 ) {
+                let directions = if both { vec![(s[0], s[1]), (s[1], s[0])] } else { vec![(s[0], s[1])] };
+                for (from, to) in directions {
+                    let Some(e) = self.edge( profile, &open, *k, w.tags(), usage, (from, to) ) else { continue };
+                    graph.entry( from ).or_default().push( e );
                 }
             }
-    	    for (key, v) in w.tags() {
-        		if key == "maxspeed" {
-                    maxspeed = v.parse::<f64>().unwrap();
-        		}
-    	    }
-
-            for i in 1..w.refs().len() {
-                // les id des noeuds
-                let start_idx = w.refs()[i-1];
-                let end_idx = w.refs()[i];
-
-                // les datas associées
-                let start = self.tnodes.get( &start_idx ).expect( "start node must exist in db ... ");
-                let end = self.tnodes.get( &end_idx ).expect( "end node must exist in db ... ");
-
-                // calcul de la distance entre les noeuds
-                // let d = distance_pythagore(  start.lat(), start.lon(), end.lat(), end.lon() );
-                // let d = distance_sinus( start.lat(), start.lon(), end.lat(), end.lon() );
-                let d = distance_haversine( start.lat(), start.lon(), end.lat(), end.lon() );
-                let t = (maxspeed / 3.6) / d ; // t en secondes
-
-                // on crée un arc vers le nodeid de fin et comprenant la distance calculée
-                let normal = Edge{ node: end_idx, distance: d, time: t, cost: 0.0 };
-                match graph.get_mut( &start_idx ) {
-                    // l'entrée existe : on reajoute à la liste des arcs du noeud considéré
-                    Some(v) => { v.push( normal ); },
-                    // l'entrée n'existe pas : on ajoute le node avec une nouvelle liste
-                    None => { graph.insert( start_idx, vec![ normal ] ); },
-                };
-                // on traite les voies a double sens en enregistrant l'arc contraire
-                if ! w.oneway() {
-                    let reverse = Edge{ node: start_idx, distance: d, time: t, cost: 0.0 };
-                    match graph.get_mut( &end_idx ) {
-                        Some(v) => { v.push( reverse ); },
-                        None => { graph.insert( end_idx, vec![ reverse ] ); },
-                    };
-                }
-            }
-
         }
         graph
     }
 
+    ///
+    /// nodes of the ways open to the profile (Access::Yes) : entering a restricted zone from one of them
+    /// costs the profile::RESTRICTED penalty
+    ///
+    pub fn open_nodes(&self, profile: &Profile) -> HashSet<i64> {
+        let mut open: HashSet<i64> = HashSet::new();
+        for w in self.tways.values() {
+            if let Some((_, Access::Yes)) = profile.way( w.tags() ) { open.extend( w.refs().iter() ); }
+        }
+        open
+    }
+
+    ///
+    /// edge from -> to of the way `k`, given its tags (possibly resolved at some time, see
+    /// routing::conditional::resolve) and the speed and access of the profile on it (see Profile::way) ;
+    /// None if one of the nodes can't be passed. Shared by the directed graphs and the conditional edges
+    ///
+    pub fn edge(&self, profile: &Profile, open: &HashSet<i64>, k: i64, tags: &HashMap<String, String>, usage: (f64, Access), (from, to): (i64, i64)) -> Option<Edge> {
+        let w = self.tways.get( &k )?;
+        let ( start, end ) = ( self.tnodes.get( &from )?, self.tnodes.get( &to )? );
+        let (mut maxspeed, access) = usage;
+        // vitesse et préférence suivant le type de voie (way)
+        let ferry = is_ferry( tags );
+        let highway = tags.get( "highway" ).map( |h| h.as_str() ).unwrap_or( if ferry { "ferry" } else { "" } );
+        // bac : la durée de la traversée, si elle est connue, donne la vitesse
+        if let Some(duration) = ferry.then( || ferry_duration( tags ) ).flatten().filter( |_| w.len() > 0.0 ) {
+            maxspeed = w.len() / duration * 3.6;
+        }
+
+        // barrières : pas d'arc vers ou depuis un noeud infranchissable
+        let ( Some(_), Some(delay) ) = ( profile.passage( start.tags() ), profile.passage( end.tags() ) ) else { return None };
+        // entrée dans une zone restreinte (noeud, ou voie depuis une voie ouverte) : pénalité hors durée
+        let zone = if access == Access::Destination && open.contains( &from ) { RESTRICTED } else { 0.0 };
+        let penalty = profile.restricted( end.tags() ) + zone;
+
+        // calcul de la distance entre les noeuds suivant la formule du graphe (cf. distances::DistanceMetric)
+        let d = self.metric.distance( start.lat(), start.lon(), end.lat(), end.lon() );
+        let t = d / (maxspeed / 3.6) ; // t en secondes
+        // pente du segment (altitudes du MNT, cf. elevation) : facteur de durée dans ce sens
+        let factor = profile.grade_factor( if ferry { 0.0 } else { grade( self, from, to, d ) } );
+        // pénalités de franchissement converties en distance à la vitesse de la voie pour le coût
+        let cost = ( d * factor + (delay + penalty) * maxspeed / 3.6 ) * profile.preference( highway );
+        Some( Edge{ node: to, distance: d, time: t * factor + delay, cost, penalty, way: k } )
+    }
+
 }
 
 ///
@@ -136,13 +201,108 @@ impl fmt::Display for Graph {
             }
         }
 
-        write!( f, "file : {}\n\ttways : {} , tnodes : {}\n",
-            self.filename, self.tways.len(), self.tnodes.len() ).unwrap();
-        write!( f, "graph : \n\ttways are :\n" ).unwrap();
+        writeln!( f, "file : {}\n\ttways : {} , tnodes : {} , adresses : {} , communes : {}",
+            self.filename, self.tways.len(), self.tnodes.len(), self.addresses.len(), self.boundaries.len() ).unwrap();
+        writeln!( f, "graph : \n\ttways are :" ).unwrap();
         for ( k, v ) in &infos {
-            write!( f, "\t{:20} => {:>7}\n", k, v ).unwrap();
+            writeln!( f, "\t{:20} => {:>7}", k, v ).unwrap();
         }
         write!(f, "")
     }
 
 }
+
+
+#[cfg(test)]
+mod graphe_tests {
+    use super::*;
+    use crate::graphe::testing::{tags, test_graph};
+
+    fn street( barrier: &str ) -> Graph {
+        let mut g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99)], &[(10, &[1, 2, 3], &[("highway", "residential")])] );
+        g.tnodes.insert( 2, TNode::new( 48.0, -1.995, tags( &[("barrier", barrier)] ) ) );
+        g
+    }
+
+    #[test]
+    fn directed_with_barriers() {
+        // des plots bloquent les voitures mais pas les vélos
+        let g = street( "bollard" );
+        assert!( g.get_directed_for( &Profile::Car ).is_empty() );
+        let bike = g.get_directed_for( &Profile::Bicycle );
+        assert_eq!( 2, bike[&2].len() );
+
+        // une barrière levante se franchit en 15 s, dans les deux sens
+        let g = street( "lift_gate" );
+        let car = g.get_directed_for( &Profile::Car );
+        let d = car[&1][0].distance;
+        assert!( ( car[&1][0].time - d / (50.0 / 3.6) - 15.0 ).abs() < 1e-9 );
+        assert!( ( car[&3][0].time - d / (50.0 / 3.6) - 15.0 ).abs() < 1e-9 );
+        assert!( car[&1][0].cost > d );
+        // en quittant la barrière, pas de pénalité
+        let from_gate = car[&2].iter().find( |e| e.node == 3 ).unwrap();
+        assert!( ( from_gate.time - d / (50.0 / 3.6) ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn directed_with_elevation() {
+        // montée de 20 m entre les noeuds 1 et 2, plat ensuite
+        let mut g = street( "no" );
+        for (id, ele) in [(1, 100.0), (2, 120.0), (3, 120.0)] { g.tnodes.get_mut( &id ).unwrap().set_ele( Some(ele) ); }
+        let bike = g.get_directed_for( &Profile::Bicycle );
+        let edge = |from: i64, to: i64| bike[&from].iter().find( |e| e.node == to ).unwrap().clone();
+        let ( up, down, flat ) = ( edge( 1, 2 ), edge( 2, 1 ), edge( 2, 3 ) );
+        let grade = 20.0 / up.distance;
+        assert!( ( up.time / flat.time - Profile::Bicycle.grade_factor( grade ) ).abs() < 1e-9 );
+        assert!( ( down.time / flat.time - Profile::Bicycle.grade_factor( -grade ) ).abs() < 1e-9 );
+        assert!( up.cost > flat.cost && down.cost < flat.cost );
+        // la voiture ignore la pente
+        let car = g.get_directed_for( &Profile::Car );
+        assert_eq!( car[&1][0].time, car[&1][0].distance / (50.0 / 3.6) );
+    }
+
+    #[test]
+    fn directed_with_metric() {
+        let mut g = street( "no" );
+        let length = |g: &Graph| g.get_directed_for( &Profile::Car )[&1][0].distance;
+        let haversine = length( &g );
+        let way = g.tways.values().next().unwrap().len();
+        g.set_metric( DistanceMetric::Vincenty );
+        assert_eq!( DistanceMetric::Vincenty, g.metric() );
+        // l'ellipsoïde et la sphère diffèrent de quelques dixièmes de pourcent
+        let vincenty = length( &g );
+        assert!( vincenty != haversine && ( vincenty / haversine - 1.0 ).abs() < 0.005 );
+        assert!( ( g.tways.values().next().unwrap().len() / way - vincenty / haversine ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn directed_with_access() {
+        // raccourci privé 2 -> 5 -> 3 et détour 2 -> 6 -> 3
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (5, 48.0, -1.985), (3, 48.0, -1.98), (6, 48.01, -1.99),
+                              (7, 48.0, -1.97), (8, 47.99, -2.0)],
+                            &[(10, &[1, 2], &[("highway", "residential")]),
+                              (11, &[2, 5, 3], &[("highway", "service"), ("access", "private")]),
+                              (12, &[2, 6, 3], &[("highway", "residential")]),
+                              (13, &[3, 7], &[("highway", "footway"), ("bicycle", "designated")]),
+                              (14, &[1, 8], &[("highway", "residential"), ("access", "no"), ("foot", "yes")])] );
+
+        let car = g.get_directed_for( &Profile::Car );
+        let edge = |from: i64, to: i64| car[&from].iter().find( |e| e.node == to ).unwrap().clone();
+        // la pénalité est due en entrant dans la zone privée, pas en la quittant ; elle ne compte pas dans la durée
+        assert!( edge( 2, 5 ).penalty == RESTRICTED && edge( 3, 5 ).penalty == RESTRICTED );
+        assert!( edge( 5, 3 ).penalty == 0.0 && edge( 5, 2 ).penalty == 0.0 );
+        assert!( edge( 2, 5 ).time < RESTRICTED && ( edge( 2, 5 ).time - edge( 5, 2 ).time ).abs() < 1e-9 );
+        // la pénalité s'applique pour toutes les grandeurs, distance comprise
+        for mode in ["time", "distance", "cost"] {
+            let path = |from: i64, to: i64| crate::routing::dijkstra::shortest_path( mode, &car, from, to ).unwrap().values().copied().collect::<Vec<i64>>();
+            assert_eq!( vec![1, 2, 6, 3], path( 1, 3 ) );
+            assert_eq!( vec![1, 2, 5], path( 1, 5 ) );
+        }
+
+        // footway ouvert aux vélos, voie interdite sauf aux piétons
+        assert!( !car.contains_key( &7 ) && !car.contains_key( &8 ) );
+        assert!( g.get_directed_for( &Profile::Bicycle ).contains_key( &7 ) );
+        assert!( !g.get_directed_for( &Profile::Bicycle ).contains_key( &8 ) );
+        assert!( g.get_directed_for( &Profile::Foot ).contains_key( &8 ) );
+    }
+}
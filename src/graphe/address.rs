@@ -0,0 +1,461 @@
+use super::*;
+
+use std::collections::HashSet;
+use crate::graphe::index::cell;
+
+
+// similarité minimale (1 - distance d'édition relative) pour que deux mots soient considérés égaux
+const FUZZY: f64 = 0.75;
+// score minimal de la partie rue d'une adresse pour qu'elle soit retenue
+const MIN_STREET: f64 = 0.6;
+// distance (m) en dessous de laquelle deux tronçons de même nom sont une seule rue
+const SAME_STREET: f64 = 1500.0;
+// distance (m) maximale pour reprendre le code postal et la commune d'une adresse voisine
+const NEIGHBOUR: f64 = 3000.0;
+
+// mots ignorés dans les requêtes et les noms de voies
+const STOP_WORDS: [&str; 12] = ["de", "du", "des", "la", "le", "les", "l", "d", "a", "au", "aux", "et"];
+// abréviations courantes
+const ABBREVIATIONS: [(&str, &str); 12] = [
+    ("st", "saint"), ("ste", "sainte"), ("bd", "boulevard"), ("bld", "boulevard"), ("av", "avenue"),
+    ("ave", "avenue"), ("r", "rue"), ("pl", "place"), ("rte", "route"), ("che", "chemin"),
+    ("imp", "impasse"), ("all", "allee"),
+];
+// indices de répétition des numéros
+const REPEATS: [&str; 4] = ["bis", "ter", "quater", "quinquies"];
+
+
+///
+/// nature d'une place : adresse (addr:*) ou voie nommée
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaceKind {
+    Address,
+    Street,
+}
+
+///
+/// une adresse ou une rue connue du géocodeur local
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Place {
+    pub kind: PlaceKind,
+    pub housenumber: Option<String>,
+    pub street: String,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    /// objets OSM d'origine
+    pub nodes: Vec<i64>,
+    pub ways: Vec<i64>,
+}
+
+impl Place {
+    ///
+    /// adresse en clair : "1 bis Rue de Brocéliande, 22000 Saint-Brieuc"
+    ///
+    pub fn label(&self) -> String {
+        let mut street = self.street.to_string();
+        if let Some(n) = &self.housenumber { street = format!( "{} {}", n, street ); }
+        let locality: Vec<&str> = [&self.postcode, &self.city].iter().filter_map( |x| x.as_deref() ).collect();
+        if locality.is_empty() { street } else { format!( "{}, {}", street, locality.join( " " ) ) }
+    }
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{} ({} , {})", self.label(), self.lat, self.lon )
+    }
+}
+
+
+// forme normalisée d'une adresse, utilisée pour la comparaison
+#[derive(Debug, Default)]
+struct Key {
+    number: Option<String>,
+    street: Vec<String>,
+    postcode: Option<String>,
+    city: Vec<String>,
+}
+
+///
+/// géocodeur hors ligne construit à partir des tags addr:* et des noms de voies (cf. reader::read_osm)
+///
+#[derive(Debug, Default)]
+pub struct AddressIndex {
+    places: Vec<Place>,
+    keys: Vec<Key>,
+    // mot normalisé -> places dont la rue ou la commune le contient
+    words: HashMap<String, Vec<usize>>,
+    // grille des adresses numérotées (cf. index::cell)
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl AddressIndex {
+    ///
+    /// nombre d'adresses et de rues indexées
+    ///
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    ///
+    /// index vide
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    ///
+    /// accès aux adresses et rues indexées
+    ///
+    pub fn places(&self) -> &Vec<Place> {
+        &self.places
+    }
+
+    ///
+    /// ajout d'une adresse portée par un noeud (addr:housenumber, addr:street ou addr:place, ...)
+    ///
+    pub fn add_node( &mut self, id: i64, tags: &HashMap<String, String>, lat: f64, lon: f64 ) {
+        if let Some(mut p) = from_tags( tags, lat, lon ) {
+            p.nodes.push( id );
+            self.places.push( p );
+        }
+    }
+
+    ///
+    /// ajout d'une way : adresse d'un bâtiment (au centre de ses noeuds) ou nom d'une voie
+    ///
+    pub fn add_way( &mut self, id: i64, tags: &HashMap<String, String>, refs: &[i64], tnodes: &HashMap<i64, TNode> ) {
+        let coords: Vec<(f64, f64)> = refs.iter().filter_map( |n| tnodes.get( n ) ).map( |n| (n.lat(), n.lon()) ).collect();
+        if coords.is_empty() { return; }
+
+        if tags.contains_key( "addr:housenumber" ) || tags.contains_key( "building" ) {
+            let n = coords.len() as f64;
+            let (lat, lon) = coords.iter().fold( (0.0, 0.0), |acc, c| (acc.0 + c.0 / n, acc.1 + c.1 / n) );
+            if let Some(mut p) = from_tags( tags, lat, lon ) {
+                p.ways.push( id );
+                self.places.push( p );
+            }
+        } else if let (Some(name), true) = ( tags.get( "name" ), tags.contains_key( "highway" ) ) {
+            let (lat, lon) = coords[coords.len() / 2];
+            self.places.push( Place {
+                kind: PlaceKind::Street, housenumber: None, street: name.to_string(), postcode: None, city: None,
+                lat, lon, nodes: Vec::new(), ways: vec![id],
+            } );
+        }
+    }
+
+    ///
+    /// fin de construction : les rues reprennent le code postal et la commune des adresses voisines,
+    /// les tronçons d'une même rue sont regroupés et les mots sont indexés
+    ///
+    pub fn finish(&mut self) {
+        let places = std::mem::take( &mut self.places );
+        let mut houses: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, p) in places.iter().enumerate() {
+            if p.kind == PlaceKind::Street { continue; }
+            houses.entry( words( &p.street ).join( " " ) ).or_default().push( i );
+        }
+
+        let mut streets: HashMap<String, Vec<usize>> = HashMap::new();
+        for p in places.iter() {
+            let mut p = p.clone();
+            let street = words( &p.street ).join( " " );
+            if p.kind == PlaceKind::Street {
+                // code postal et commune de l'adresse la plus proche sur la même rue
+                let neighbour = houses.get( &street ).and_then( |v| v.iter()
+                    .map( |i| ( distance_haversine( p.lat, p.lon, places[*i].lat, places[*i].lon ), *i ) )
+                    .filter( |(d, _)| *d < NEIGHBOUR )
+                    .min_by( |a, b| a.0.total_cmp( &b.0 ) ) );
+                if let Some((_, i)) = neighbour {
+                    p.postcode = places[i].postcode.clone();
+                    p.city = places[i].city.clone();
+                }
+
+                // regroupement avec un tronçon déjà vu
+                let group = streets.entry( street ).or_default();
+                let same = group.iter().copied().find( |i| {
+                    let q: &Place = &self.places[*i];
+                    q.city == p.city && ( p.city.is_some() || distance_haversine( p.lat, p.lon, q.lat, q.lon ) < SAME_STREET )
+                } );
+                if let Some(i) = same {
+                    self.places[i].ways.extend( p.ways );
+                    continue;
+                }
+                group.push( self.places.len() );
+            }
+            self.places.push( p );
+        }
+
+        self.keys = self.places.iter().map( key ).collect();
+        self.words.clear();
+        for (i, k) in self.keys.iter().enumerate() {
+            let unique: HashSet<&String> = k.street.iter().chain( k.city.iter() ).collect();
+            for w in unique { self.words.entry( w.to_string() ).or_default().push( i ); }
+        }
+
+        self.cells.clear();
+        for (i, p) in self.places.iter().enumerate() {
+            if p.housenumber.is_some() { self.cells.entry( cell( p.lat, p.lon ) ).or_default().push( i ); }
+        }
+    }
+
+    ///
+    /// adresses numérotées situées à moins de `radius` mètres, triées par distance
+    ///
+    pub fn nearest( &self, lat: f64, lon: f64, radius: f64 ) -> Vec<(f64, &Place)> {
+        let dlat = ( radius / 6371000.0 ).to_degrees();
+        let dlon = dlat / lat.to_radians().cos().max( 0.01 );
+        let (i0, j0) = cell( lat - dlat, lon - dlon );
+        let (i1, j1) = cell( lat + dlat, lon + dlon );
+
+        let mut result: Vec<(f64, &Place)> = Vec::new();
+        for i in i0..=i1 {
+            for j in j0..=j1 {
+                let Some(idxs) = self.cells.get( &(i, j) ) else { continue };
+                for idx in idxs.iter() {
+                    let p = &self.places[*idx];
+                    let d = distance_haversine( lat, lon, p.lat, p.lon );
+                    if d <= radius { result.push( (d, p) ); }
+                }
+            }
+        }
+        result.sort_by( |a, b| a.0.total_cmp( &b.0 ) );
+        result
+    }
+
+    ///
+    /// recherche approchée (accents, casse, abréviations et fautes de frappe tolérés)
+    /// retourne au plus `limit` résultats avec leur score (1.0 = correspondance parfaite), du meilleur au moins bon
+    ///
+    pub fn search( &self, query: &str, limit: usize ) -> Vec<(f64, &Place)> {
+        let q = parse_query( query );
+        if q.street.is_empty() { return Vec::new(); }
+
+        // candidats : les places contenant un mot proche d'un mot de la requête
+        let mut candidates: HashSet<usize> = HashSet::new();
+        for (w, idxs) in self.words.iter() {
+            if q.street.iter().any( |qw| similarity( qw, w ) >= FUZZY ) { candidates.extend( idxs.iter() ); }
+        }
+
+        let mut result: Vec<(f64, usize)> = candidates.into_iter()
+            .filter_map( |i| score( &q, &self.keys[i] ).map( |s| (s, i) ) )
+            .collect();
+        result.sort_by( |a, b| b.0.total_cmp( &a.0 ).then( a.1.cmp( &b.1 ) ) );
+        result.truncate( limit );
+        result.into_iter().map( |(s, i)| (s, &self.places[i]) ).collect()
+    }
+}
+
+///
+/// deux noms désignent la même voie (casse, accents, abréviations et mots vides ignorés)
+///
+pub fn same_street( a: &str, b: &str ) -> bool {
+    words( a ) == words( b )
+}
+
+// construction d'une place à partir des tags addr:*
+fn from_tags( tags: &HashMap<String, String>, lat: f64, lon: f64 ) -> Option<Place> {
+    let street = tags.get( "addr:street" ).or( tags.get( "addr:place" ) )?;
+    Some( Place {
+        kind: PlaceKind::Address,
+        housenumber: tags.get( "addr:housenumber" ).cloned(),
+        street: street.to_string(),
+        postcode: tags.get( "addr:postcode" ).cloned(),
+        city: tags.get( "addr:city" ).cloned(),
+        lat, lon, nodes: Vec::new(), ways: Vec::new(),
+    } )
+}
+
+fn key( p: &Place ) -> Key {
+    Key {
+        number: p.housenumber.as_ref().map( |n| normalize( n ).replace( ' ', "" ) ),
+        street: words( &p.street ),
+        postcode: p.postcode.as_ref().map( |c| c.trim().to_string() ),
+        city: p.city.as_ref().map( |c| words( c ) ).unwrap_or_default(),
+    }
+}
+
+// requête : numéro (éventuellement suivi de bis, ter, ... ou d'une lettre), code postal, puis les mots
+fn parse_query( query: &str ) -> Key {
+    let tokens: Vec<String> = normalize( query ).split_whitespace().map( |t| t.to_string() ).collect();
+    let mut k = Key::default();
+    let mut i = 0;
+    if let Some(t) = tokens.first() {
+        let digits: String = t.chars().take_while( |c| c.is_ascii_digit() ).collect();
+        if !digits.is_empty() && digits.len() < 5 {
+            let mut n = t.to_string();
+            if let Some(next) = tokens.get( 1 ) {
+                if REPEATS.contains( &next.as_str() ) || ( next.len() == 1 && next.as_str() <= "h" && next.as_str() != "a" ) {
+                    n.push_str( next );
+                    i = 1;
+                }
+            }
+            k.number = Some(n);
+            i += 1;
+        }
+    }
+    for t in tokens[i..].iter() {
+        if t.len() == 5 && t.chars().all( |c| c.is_ascii_digit() ) { k.postcode = Some(t.to_string()); }
+        else if let Some(w) = word( t ) { k.street.push( w ); }
+    }
+    k
+}
+
+// score d'une place pour la requête, None si la rue ne correspond pas
+fn score( q: &Key, k: &Key ) -> Option<f64> {
+    let best = |w: &String, among: &mut dyn Iterator<Item = &String>| among
+        .map( |x| similarity( w, x ) )
+        .filter( |s| *s >= FUZZY )
+        .fold( 0.0, f64::max );
+
+    // part des mots de la rue retrouvés dans la requête
+    let street = k.street.iter().map( |w| best( w, &mut q.street.iter() ) ).sum::<f64>() / k.street.len().max( 1 ) as f64;
+    if street < MIN_STREET { return None; }
+    // part des mots de la requête retrouvés dans la rue ou la commune
+    let coverage = q.street.iter().map( |w| best( w, &mut k.street.iter().chain( k.city.iter() ) ) ).sum::<f64>() / q.street.len() as f64;
+    let city = if k.city.is_empty() { 0.5 } else {
+        k.city.iter().map( |w| best( w, &mut q.street.iter() ) ).sum::<f64>() / k.city.len() as f64
+    };
+    let postcode = match (&q.postcode, &k.postcode) {
+        (Some(a), Some(b)) => if a == b { 1.0 } else { 0.0 },
+        _ => 0.5,
+    };
+    let number = match (&q.number, &k.number) {
+        (Some(a), Some(b)) => if a == b { 1.0 } else { 0.2 },
+        (None, None) => 1.0,
+        _ => 0.5,
+    };
+    Some( 0.4 * street + 0.3 * coverage + 0.1 * city + 0.1 * postcode + 0.1 * number )
+}
+
+///
+/// minuscules, sans accents ni ponctuation
+///
+pub fn normalize( s: &str ) -> String {
+    let mut r = String::with_capacity( s.len() );
+    for c in s.to_lowercase().chars() {
+        match c {
+            'à' | 'â' | 'ä' | 'á' | 'ã' => r.push( 'a' ),
+            'é' | 'è' | 'ê' | 'ë' => r.push( 'e' ),
+            'î' | 'ï' | 'í' | 'ì' => r.push( 'i' ),
+            'ô' | 'ö' | 'ó' | 'ò' | 'õ' => r.push( 'o' ),
+            'ù' | 'û' | 'ü' | 'ú' => r.push( 'u' ),
+            'ç' => r.push( 'c' ),
+            'ñ' => r.push( 'n' ),
+            'ÿ' => r.push( 'y' ),
+            'œ' => r.push_str( "oe" ),
+            'æ' => r.push_str( "ae" ),
+            c if c.is_alphanumeric() => r.push( c ),
+            _ => r.push( ' ' ),
+        }
+    }
+    r.split_whitespace().collect::<Vec<&str>>().join( " " )
+}
+
+// un mot normalisé, abréviations développées, sans les mots vides
+fn word( t: &str ) -> Option<String> {
+    if STOP_WORDS.contains( &t ) { return None; }
+    let w = ABBREVIATIONS.iter().find( |(a, _)| *a == t ).map( |(_, b)| *b ).unwrap_or( t );
+    Some( w.to_string() )
+}
+
+// les mots significatifs d'un nom
+fn words( s: &str ) -> Vec<String> {
+    normalize( s ).split_whitespace().filter_map( word ).collect()
+}
+
+// similarité entre deux mots : 1 - distance de Levenshtein / longueur du plus long
+fn similarity( a: &str, b: &str ) -> f64 {
+    if a == b { return 1.0; }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let sub = if a[i-1] == b[j-1] { 0 } else { 1 };
+            cur[j] = ( prev[j] + 1 ).min( cur[j-1] + 1 ).min( prev[j-1] + sub );
+        }
+        prev = cur;
+    }
+    1.0 - prev[b.len()] as f64 / a.len().max( b.len() ) as f64
+}
+
+
+#[cfg(test)]
+mod address_tests {
+    use super::*;
+    use crate::graphe::testing::{tags, test_nodes};
+
+    fn index() -> AddressIndex {
+        let tnodes = test_nodes( &[(1, 48.5100, -2.7700), (2, 48.5110, -2.7690), (3, 48.5120, -2.7680),
+                                   (4, 48.4100, -2.8190), (5, 48.4110, -2.8180)] );
+
+        let mut idx = AddressIndex::default();
+        idx.add_way( 10, &tags( &[("highway", "residential"), ("name", "Rue de Brocéliande")] ), &[1, 2], &tnodes );
+        idx.add_way( 11, &tags( &[("highway", "residential"), ("name", "Rue de Brocéliande")] ), &[2, 3], &tnodes );
+        idx.add_way( 12, &tags( &[("highway", "residential"), ("name", "Rue de Saint-Brieuc")] ), &[4, 5], &tnodes );
+        idx.add_node( 100, &tags( &[("addr:housenumber", "1 bis"), ("addr:street", "Rue de Brocéliande"),
+            ("addr:postcode", "22000"), ("addr:city", "Saint-Brieuc")] ), 48.5101, -2.7699 );
+        idx.add_node( 101, &tags( &[("addr:housenumber", "3"), ("addr:street", "Rue de Brocéliande"),
+            ("addr:postcode", "22000"), ("addr:city", "Saint-Brieuc")] ), 48.5103, -2.7697 );
+        idx.add_way( 200, &tags( &[("building", "yes"), ("addr:housenumber", "2"), ("addr:street", "Rue de Saint-Brieuc"),
+            ("addr:postcode", "22800"), ("addr:city", "Plaintel")] ), &[4, 5], &tnodes );
+        idx.finish();
+        idx
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!( "1 bis rue de broceliande 22000 saint brieuc", normalize( "1 Bis, Rue de Brocéliande 22000 SAINT-BRIEUC" ) );
+        assert_eq!( vec!["rue", "saint", "brieuc"], words( "R. de St Brieuc" ) );
+        assert!( similarity( "broceliande", "brocelainde" ) >= FUZZY );
+        assert!( similarity( "rue", "route" ) < FUZZY );
+    }
+
+    #[test]
+    fn build_index() {
+        let idx = index();
+        // deux tronçons de la même rue regroupés, la rue reprend la commune de ses adresses
+        assert_eq!( 5, idx.len() );
+        let street = idx.places().iter().find( |p| p.ways == vec![10, 11] ).unwrap();
+        assert_eq!( Some("Saint-Brieuc".to_string()), street.city );
+        let building = idx.places().iter().find( |p| p.ways == vec![200] ).unwrap();
+        assert!( ( building.lat - 48.4105 ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn search_address() {
+        let idx = index();
+        let r = idx.search( "1 bis rue de broceliande 22000 st brieuc", 3 );
+        assert_eq!( vec![100], r[0].1.nodes );
+        assert!( r[0].0 > 0.99 );
+        // la rue puis les autres numéros
+        assert_eq!( vec![10, 11], r[1].1.ways );
+        assert_eq!( vec![101], r[2].1.nodes );
+
+        // faute de frappe, sans accent ni code postal
+        let r = idx.search( "3 rue de brocelainde saint brieuc", 1 );
+        assert_eq!( vec![101], r[0].1.nodes );
+
+        // rue de Saint-Brieuc à Plaintel
+        let r = idx.search( "2 rue de saint brieuc plaintel", 1 );
+        assert_eq!( vec![200], r[0].1.ways );
+
+        assert!( idx.search( "impasse des lilas", 5 ).is_empty() );
+        assert!( idx.search( "22000", 5 ).is_empty() );
+    }
+
+    #[test]
+    fn nearest_address() {
+        let idx = index();
+        let r = idx.nearest( 48.5102, -2.7698, 50.0 );
+        assert_eq!( 2, r.len() );
+        assert!( r[0].0 <= r[1].0 );
+        assert!( idx.nearest( 48.5200, -2.7698, 50.0 ).is_empty() );
+        assert!( same_street( "R. de St-Brieuc", "Rue de Saint Brieuc" ) );
+    }
+}
@@ -0,0 +1,221 @@
+use super::*;
+
+use std::collections::HashSet;
+use osmpbf::{Relation, RelMemberType};
+
+
+// niveau administratif des communes françaises
+const COMMUNE_LEVEL: &str = "8";
+
+
+///
+/// une commune : relation boundary=administrative (admin_level=8) et ses anneaux (lat, lon)
+///
+#[derive(Debug, Clone)]
+pub struct Boundary {
+    pub id: i64,
+    pub name: String,
+    pub postcode: Option<String>,
+    pub outers: Vec<Vec<(f64, f64)>>,
+    pub inners: Vec<Vec<(f64, f64)>>,
+    // ways membres (outer, inner) en attente d'assemblage
+    members: (Vec<i64>, Vec<i64>),
+    // rectangle englobant (lat min, lon min, lat max, lon max)
+    bbox: (f64, f64, f64, f64),
+}
+
+impl Boundary {
+    ///
+    /// création à partir d'anneaux déjà assemblés
+    ///
+    pub fn new( id: i64, name: &str, postcode: Option<String>, outers: Vec<Vec<(f64, f64)>>, inners: Vec<Vec<(f64, f64)>> ) -> Self {
+        let mut b = Self { id, name: name.to_string(), postcode, outers, inners, members: (Vec::new(), Vec::new()), bbox: (0.0, 0.0, 0.0, 0.0) };
+        b.bbox = bbox( &b.outers );
+        b
+    }
+
+    ///
+    /// le point (lat, lon) est à l'intérieur de la commune
+    ///
+    pub fn contains( &self, lat: f64, lon: f64 ) -> bool {
+        if lat < self.bbox.0 || lon < self.bbox.1 || lat > self.bbox.2 || lon > self.bbox.3 { return false; }
+        self.outers.iter().any( |r| in_ring( lat, lon, r ) ) && !self.inners.iter().any( |r| in_ring( lat, lon, r ) )
+    }
+}
+
+
+///
+/// les communes lues dans le fichier osm.pbf (cf. reader::read_osm)
+///
+#[derive(Debug, Default)]
+pub struct Boundaries {
+    boundaries: Vec<Boundary>,
+    // ways membres des relations et leurs noeuds, le temps de la lecture
+    wanted: HashSet<i64>,
+    ways: HashMap<i64, Vec<i64>>,
+}
+
+impl Boundaries {
+    ///
+    /// nombre de communes
+    ///
+    pub fn len(&self) -> usize {
+        self.boundaries.len()
+    }
+
+    ///
+    /// aucune commune
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.boundaries.is_empty()
+    }
+
+    ///
+    /// ajout d'une commune déjà construite
+    ///
+    pub fn push( &mut self, b: Boundary ) {
+        self.boundaries.push( b );
+    }
+
+    ///
+    /// enregistrement d'une relation boundary=administrative de niveau commune
+    ///
+    pub fn add_relation( &mut self, r: &Relation ) {
+        let mut name = None;
+        let mut postcode = None;
+        let (mut administrative, mut commune) = (false, false);
+        for (k, v) in r.tags() {
+            match k {
+                "boundary" => { administrative = v == "administrative"; },
+                "admin_level" => { commune = v == COMMUNE_LEVEL; },
+                "name" => { name = Some( v.to_string() ); },
+                "postal_code" | "addr:postcode" => { postcode = Some( v.to_string() ); },
+                _ => {},
+            }
+        }
+        let (true, true, Some(name)) = ( administrative, commune, name ) else { return };
+
+        let mut b = Boundary::new( r.id(), &name, postcode, Vec::new(), Vec::new() );
+        for m in r.members() {
+            if m.member_type != RelMemberType::Way { continue; }
+            match m.role() {
+                Ok("inner") => { b.members.1.push( m.member_id ); },
+                Ok("outer") | Ok("") => { b.members.0.push( m.member_id ); },
+                _ => { continue; },
+            }
+            self.wanted.insert( m.member_id );
+        }
+        self.boundaries.push( b );
+    }
+
+    ///
+    /// la way est membre d'une commune
+    ///
+    pub fn wants( &self, id: i64 ) -> bool {
+        self.wanted.contains( &id )
+    }
+
+    ///
+    /// noeuds d'une way membre
+    ///
+    pub fn add_way( &mut self, id: i64, refs: Vec<i64> ) {
+        self.ways.insert( id, refs );
+    }
+
+    ///
+    /// assemblage des anneaux une fois tous les noeuds et ways lus
+    ///
+    pub fn finish( &mut self, tnodes: &HashMap<i64, TNode> ) {
+        for b in self.boundaries.iter_mut() {
+            let (outer, inner) = std::mem::take( &mut b.members );
+            if outer.is_empty() { continue; }
+            b.outers = rings( &outer, &self.ways, tnodes );
+            b.inners = rings( &inner, &self.ways, tnodes );
+            b.bbox = bbox( &b.outers );
+        }
+        self.boundaries.retain( |b| !b.outers.is_empty() );
+        self.wanted.clear();
+        self.ways.clear();
+    }
+
+    ///
+    /// la commune contenant le point (lat, lon)
+    ///
+    pub fn containing( &self, lat: f64, lon: f64 ) -> Option<&Boundary> {
+        self.boundaries.iter().find( |b| b.contains( lat, lon ) )
+    }
+}
+
+// assemblage des ways bout à bout en anneaux fermés
+fn rings( members: &[i64], ways: &HashMap<i64, Vec<i64>>, tnodes: &HashMap<i64, TNode> ) -> Vec<Vec<(f64, f64)>> {
+    let mut pending: Vec<&Vec<i64>> = members.iter().filter_map( |m| ways.get( m ) ).filter( |w| w.len() > 1 ).collect();
+    let mut result = Vec::new();
+    while let Some(first) = pending.pop() {
+        let mut ring: Vec<i64> = first.clone();
+        while ring.first() != ring.last() {
+            let last = *ring.last().unwrap();
+            let Some(i) = pending.iter().position( |w| w[0] == last || *w.last().unwrap() == last ) else { break };
+            let w = pending.swap_remove( i );
+            if w[0] == last { ring.extend( w.iter().skip( 1 ) ); }
+            else { ring.extend( w.iter().rev().skip( 1 ) ); }
+        }
+        let coords: Vec<(f64, f64)> = ring.iter().filter_map( |n| tnodes.get( n ) ).map( |n| (n.lat(), n.lon()) ).collect();
+        if coords.len() > 2 { result.push( coords ); }
+    }
+    result
+}
+
+fn bbox( rings: &[Vec<(f64, f64)>] ) -> (f64, f64, f64, f64) {
+    rings.iter().flatten().fold( (f64::MAX, f64::MAX, f64::MIN, f64::MIN), |b, (lat, lon)| {
+        ( b.0.min( *lat ), b.1.min( *lon ), b.2.max( *lat ), b.3.max( *lon ) )
+    } )
+}
+
+// lancer de rayon : nombre impair de traversées de l'anneau (fermé implicitement)
+fn in_ring( lat: f64, lon: f64, ring: &[(f64, f64)] ) -> bool {
+    let mut inside = false;
+    let mut j = ring.len() - 1;
+    for i in 0..ring.len() {
+        let (yi, xi) = ring[i];
+        let (yj, xj) = ring[j];
+        if ( yi > lat ) != ( yj > lat ) && lon < ( xj - xi ) * ( lat - yi ) / ( yj - yi ) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+
+#[cfg(test)]
+mod boundary_tests {
+    use super::*;
+    use crate::graphe::testing::test_nodes;
+
+    #[test]
+    fn assemble_and_contain() {
+        // carré 48.0..48.1 x -2.1..-2.0 en trois ways dont une à l'envers, avec un trou au centre
+        let tnodes = test_nodes( &[(1, 48.0, -2.1), (2, 48.0, -2.0), (3, 48.1, -2.0), (4, 48.1, -2.1),
+                                   (5, 48.04, -2.06), (6, 48.04, -2.04), (7, 48.06, -2.04), (8, 48.06, -2.06)] );
+        let mut ways: HashMap<i64, Vec<i64>> = HashMap::new();
+        ways.insert( 10, vec![1, 2] );
+        ways.insert( 11, vec![4, 3, 2] );
+        ways.insert( 12, vec![4, 1] );
+        ways.insert( 13, vec![5, 6, 7, 8, 5] );
+
+        let outers = rings( &[10, 11, 12], &ways, &tnodes );
+        assert_eq!( 1, outers.len() );
+        assert_eq!( 5, outers[0].len() );
+        let b = Boundary::new( 42, "Plaintel", Some("22940".to_string()), outers, rings( &[13], &ways, &tnodes ) );
+
+        assert!( b.contains( 48.02, -2.08 ) );
+        assert!( !b.contains( 48.05, -2.05 ) );
+        assert!( !b.contains( 48.2, -2.05 ) );
+        assert!( !b.contains( 48.05, -1.99 ) );
+
+        let mut all = Boundaries::default();
+        all.push( b );
+        assert_eq!( Some(42), all.containing( 48.09, -2.01 ).map( |b| b.id ) );
+        assert!( all.containing( 47.0, -2.0 ).is_none() );
+    }
+}
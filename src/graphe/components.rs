@@ -0,0 +1,234 @@
+use std::fmt;
+use std::collections::{HashMap, HashSet};
+
+use crate::graphe::Graph;
+use crate::graphe::elements::Edge;
+use crate::routing::profile::Profile;
+
+
+// îlots détaillés par l'affichage, et noeuds cités pour chacun
+const SHOWN_ISLANDS: usize = 10;
+const SHOWN_NODES: usize = 5;
+
+///
+/// composantes fortement connexes d'un graphe orienté (algorithme de Tarjan) : depuis un noeud
+/// on peut rejoindre tous les noeuds de sa composante, et en revenir
+///
+#[derive(Debug, Clone, Default)]
+pub struct Components {
+    component: HashMap<i64, usize>,
+    sizes: Vec<usize>,
+    main: usize,
+}
+
+impl Components {
+    pub fn new( adj: &HashMap<i64, Vec<Edge>> ) -> Self {
+        // noeuds de départ et d'arrivée des arcs, triés pour un numérotage reproductible
+        let mut nodes: Vec<i64> = adj.keys().copied()
+            .chain( adj.values().flat_map( |edges| edges.iter().map( |e| e.node ) ) )
+            .collect::<HashSet<i64>>().into_iter().collect();
+        nodes.sort();
+
+        let none: Vec<Edge> = Vec::new();
+        let successors = |n: i64| adj.get( &n ).unwrap_or( &none );
+        let mut c = Components::default();
+        let mut index: HashMap<i64, usize> = HashMap::new();
+        let mut low: HashMap<i64, usize> = HashMap::new();
+        let mut stack: Vec<i64> = Vec::new();
+        let mut on_stack: HashSet<i64> = HashSet::new();
+
+        for root in nodes {
+            if index.contains_key( &root ) { continue; }
+            // pile d'appels explicite : (noeud, prochain arc à explorer)
+            let mut calls: Vec<(i64, usize)> = vec![(root, 0)];
+            index.insert( root, index.len() );
+            low.insert( root, low.len() );
+            stack.push( root );
+            on_stack.insert( root );
+
+            while let Some(&(v, i)) = calls.last() {
+                if let Some(e) = successors( v ).get( i ) {
+                    calls.last_mut().unwrap().1 += 1;
+                    let w = e.node;
+                    if !index.contains_key( &w ) {
+                        index.insert( w, index.len() );
+                        low.insert( w, low.len() );
+                        stack.push( w );
+                        on_stack.insert( w );
+                        calls.push( (w, 0) );
+                    } else if on_stack.contains( &w ) {
+                        low.insert( v, low[&v].min( index[&w] ) );
+                    }
+                    continue;
+                }
+                calls.pop();
+                if let Some(&(u, _)) = calls.last() { low.insert( u, low[&u].min( low[&v] ) ); }
+                // v est la racine d'une composante : ses noeuds sont en haut de la pile
+                if low[&v] == index[&v] {
+                    let id = c.sizes.len();
+                    let mut size = 0;
+                    while let Some(w) = stack.pop() {
+                        on_stack.remove( &w );
+                        c.component.insert( w, id );
+                        size += 1;
+                        if w == v { break; }
+                    }
+                    c.sizes.push( size );
+                }
+            }
+        }
+        c.main = ( 0..c.sizes.len() ).max_by_key( |i| c.sizes[*i] ).unwrap_or( 0 );
+        c
+    }
+
+    ///
+    /// composantes du graphe orienté d'un profil
+    ///
+    pub fn of_profile( g: &Graph, profile: &Profile ) -> Self {
+        Self::new( &g.get_directed_for( profile ) )
+    }
+
+    ///
+    /// nombre de composantes
+    ///
+    pub fn len( &self ) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.sizes.is_empty()
+    }
+
+    ///
+    /// composante du noeud, None s'il n'est pas dans le graphe
+    ///
+    pub fn of( &self, node: i64 ) -> Option<usize> {
+        self.component.get( &node ).copied()
+    }
+
+    pub fn size( &self, component: usize ) -> usize {
+        self.sizes.get( component ).copied().unwrap_or( 0 )
+    }
+
+    ///
+    /// taille de la composante principale (la plus grande)
+    ///
+    pub fn main_size( &self ) -> usize {
+        self.size( self.main )
+    }
+
+    ///
+    /// le noeud est-il dans la composante principale
+    ///
+    pub fn is_main( &self, node: i64 ) -> bool {
+        self.of( node ) == Some( self.main )
+    }
+
+    ///
+    /// îlots : les autres composantes, de la plus grande à la plus petite, avec leurs noeuds
+    ///
+    pub fn islands( &self ) -> Vec<Vec<i64>> {
+        let mut islands: Vec<Vec<i64>> = vec![Vec::new(); self.sizes.len()];
+        for (n, c) in self.component.iter() {
+            if *c != self.main { islands[*c].push( *n ); }
+        }
+        islands.retain( |i| !i.is_empty() );
+        for i in islands.iter_mut() { i.sort(); }
+        islands.sort_by( |a, b| b.len().cmp( &a.len() ).then( a[0].cmp( &b[0] ) ) );
+        islands
+    }
+}
+
+///
+/// résumé : nombre de composantes, taille de la principale et plus grands îlots
+///
+impl fmt::Display for Components {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        let total: usize = self.sizes.iter().sum();
+        let percent = if total > 0 { 100.0 * self.main_size() as f64 / total as f64 } else { 0.0 };
+        write!( f, "{} composantes, principale : {} noeuds ({:.1} %)", self.len(), self.main_size(), percent )?;
+        let islands = self.islands();
+        for island in islands.iter().take( SHOWN_ISLANDS ) {
+            let nodes: Vec<String> = island.iter().take( SHOWN_NODES ).map( |n| n.to_string() ).collect();
+            let more = if island.len() > SHOWN_NODES { ", ..." } else { "" };
+            write!( f, "\n\t\tîlot de {} noeud(s) : {}{}", island.len(), nodes.join( ", " ), more )?;
+        }
+        if islands.len() > SHOWN_ISLANDS {
+            write!( f, "\n\t\t... et {} autres îlots", islands.len() - SHOWN_ISLANDS )?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// suppression des îlots : les ways dont aucun noeud n'est dans une composante d'au moins `min`
+/// noeuds, pour aucun des profils, sont retirées du graphe avec leurs noeuds. Renvoie le nombre
+/// de ways supprimées.
+///
+pub fn prune_islands( g: &mut Graph, min: usize ) -> usize {
+    let mut keep: HashSet<i64> = HashSet::new();
+    for profile in [Profile::Car, Profile::Bicycle, Profile::Foot] {
+        let c = Components::of_profile( g, &profile );
+        keep.extend( c.component.iter().filter( |(_, id)| c.size( **id ) >= min ).map( |(n, _)| *n ) );
+    }
+    let removed: Vec<i64> = g.tways.iter()
+        .filter( |(_, w)| !w.refs().iter().any( |n| keep.contains( n ) ) )
+        .map( |(id, _)| *id )
+        .collect();
+    for id in removed.iter() {
+        if let Some(w) = g.tways.remove( id ) {
+            for n in w.refs() {
+                if let Some(tn) = g.tnodes.get_mut( n ) { tn.remove_wayid( *id ); }
+            }
+        }
+    }
+    let used: HashSet<i64> = g.tways.values().flat_map( |w| w.refs().iter().copied() ).collect();
+    g.tnodes.retain( |id, _| used.contains( id ) );
+    removed.len()
+}
+
+
+#[cfg(test)]
+mod components_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    fn edge( node: i64 ) -> Edge {
+        Edge { node, distance: 1.0, time: 1.0, cost: 1.0, penalty: 0.0, way: 0 }
+    }
+
+    #[test]
+    fn tarjan() {
+        // boucle 1 -> 2 -> 3 -> 1, impasse à sens unique 3 -> 4, îlot 5 <-> 6
+        let mut adj: HashMap<i64, Vec<Edge>> = HashMap::new();
+        adj.insert( 1, vec![edge( 2 )] );
+        adj.insert( 2, vec![edge( 3 )] );
+        adj.insert( 3, vec![edge( 1 ), edge( 4 )] );
+        adj.insert( 5, vec![edge( 6 )] );
+        adj.insert( 6, vec![edge( 5 )] );
+        let c = Components::new( &adj );
+
+        assert_eq!( 3, c.len() );
+        assert_eq!( 3, c.main_size() );
+        assert!( c.is_main( 1 ) && c.is_main( 3 ) && !c.is_main( 4 ) && !c.is_main( 5 ) );
+        assert_eq!( c.of( 5 ), c.of( 6 ) );
+        assert_eq!( None, c.of( 7 ) );
+        assert_eq!( vec![vec![5, 6], vec![4]], c.islands() );
+        assert!( c.to_string().starts_with( "3 composantes, principale : 3 noeuds (50.0 %)" ) );
+    }
+
+    #[test]
+    fn prune() {
+        let rue = &[("highway", "residential")];
+        let mut g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.99), (4, 48.1, -2.0), (5, 48.1, -1.99)],
+                                &[(10, &[1, 2, 3], rue), (11, &[4, 5], rue)] );
+        for (w, nodes) in [(10, vec![1, 2, 3]), (11, vec![4, 5])] {
+            for n in nodes { g.tnodes.get_mut( &n ).unwrap().add_wayid( w ); }
+        }
+
+        assert_eq!( 0, prune_islands( &mut g, 2 ) );
+        assert_eq!( 1, prune_islands( &mut g, 3 ) );
+        assert!( g.tways.contains_key( &10 ) && !g.tways.contains_key( &11 ) );
+        assert_eq!( 3, g.tnodes.len() );
+    }
+}
@@ -16,6 +16,18 @@ pub struct Edge {
     pub distance: f64,
     pub time: f64,
     pub cost: f64,
+    // pénalité de routage (s) de l'entrée dans une zone à accès restreint, hors durée du trajet
+    pub penalty: f64,
+    pub way: i64,
+}
+
+impl Edge {
+    ///
+    /// pénalité de routage exprimée en distance, à la vitesse moyenne de l'arc
+    ///
+    pub fn penalty_distance( &self ) -> f64 {
+        if self.time > 0.0 { self.penalty * self.distance / self.time } else { 0.0 }
+    }
 }
 
 
@@ -32,6 +44,8 @@ pub struct TNode {
     ways: Vec<i64>,
     #[doc(hidden)]
     r#type: TNodeType,
+    #[doc(hidden)]
+    ele: Option<f64>,
 }
 
 ///
@@ -44,7 +58,7 @@ impl TNode {
     pub fn new( lat: f64, lon: f64,  t: HashMap<String,String> ) -> Self {
         let mut tags = HashMap::new();
         for (k, v) in t.iter() { tags.insert( k.to_string(), v.to_string() ); }
-        Self { lat: lat, lon: lon, tags: tags, r#type: TNodeType::UnUsed, ways: Vec::new() }
+        Self { lat, lon, tags, r#type: TNodeType::UnUsed, ways: Vec::new(), ele: None }
     }
 
     ///
@@ -53,7 +67,7 @@ impl TNode {
     pub fn from( dn: DenseNode ) -> Self {
         let mut tags = HashMap::new();
         for (k, v) in dn.tags() { tags.insert( k.to_string(), v.to_string() ); }
-        Self { lat: dn.lat(), lon: dn.lon(), tags: tags, r#type: TNodeType::UnUsed, ways: Vec::new()  }
+        Self { lat: dn.lat(), lon: dn.lon(), tags, r#type: TNodeType::UnUsed, ways: Vec::new(), ele: None  }
     }
 
     ///
@@ -70,6 +84,20 @@ impl TNode {
         self.lon
     }
 
+    ///
+    /// accès à l'altitude (m), si elle a été lue dans un modèle numérique de terrain (cf. elevation)
+    ///
+    pub fn ele(&self) -> Option<f64> {
+        self.ele
+    }
+
+    ///
+    /// modification de l'altitude
+    ///
+    pub fn set_ele(&mut self, e: Option<f64> ) {
+        self.ele = e;
+    }
+
     ///
     /// accès aux tags du point
     ///
@@ -106,6 +134,13 @@ impl TNode {
         self.ways.push( w );
     }
 
+    ///
+    /// retrait d'un wayid
+    ///
+    pub fn remove_wayid(&mut self, w: i64 ) {
+        self.ways.retain( |id| *id != w );
+    }
+
 
     ///
     /// récuperation du tableu des wayid
@@ -119,22 +154,22 @@ impl TNode {
 
 impl fmt::Display for TNode {
     fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
-        write!( f, "\tcoords : ({} , {}) :\n",
+        writeln!( f, "\tcoords : ({} , {}) :",
             Colour::Green.paint( self.lat.to_string() ),
             Colour::Green.paint( self.lon.to_string() ) ).unwrap();
-        if self.tags.len() > 0 {
-            write!(f, "\ttags : \n" ).unwrap();
+        if !self.tags.is_empty() {
+            writeln!(f, "\ttags : " ).unwrap();
             for (k, v) in self.tags() {
-                write!( f, "\t\t{} => {}\n", k, v).unwrap();
+                writeln!( f, "\t\t{} => {}", k, v).unwrap();
             }
         }
-        if self.ways.len() > 0 {
+        if !self.ways.is_empty() {
             write!(f, "\tways :" ).unwrap();
             for w in self.ways() {
                 write!( f, " {}", w).unwrap();
             }
         }
-        write!(f, "\n")
+        writeln!(f)
     }
 }
 
@@ -207,11 +242,11 @@ impl TWay {
         let Some(mut vo) = nodes.get( &refs[0] ) else { panic!( "start node must exist in db ... ")};
         let mut d = 0.0;
         for idx in refs.iter() {
-            let Some(vc) = nodes.get( &idx ) else { panic!( "cur node must exist in db ... ")};
+            let Some(vc) = nodes.get( idx ) else { panic!( "cur node must exist in db ... ")};
             d += distance_haversine( vo.lat(), vo.lon(), vc.lat(), vc.lon() );
             vo = vc
         }
-        Self { refs: refs, tags: tags, len: d }
+        Self { refs, tags, len: d }
     }
 
     ///
@@ -225,11 +260,11 @@ impl TWay {
         let Some(mut vo) = nodes.get( &refs[0] ) else { panic!( "start node must exist in db ... ")};
         let mut d = 0.0;
         for idx in refs.iter() {
-            let Some(vc) = nodes.get( &idx ) else { panic!( "cur node must exist in db ... ")};
+            let Some(vc) = nodes.get( idx ) else { panic!( "cur node must exist in db ... ")};
             d += distance_haversine( vo.lat(), vo.lon(), vc.lat(), vc.lon() );
             vo = vc
         }
-        Self { refs: refs, tags: tags, len: d }
+        Self { refs, tags, len: d }
     }
 
     ///
@@ -261,6 +296,16 @@ impl TWay {
         *last
     }
 
+    ///
+    /// nouveau calcul de la longueur avec la formule de distance donnée
+    ///
+    pub fn measure(&mut self, nodes: &HashMap<i64, TNode>, metric: DistanceMetric ) {
+        self.len = self.refs.windows( 2 )
+            .filter_map( |p| nodes.get( &p[0] ).zip( nodes.get( &p[1] ) ) )
+            .map( |(a, b)| metric.distance( a.lat(), a.lon(), b.lat(), b.lon() ) )
+            .sum();
+    }
+
     ///
     /// accès à la longeur de l'Arc
     ///
@@ -287,10 +332,10 @@ impl fmt::Display for TWay {
             write!( f, " {},", Colour::Blue.paint( n.to_string() ) ).unwrap();
         }
         write!(f, "\n\tlongueur : {}", self.len).unwrap();
-        if self.tags.len() > 0 {
-            write!( f, "\n\ttags :\n" ).unwrap();
+        if !self.tags.is_empty() {
+            writeln!( f, "\n\ttags :" ).unwrap();
             for (k, v) in self.tags() {
-                write!( f, "\t\t{} => {}\n", k, v).unwrap();
+                writeln!( f, "\t\t{} => {}", k, v).unwrap();
             }// pub struct Seg {
 //     dist: f64,
 //     old: i64
@@ -331,12 +376,9 @@ mod tway_tests {
         let mut tnodes: HashMap<i64, TNode> = HashMap::new();
 
         reader.for_each( |element| {
-            match element {
-                Element::DenseNode(dne) => {
-                    let p = TNode::from( dne.clone() );
-                    tnodes.insert( dne.id(), p );
-                },
-                _ => {},
+            if let Element::DenseNode(dne) = element {
+                let p = TNode::from( dne.clone() );
+                tnodes.insert( dne.id(), p );
             }
         } ). unwrap();
 
@@ -366,10 +408,8 @@ mod tway_tests {
                     let p = TNode::from( dne.clone() );
                     peaks.insert( dne.id(), p );
                 },
-                Element::Way(we) => {
-                    if we.id() == 4945346 {
-                        p = Some( TWay::from(we.clone(), &peaks) );
-                    }
+                Element::Way(we) if we.id() == 4945346 => {
+                    p = Some( TWay::from(we.clone(), &peaks) );
                 },
                 _ => {},
             }
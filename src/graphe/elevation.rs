@@ -0,0 +1,337 @@
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use tiff::TiffError;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+use crate::graphe::Graph;
+use crate::routing::distances::distance_haversine;
+
+
+// valeur des points sans donnée des tuiles SRTM
+const VOID: i16 = -32768;
+
+// pente (dénivelé / distance) retenue au plus pour le coût d'un segment, au-delà c'est du bruit du MNT
+pub const MAX_GRADE: f64 = 0.3;
+
+// distance (m) sur laquelle est mesurée la pente maximale d'un itinéraire
+const GRADE_DISTANCE: f64 = 50.0;
+
+
+#[derive(Debug)]
+pub enum DemError {
+    Io(io::Error),
+    Tiff(TiffError),
+    Format(String),
+}
+
+impl fmt::Display for DemError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            DemError::Io(e) => write!( f, "lecture du MNT impossible : {}", e ),
+            DemError::Tiff(e) => write!( f, "erreur GeoTIFF : {}", e ),
+            DemError::Format(s) => write!( f, "tuile invalide : {}", s ),
+        }
+    }
+}
+
+impl std::error::Error for DemError {}
+
+impl From<io::Error> for DemError {
+    fn from(e: io::Error) -> Self {
+        DemError::Io(e)
+    }
+}
+
+impl From<TiffError> for DemError {
+    fn from(e: TiffError) -> Self {
+        DemError::Tiff(e)
+    }
+}
+
+
+///
+/// grille d'altitudes : `rows` x `cols` points espacés de `step_lat` x `step_lon` degrés,
+/// le premier au nord-ouest ; NaN pour les points sans donnée
+///
+#[derive(Debug, Clone)]
+struct Tile {
+    north: f64,
+    west: f64,
+    step_lat: f64,
+    step_lon: f64,
+    rows: usize,
+    cols: usize,
+    data: Vec<f32>,
+}
+
+impl Tile {
+    ///
+    /// tuile SRTM : le nom ("N48W003") donne le coin sud-ouest, le contenu est un carré d'entiers
+    /// 16 bits big-endian (1201 x 1201 points en 3", 3601 x 3601 en 1")
+    ///
+    fn hgt( name: &str, bytes: &[u8] ) -> Result<Tile, DemError> {
+        let invalid = || DemError::Format( format!( "{} : nom de tuile SRTM attendu (N48W003.hgt)", name ) );
+        let name = name.to_uppercase();
+        let ( lat, lon ) = ( name.get( 1..3 ).ok_or_else( invalid )?, name.get( 4..7 ).ok_or_else( invalid )? );
+        let ( lat, lon ) = ( lat.parse::<f64>().map_err( |_| invalid() )?, lon.parse::<f64>().map_err( |_| invalid() )? );
+        let lat = match &name[0..1] { "N" => lat, "S" => -lat, _ => { return Err( invalid() ); } };
+        let lon = match &name[3..4] { "E" => lon, "W" => -lon, _ => { return Err( invalid() ); } };
+
+        let size = ( ( bytes.len() / 2 ) as f64 ).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            return Err( DemError::Format( format!( "{} : {} octets, pas une grille carrée", name, bytes.len() ) ) );
+        }
+        let data = bytes.chunks_exact( 2 )
+            .map( |b| i16::from_be_bytes( [b[0], b[1]] ) )
+            .map( |v| if v == VOID { f32::NAN } else { v as f32 } )
+            .collect();
+        let step = 1.0 / ( size - 1 ) as f64;
+        Ok( Tile { north: lat + 1.0, west: lon, step_lat: step, step_lon: step, rows: size, cols: size, data } )
+    }
+
+    ///
+    /// MNT GeoTIFF en degrés (EPSG:4326), une bande : l'emprise est donnée par les tags ModelPixelScale
+    /// et ModelTiepoint, les valeurs sont au centre des pixels ; GDAL_NODATA donne les points sans donnée
+    ///
+    fn geotiff( path: &Path ) -> Result<Tile, DemError> {
+        let mut decoder = Decoder::new( BufReader::new( File::open( path )? ) )?;
+        let ( cols, rows ) = decoder.dimensions()?;
+        let ( cols, rows ) = ( cols as usize, rows as usize );
+        let scale = decoder.get_tag_f64_vec( Tag::ModelPixelScaleTag )?;
+        let tie = decoder.get_tag_f64_vec( Tag::ModelTiepointTag )?;
+        if scale.len() < 2 || tie.len() < 6 || rows < 2 || cols < 2 {
+            return Err( DemError::Format( format!( "{} : géoréférencement absent", path.display() ) ) );
+        }
+        let nodata = decoder.get_tag_ascii_string( Tag::GdalNodata ).ok().and_then( |s| s.trim_matches( char::from( 0 ) ).trim().parse::<f32>().ok() );
+        let data: Vec<f32> = match decoder.read_image()? {
+            DecodingResult::I16(v) => v.into_iter().map( |x| x as f32 ).collect(),
+            DecodingResult::U16(v) => v.into_iter().map( |x| x as f32 ).collect(),
+            DecodingResult::I32(v) => v.into_iter().map( |x| x as f32 ).collect(),
+            DecodingResult::F32(v) => v,
+            DecodingResult::F64(v) => v.into_iter().map( |x| x as f32 ).collect(),
+            _ => { return Err( DemError::Format( format!( "{} : type de valeurs non géré", path.display() ) ) ); },
+        };
+        if data.len() != rows * cols {
+            return Err( DemError::Format( format!( "{} : une seule bande attendue", path.display() ) ) );
+        }
+        let data = data.into_iter()
+            .map( |v| if Some(v) == nodata || v == VOID as f32 { f32::NAN } else { v } )
+            .collect();
+        let ( sx, sy ) = ( scale[0], scale[1] );
+        Ok( Tile {
+            north: tie[4] + tie[1] * sy - sy / 2.0,
+            west: tie[3] - tie[0] * sx + sx / 2.0,
+            step_lat: sy,
+            step_lon: sx,
+            rows,
+            cols,
+            data,
+        } )
+    }
+
+    fn at( &self, row: usize, col: usize ) -> Option<f64> {
+        let v = self.data[row * self.cols + col];
+        if v.is_nan() { None } else { Some( v as f64 ) }
+    }
+
+    ///
+    /// altitude interpolée entre les quatre points qui entourent (lat, lon), les points sans donnée
+    /// étant ignorés ; None hors de la tuile
+    ///
+    fn elevation( &self, lat: f64, lon: f64 ) -> Option<f64> {
+        let y = ( self.north - lat ) / self.step_lat;
+        let x = ( lon - self.west ) / self.step_lon;
+        if !( 0.0..=( self.rows - 1 ) as f64 ).contains( &y ) || !( 0.0..=( self.cols - 1 ) as f64 ).contains( &x ) { return None; }
+        let ( row, col ) = ( ( y as usize ).min( self.rows - 2 ), ( x as usize ).min( self.cols - 2 ) );
+        let ( fy, fx ) = ( y - row as f64, x - col as f64 );
+        let corners = [
+            ( self.at( row, col ), ( 1.0 - fy ) * ( 1.0 - fx ) ),
+            ( self.at( row, col + 1 ), ( 1.0 - fy ) * fx ),
+            ( self.at( row + 1, col ), fy * ( 1.0 - fx ) ),
+            ( self.at( row + 1, col + 1 ), fy * fx ),
+        ];
+        let ( sum, weight ) = corners.iter()
+            .filter_map( |(v, w)| v.map( |v| ( v * w, *w ) ) )
+            .fold( ( 0.0, 0.0 ), |acc, (v, w)| ( acc.0 + v, acc.1 + w ) );
+        if weight > 0.0 { Some( sum / weight ) } else { None }
+    }
+}
+
+///
+/// modèle numérique de terrain : les tuiles SRTM (.hgt) et GeoTIFF (.tif) d'un répertoire
+///
+#[derive(Debug, Clone, Default)]
+pub struct Dem {
+    tiles: Vec<Tile>,
+}
+
+impl Dem {
+    ///
+    /// lecture des tuiles du répertoire, les autres fichiers sont ignorés
+    ///
+    pub fn load_dir( dir: &Path ) -> Result<Self, DemError> {
+        let mut paths: Vec<_> = fs::read_dir( dir )?.filter_map( |e| e.ok() ).map( |e| e.path() ).collect();
+        paths.sort();
+        let mut dem = Dem::default();
+        for path in paths {
+            let ext = path.extension().and_then( |e| e.to_str() ).map( |e| e.to_lowercase() );
+            match ext.as_deref() {
+                Some("hgt") => {
+                    let name = path.file_stem().and_then( |s| s.to_str() ).unwrap_or( "" ).to_string();
+                    dem.tiles.push( Tile::hgt( &name, &fs::read( &path )? )? );
+                },
+                Some("tif" | "tiff") => { dem.tiles.push( Tile::geotiff( &path )? ); },
+                _ => {},
+            }
+        }
+        Ok(dem)
+    }
+
+    ///
+    /// nombre de tuiles
+    ///
+    pub fn len( &self ) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.tiles.is_empty()
+    }
+
+    ///
+    /// altitude (m) interpolée au point, None hors des tuiles
+    ///
+    pub fn elevation( &self, lat: f64, lon: f64 ) -> Option<f64> {
+        self.tiles.iter().find_map( |t| t.elevation( lat, lon ) )
+    }
+}
+
+///
+/// altitude de chaque noeud du graphe ; renvoie le nombre de noeuds couverts par le MNT
+///
+pub fn set_elevation( g: &mut Graph, dem: &Dem ) -> usize {
+    let mut count = 0;
+    for n in g.tnodes.values_mut() {
+        let ele = dem.elevation( n.lat(), n.lon() );
+        if ele.is_some() { count += 1; }
+        n.set_ele( ele );
+    }
+    count
+}
+
+///
+/// pente du segment from -> to de longueur `d` (m), bornée à MAX_GRADE ; 0 sans altitude
+///
+pub fn grade( g: &Graph, from: i64, to: i64, d: f64 ) -> f64 {
+    let ele = |n: i64| g.tnodes.get( &n ).and_then( |n| n.ele() );
+    match ( ele( from ), ele( to ) ) {
+        ( Some(a), Some(b) ) if d > 0.0 => ( ( b - a ) / d ).clamp( -MAX_GRADE, MAX_GRADE ),
+        _ => 0.0,
+    }
+}
+
+///
+/// profil en long d'un itinéraire : dénivelés positif et négatif (m), pente maximale en montée
+/// (mesurée sur GRADE_DISTANCE), altitudes extrêmes
+///
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Climb {
+    pub ascent: f64,
+    pub descent: f64,
+    pub max_grade: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Climb {
+    ///
+    /// profil de la suite de noeuds, None si aucun n'a d'altitude
+    ///
+    pub fn of( g: &Graph, nodes: &[i64] ) -> Option<Climb> {
+        let points: Vec<(f64, f64, f64)> = nodes.iter()
+            .filter_map( |n| g.tnodes.get( n ) )
+            .filter_map( |n| n.ele().map( |e| (n.lat(), n.lon(), e) ) )
+            .collect();
+        let first = points.first()?;
+        let mut c = Climb { min: first.2, max: first.2, ..Default::default() };
+        // début de la portion sur laquelle la pente est mesurée, et sa longueur
+        let ( mut anchor, mut run, mut measured ) = ( first.2, 0.0, false );
+        for pair in points.windows( 2 ) {
+            let ( a, b ) = ( pair[0], pair[1] );
+            let dz = b.2 - a.2;
+            if dz > 0.0 { c.ascent += dz; } else { c.descent -= dz; }
+            c.min = c.min.min( b.2 );
+            c.max = c.max.max( b.2 );
+            run += distance_haversine( a.0, a.1, b.0, b.1 );
+            if run >= GRADE_DISTANCE {
+                c.max_grade = c.max_grade.max( ( b.2 - anchor ) / run );
+                ( anchor, run, measured ) = ( b.2, 0.0, true );
+            }
+        }
+        // itinéraire plus court que GRADE_DISTANCE : pente moyenne
+        if !measured && run > 0.0 {
+            c.max_grade = ( points[points.len() - 1].2 - anchor ) / run;
+        }
+        Some(c)
+    }
+}
+
+impl fmt::Display for Climb {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "+{:.0} m / -{:.0} m, pente max {:.1} %, altitude {:.0} à {:.0} m",
+            self.ascent, self.descent, self.max_grade * 100.0, self.min, self.max )
+    }
+}
+
+
+#[cfg(test)]
+mod elevation_tests {
+    use super::*;
+    use crate::graphe::testing::{test_nodes, test_ways};
+
+    // tuile 3 x 3 (pas de 0.5°) : 100 m au nord, 200 m au centre, 300 m au sud, un point sans donnée
+    fn tile() -> Tile {
+        let values: [i16; 9] = [100, 100, 100, 200, 200, VOID, 300, 300, 300];
+        let bytes: Vec<u8> = values.iter().flat_map( |v| v.to_be_bytes() ).collect();
+        Tile::hgt( "N48W003", &bytes ).unwrap()
+    }
+
+    #[test]
+    fn hgt_tile() {
+        let t = tile();
+        assert_eq!( (49.0, -3.0, 3), (t.north, t.west, t.rows) );
+        assert_eq!( Some(100.0), t.elevation( 49.0, -3.0 ) );
+        assert_eq!( Some(150.0), t.elevation( 48.75, -2.75 ) );
+        assert_eq!( Some(300.0), t.elevation( 48.0, -2.0 ) );
+        // le point sans donnée est ignoré
+        assert_eq!( Some(200.0), t.elevation( 48.5, -2.25 ) );
+        assert_eq!( None, t.elevation( 48.5, -2.0 ) );
+        assert_eq!( None, t.elevation( 47.9, -2.5 ) );
+        assert!( Tile::hgt( "N48W003", &[0; 10] ).is_err() );
+        assert!( Tile::hgt( "X48W003", &[0; 8] ).is_err() );
+        assert_eq!( (-12.0, 45.0), { let t = Tile::hgt( "s13e045", &[0; 8] ).unwrap(); (t.north, t.west) } );
+    }
+
+    #[test]
+    fn route_climb() {
+        let dem = Dem { tiles: vec![tile()] };
+        // du nord vers le sud puis retour : 100 m de montée et autant de descente
+        let tnodes = test_nodes( &[(1, 48.75, -2.75), (2, 48.7496, -2.75), (3, 48.749, -2.75), (4, 48.7496, -2.75)] );
+        let tways = test_ways( &tnodes, &[(10, &[1, 2, 3], &[])] );
+        let mut g = Graph::new( "climb".to_string(), tnodes, tways );
+        assert_eq!( 4, set_elevation( &mut g, &dem ) );
+
+        let c = Climb::of( &g, &[1, 2, 3, 4] ).unwrap();
+        assert!( ( c.ascent - 0.2 ).abs() < 1e-6 && ( c.descent - 0.12 ).abs() < 1e-6 );
+        assert!( ( c.max - 150.2 ).abs() < 1e-6 && ( c.min - 150.0 ).abs() < 1e-6 );
+        assert!( ( c.max_grade - 0.2 / 111.19 ).abs() < 1e-4 );
+        let d = distance_haversine( 48.75, -2.75, 48.7496, -2.75 );
+        assert!( ( grade( &g, 1, 2, d ) - 0.08 / d ).abs() < 1e-9 );
+        assert_eq!( 0.0, grade( &g, 1, 5, d ) );
+        assert_eq!( None, Climb::of( &g, &[5] ) );
+    }
+}
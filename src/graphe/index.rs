@@ -0,0 +1,186 @@
+use super::*;
+
+use std::collections::HashSet;
+use std::f64::consts::PI;
+
+// taille d'une cellule de la grille en degrés (environ 200 m en latitude)
+const CELL: f64 = 0.002;
+// rayon terrestre en mètres (cf. routing::distances)
+const EARTH_RADIUS: f64 = 6371000.0;
+
+
+///
+/// un segment de voie : deux noeuds consécutifs d'une TWay
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Segment {
+    pub from: i64,
+    pub to: i64,
+    pub way: i64,
+}
+
+///
+/// projection d'un point sur un segment
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Projection {
+    pub segment: Segment,
+    /// coordonnées du point projeté
+    pub lat: f64,
+    pub lon: f64,
+    /// position sur le segment (0.0 = from, 1.0 = to)
+    pub frac: f64,
+    /// distance en mètres entre le point et sa projection
+    pub distance: f64,
+}
+
+
+// un segment et les coordonnées (lat, lon) de ses extrémités
+type Located = (Segment, (f64, f64), (f64, f64));
+
+///
+/// index spatial des segments du graphe : grille régulière en lat/lon
+///
+pub struct SpatialIndex {
+    segments: Vec<Located>,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+///
+/// cellule de la grille contenant le point (lat, lon)
+///
+pub fn cell( lat: f64, lon: f64 ) -> (i64, i64) {
+    ( (lat / CELL).floor() as i64, (lon / CELL).floor() as i64 )
+}
+
+impl SpatialIndex {
+    ///
+    /// indexation de tous les segments des tways du graphe
+    ///
+    pub fn new( g: &Graph ) -> Self {
+        let mut segments = Vec::new();
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+
+        for (id, w) in g.tways.iter() {
+            for pair in w.refs().windows( 2 ) {
+                let (Some(a), Some(b)) = ( g.tnodes.get( &pair[0] ), g.tnodes.get( &pair[1] ) ) else { continue };
+                let idx = segments.len();
+                segments.push( ( Segment { from: pair[0], to: pair[1], way: *id }, (a.lat(), a.lon()), (b.lat(), b.lon()) ) );
+
+                // le segment est rangé dans toutes les cellules couvertes par son rectangle englobant
+                let (i0, j0) = cell( a.lat().min( b.lat() ), a.lon().min( b.lon() ) );
+                let (i1, j1) = cell( a.lat().max( b.lat() ), a.lon().max( b.lon() ) );
+                for i in i0..=i1 {
+                    for j in j0..=j1 {
+                        cells.entry( (i, j) ).or_default().push( idx );
+                    }
+                }
+            }
+        }
+        Self { segments, cells }
+    }
+
+    ///
+    /// nombre de segments indexés
+    ///
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    ///
+    /// index vide
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    ///
+    /// projections du point sur les segments situés à moins de `radius` mètres, triées par distance
+    ///
+    pub fn within( &self, lat: f64, lon: f64, radius: f64 ) -> Vec<Projection> {
+        let dlat = ( radius / EARTH_RADIUS ).to_degrees();
+        let dlon = dlat / lat.to_radians().cos().max( 0.01 );
+        let (i0, j0) = cell( lat - dlat, lon - dlon );
+        let (i1, j1) = cell( lat + dlat, lon + dlon );
+
+        let mut seen: HashSet<usize> = HashSet::new();
+        let mut result: Vec<Projection> = Vec::new();
+        for i in i0..=i1 {
+            for j in j0..=j1 {
+                let Some(idxs) = self.cells.get( &(i, j) ) else { continue };
+                for idx in idxs.iter() {
+                    if !seen.insert( *idx ) { continue; }
+                    let (s, a, b) = &self.segments[*idx];
+                    let p = project( lat, lon, *a, *b );
+                    if p.3 <= radius {
+                        result.push( Projection { segment: *s, lat: p.0, lon: p.1, frac: p.2, distance: p.3 } );
+                    }
+                }
+            }
+        }
+        result.sort_by( |x, y| x.distance.total_cmp( &y.distance ) );
+        result
+    }
+
+    ///
+    /// projection sur le segment le plus proche, en élargissant la recherche si besoin
+    ///
+    pub fn nearest( &self, lat: f64, lon: f64 ) -> Option<Projection> {
+        if self.segments.is_empty() { return None; }
+        let mut radius = 100.0;
+        loop {
+            if let Some(p) = self.within( lat, lon, radius ).first() { return Some(*p); }
+            radius *= 4.0;
+        }
+    }
+}
+
+///
+/// projection du point (lat, lon) sur le segment [a, b] dans un repère plan local
+/// retourne (lat, lon, fraction, distance en mètres)
+///
+pub fn project( lat: f64, lon: f64, a: (f64, f64), b: (f64, f64) ) -> (f64, f64, f64, f64) {
+    let k = PI / 180.0 * EARTH_RADIUS;
+    let c = lat.to_radians().cos();
+    let (ax, ay) = ( (a.1 - lon) * c * k, (a.0 - lat) * k );
+    let (bx, by) = ( (b.1 - lon) * c * k, (b.0 - lat) * k );
+    let (dx, dy) = ( bx - ax, by - ay );
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 { ( -( ax * dx + ay * dy ) / len2 ).clamp( 0.0, 1.0 ) } else { 0.0 };
+    let (px, py) = ( ax + t * dx, ay + t * dy );
+    ( a.0 + t * (b.0 - a.0), a.1 + t * (b.1 - a.1), t, ( px * px + py * py ).sqrt() )
+}
+
+
+#[cfg(test)]
+mod index_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    fn line() -> Graph {
+        test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.99)], &[(7, &[1, 2, 3], &[])] )
+    }
+
+    #[test]
+    fn test_project() {
+        let (lat, lon, t, d) = project( 48.0001, -1.995, (48.0, -2.0), (48.0, -1.99) );
+        assert_eq!( 48.0, lat );
+        assert!( ( lon + 1.995 ).abs() < 1e-9 );
+        assert!( ( t - 0.5 ).abs() < 1e-9 );
+        assert!( ( d - 11.119 ).abs() < 0.01 );
+    }
+
+    #[test]
+    fn test_within() {
+        let idx = SpatialIndex::new( &line() );
+        assert_eq!( 2, idx.len() );
+
+        let r = idx.within( 48.0001, -1.995, 50.0 );
+        assert_eq!( 1, r.len() );
+        assert_eq!( Segment { from: 1, to: 2, way: 7 }, r[0].segment );
+        // près du coin les deux segments sont candidats
+        assert_eq!( 2, idx.within( 48.0001, -1.9901, 50.0 ).len() );
+        assert!( idx.within( 48.1, -1.995, 50.0 ).is_empty() );
+        assert_eq!( 3, idx.nearest( 48.1, -1.99 ).unwrap().segment.to );
+    }
+}
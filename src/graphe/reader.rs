@@ -1,70 +1,171 @@
 use super::*;
 
-use osmpbf::{ElementReader, Element};
+use osmpbf::{ElementReader, Element, Relation, RelMemberType};
 use crate::graphe::Graph;
+use crate::graphe::address::AddressIndex;
+use crate::graphe::boundary::Boundaries;
 use crate::graphe::elements::{TNode, TWay};
+use crate::graphe::validate::{Check, Problem};
+use crate::routing::profile::{ROUTABLE, is_ferry};
 
-fn register_tnode( dne: DenseNode, tnodes: &mut HashMap<i64, TNode> ) {
+fn register_tnode( dne: DenseNode, tnodes: &mut HashMap<i64, TNode>, addresses: &mut AddressIndex ) {
     let p = TNode::from( dne.clone() );
+    addresses.add_node( dne.id(), p.tags(), p.lat(), p.lon() );
     tnodes.insert( dne.id(), p );
 }
 
-fn register_tway( we: Way, tways: &mut HashMap<i64, TWay>, tnodes: &mut HashMap<i64, TNode>  ) {
+///
+/// adresses des bâtiments (addr:*) et noms des voies
+///
+fn register_address( we: &Way, tnodes: &HashMap<i64, TNode>, addresses: &mut AddressIndex ) {
+    let tags: HashMap<String, String> = we.tags()
+        .filter( |(k, _)| k.starts_with( "addr:" ) || *k == "building" || *k == "name" || *k == "highway" )
+        .map( |(k, v)| (k.to_string(), v.to_string()) )
+        .collect();
+    if tags.contains_key( "addr:street" ) || tags.contains_key( "addr:place" ) || tags.contains_key( "name" ) {
+        let refs: Vec<i64> = we.refs().collect();
+        addresses.add_way( we.id(), &tags, &refs, tnodes );
+    }
+}
+
+///
+/// relation route=ferry : ses tags (nom, accès, ...) sont reportés sur les ways membres,
+/// la durée seulement si la relation n'a qu'une way
+///
+fn register_ferry( r: &Relation, ferries: &mut HashMap<i64, HashMap<String, String>> ) {
+    let mut tags: HashMap<String, String> = r.tags()
+        .filter( |(k, _)| *k != "type" )
+        .map( |(k, v)| (k.to_string(), v.to_string()) )
+        .collect();
+    if !is_ferry( &tags ) { return; }
+    let members: Vec<i64> = r.members().filter( |m| m.member_type == RelMemberType::Way ).map( |m| m.member_id ).collect();
+    if members.len() > 1 { tags.remove( "duration" ); }
+    for id in members {
+        ferries.insert( id, tags.clone() );
+    }
+}
+
+///
+/// voies routables : les droits d'accès (access, vehicle, bicycle, ...) sont évalués pour chaque profil
+/// à la construction du graphe orienté (cf. Graph::get_directed_for). Les bacs (route=ferry, ou membres
+/// d'une relation route=ferry) sont routables. Les ways écartées (highway non géré, noeuds absents)
+/// sont notées dans `rejected` (cf. graphe::validate).
+///
+fn register_tway( we: Way, tways: &mut HashMap<i64, TWay>, tnodes: &mut HashMap<i64, TNode>, ferries: &HashMap<i64, HashMap<String, String>>, rejected: &mut Vec<Problem> ) {
     let mut routable = false;
-    let mut accessible = true;
     for (k, v) in we.tags() {
-        // type de route
+        // type de route (cf. routing::profile)
         if k == "highway" {
-            match v {
-                "motorway" | "trunk" | "primary" | "secondary" | "tertiary" | "unclassified" |
-                "residential" | "service" | "motorway_link" | "trunk_link" | "primary_link" |
-                "secondary_link" | "tertiary_link" | "motorway_junction" =>
-                { routable = true; },
-                _ => {},
-            }
-        }
-        // access
-        if k == "access" {
-            match v {
-                "yes" => {},
-                _ => { accessible = false; },
-            }
+            if ROUTABLE.contains( &v ) { routable = true; }
+            else { rejected.push( Problem::rejected_way( Check::Highway, we.id(), &we.refs().collect::<Vec<i64>>(), tnodes, format!( "highway={}", v ) ) ); }
         }
+        if k == "route" && v == "ferry" { routable = true; }
+    }
+    let relation = ferries.get( &we.id() );
+    if !routable && relation.is_none() { return; }
+    // extrait découpé : la way référence des noeuds absents du fichier, elle est écartée
+    let refs: Vec<i64> = we.refs().collect();
+    let missing: Vec<String> = refs.iter().filter( |n| !tnodes.contains_key( n ) ).map( |n| n.to_string() ).collect();
+    if !missing.is_empty() {
+        let detail = format!( "{} noeud(s) absent(s) : {}", missing.len(), missing.join( ", " ) );
+        rejected.push( Problem::rejected_way( Check::MissingNodes, we.id(), &refs, tnodes, detail ) );
+        return;
     }
-    if routable & accessible {
+    if let Some(relation) = relation {
+        // les tags de la way l'emportent sur ceux de la relation
+        let mut tags = relation.clone();
+        tags.extend( we.tags().map( |(k, v)| (k.to_string(), v.to_string()) ) );
+        let b = TWay::new( refs, tags, tnodes );
+        insert_tway( we.id(), b, tways, tnodes );
+    }
+    else {
         let b = TWay::from( we.clone(), tnodes );
-        tways.insert( we.id(), b.clone() );
-        for n in b.refs() {
-            match tnodes.get_mut( &n )  {
-                Some(tn) => { tn.add_wayid( we.id() ); },
-                None => { println!( "nodeid {} is not in the db", n ); },
+        insert_tway( we.id(), b, tways, tnodes );
+    }
+}
+
+fn insert_tway( id: i64, b: TWay, tways: &mut HashMap<i64, TWay>, tnodes: &mut HashMap<i64, TNode> ) {
+    tways.insert( id, b.clone() );
+    // les ways aux noeuds absents sont écartées avant (cf. Check::MissingNodes)
+    for n in b.refs() {
+        if let Some(tn) = tnodes.get_mut( n ) { tn.add_wayid( id ); }
+    }
+}
+
+///
+/// seuls les bacs reliés au réseau (chaque extrémité touche une autre voie) sont gardés
+///
+fn connect_ferries( tways: &mut HashMap<i64, TWay>, tnodes: &mut HashMap<i64, TNode> ) {
+    let connected = |n: &i64| tnodes.get( n ).is_some_and( |tn| tn.ways().len() > 1 );
+    let isolated: Vec<i64> = tways.iter()
+        .filter( |(_, w)| is_ferry( w.tags() ) && !( connected( &w.start() ) && connected( &w.end() ) ) )
+        .map( |(id, _)| *id )
+        .collect();
+    for id in isolated {
+        if let Some(w) = tways.remove( &id ) {
+            for n in w.refs() {
+                if let Some(tn) = tnodes.get_mut( n ) { tn.remove_wayid( id ); }
             }
         }
     }
 }
 
+///
+/// lecture du fichier osm.pbf, le programme s'arrête si le fichier est illisible (cf. load_osm)
+///
 pub fn read_osm(filename: &str ) -> Graph {
+    load_osm( filename ).unwrap()
+}
+
+///
+/// lecture du fichier osm.pbf : graphe des voies routables, adresses et communes
+///
+pub fn load_osm(filename: &str ) -> Result<Graph, osmpbf::Error> {
     let mut tnodes: HashMap<i64, TNode> = HashMap::new();
     let mut tways: HashMap<i64, TWay> = HashMap::new();
+    let mut addresses = AddressIndex::default();
+    let mut boundaries = Boundaries::default();
+    let mut ferries: HashMap<i64, HashMap<String, String>> = HashMap::new();
+    let mut rejected: Vec<Problem> = Vec::new();
 
-    let reader = ElementReader::from_path( filename ).unwrap();
+    // les relations sont en fin de fichier : une première lecture pour connaître les ways des communes et des bacs
+    let reader = ElementReader::from_path( filename )?;
+    reader.for_each( |element| {
+        if let Element::Relation(r) = element {
+            boundaries.add_relation( &r );
+            register_ferry( &r, &mut ferries );
+        }
+    } )?;
+
+    let reader = ElementReader::from_path( filename )?;
 
     reader.for_each( |element| {
         match element {
-            Element::DenseNode(dne) => { register_tnode( dne, &mut tnodes ); },
-            Element::Way(we) => { register_tway( we, &mut tways, &mut tnodes ); },
+            Element::DenseNode(dne) => { register_tnode( dne, &mut tnodes, &mut addresses ); },
+            Element::Way(we) => {
+                if boundaries.wants( we.id() ) { boundaries.add_way( we.id(), we.refs().collect() ); }
+                register_address( &we, &tnodes, &mut addresses );
+                register_tway( we, &mut tways, &mut tnodes, &ferries, &mut rejected );
+            },
             _ => {},
         }
-    } ). unwrap();
+    } )?;
+    connect_ferries( &mut tways, &mut tnodes );
+    addresses.finish();
+    boundaries.finish( &tnodes );
     let mut g = Graph::new( filename.to_string(), tnodes, tways);
+    g.addresses = addresses;
+    g.boundaries = boundaries;
+    g.rejected = rejected;
     g.clean();
-    g
+    Ok(g)
 }
 
 
 #[cfg(test)]
 mod reader_tests {
     use super::*;
+    use crate::graphe::testing::{tags, test_nodes};
 
     #[test]
     fn read_osmfile() {
@@ -74,4 +175,20 @@ mod reader_tests {
         assert_eq!( 221939, g.tnodes.len() ); // 221773 calculé à partir des resultats osmium (cf data/osmium_cde.txt)
     }
 
+    #[test]
+    fn ferries_connected() {
+        let mut tnodes = test_nodes( &[(1, 48.82, -3.01), (2, 48.84, -3.0), (3, 48.85, -3.0), (4, 48.86, -2.99), (5, 48.9, -3.1)] );
+        let mut tways: HashMap<i64, TWay> = HashMap::new();
+        // route vers l'embarcadère, bac vers l'île et route de l'île ; un bac vers le large
+        insert_tway( 10, TWay::new( vec![1, 2], tags( &[("highway", "secondary")] ), &tnodes ), &mut tways, &mut tnodes );
+        insert_tway( 11, TWay::new( vec![2, 3], tags( &[("route", "ferry")] ), &tnodes ), &mut tways, &mut tnodes );
+        insert_tway( 12, TWay::new( vec![3, 4], tags( &[("highway", "track")] ), &tnodes ), &mut tways, &mut tnodes );
+        insert_tway( 13, TWay::new( vec![2, 5], tags( &[("route", "ferry")] ), &tnodes ), &mut tways, &mut tnodes );
+        connect_ferries( &mut tways, &mut tnodes );
+
+        assert!( tways.contains_key( &11 ) && !tways.contains_key( &13 ) );
+        assert_eq!( &vec![10, 11], tnodes[&2].ways() );
+        assert!( tnodes[&5].ways().is_empty() );
+    }
+
 }
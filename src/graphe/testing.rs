@@ -0,0 +1,46 @@
+//!
+//! données des tests construites à la main : tags et petits graphes
+//! (aussi incluses dans les tests du programme, cf. main.rs)
+//!
+
+use std::collections::HashMap;
+
+use crate::graphe::Graph;
+use crate::graphe::elements::{TNode, TWay};
+
+
+// way d'un graphe de test : id, noeuds et tags
+pub(crate) type TestWay<'a> = (i64, &'a [i64], &'a [(&'a str, &'a str)]);
+
+
+///
+/// tags osm à partir de couples (clé, valeur)
+///
+pub(crate) fn tags( kv: &[(&str, &str)] ) -> HashMap<String, String> {
+    kv.iter().map( |(k, v)| (k.to_string(), v.to_string()) ).collect()
+}
+
+///
+/// noeuds sans tags (id, lat, lon)
+///
+pub(crate) fn test_nodes( nodes: &[(i64, f64, f64)] ) -> HashMap<i64, TNode> {
+    nodes.iter().map( |(id, lat, lon)| (*id, TNode::new( *lat, *lon, HashMap::new() )) ).collect()
+}
+
+///
+/// ways (id, noeuds, tags) sur ces noeuds
+///
+pub(crate) fn test_ways( tnodes: &HashMap<i64, TNode>, ways: &[TestWay] ) -> HashMap<i64, TWay> {
+    ways.iter().map( |(id, refs, kv)| (*id, TWay::new( refs.to_vec(), tags( kv ), tnodes )) ).collect()
+}
+
+///
+/// graphe des noeuds (id, lat, lon) et des ways (id, noeuds, tags), nettoyé comme après lecture d'un fichier
+///
+pub(crate) fn test_graph( nodes: &[(i64, f64, f64)], ways: &[TestWay] ) -> Graph {
+    let tnodes = test_nodes( nodes );
+    let tways = test_ways( &tnodes, ways );
+    let mut g = Graph::new( "test".to_string(), tnodes, tways );
+    g.clean();
+    g
+}
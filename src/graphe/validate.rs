@@ -0,0 +1,321 @@
+use std::fmt;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::graphe::Graph;
+use crate::graphe::elements::{TNode, TWay};
+use crate::routing::distances::distance_haversine;
+use crate::routing::profile::{ROUTABLE, is_ferry};
+
+
+// longueur (m) en dessous de laquelle un segment entre deux noeuds distincts est nul
+pub const ZERO_LENGTH: f64 = 0.01;
+
+// problèmes détaillés par l'affichage pour chaque contrôle
+const SHOWN_PROBLEMS: usize = 10;
+
+///
+/// contrôle effectué sur le graphe
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Check {
+    MissingNodes,
+    DuplicateNodes,
+    ZeroLength,
+    SelfIntersection,
+    Maxspeed,
+    Oneway,
+    Coordinates,
+    Highway,
+}
+
+impl Check {
+    pub const ALL: [Check; 8] = [Check::MissingNodes, Check::DuplicateNodes, Check::ZeroLength, Check::SelfIntersection,
+        Check::Maxspeed, Check::Oneway, Check::Coordinates, Check::Highway];
+
+    ///
+    /// libellé du contrôle
+    ///
+    pub fn label( &self ) -> &'static str {
+        match self {
+            Check::MissingNodes => "ways référençant des noeuds absents",
+            Check::DuplicateNodes => "noeuds consécutifs en double",
+            Check::ZeroLength => "segments de longueur nulle",
+            Check::SelfIntersection => "ways qui se recoupent",
+            Check::Maxspeed => "maxspeed illisibles",
+            Check::Oneway => "oneway illisibles",
+            Check::Coordinates => "coordonnées invraisemblables",
+            Check::Highway => "highway non gérés par les profils",
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            Check::MissingNodes => write!( f, "missing_nodes" ),
+            Check::DuplicateNodes => write!( f, "duplicate_nodes" ),
+            Check::ZeroLength => write!( f, "zero_length" ),
+            Check::SelfIntersection => write!( f, "self_intersection" ),
+            Check::Maxspeed => write!( f, "maxspeed" ),
+            Check::Oneway => write!( f, "oneway" ),
+            Check::Coordinates => write!( f, "coordinates" ),
+            Check::Highway => write!( f, "highway" ),
+        }
+    }
+}
+
+///
+/// problème relevé sur une way ou un noeud, avec sa position (lat, lon) si elle est connue
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub check: Check,
+    pub way: Option<i64>,
+    pub node: Option<i64>,
+    pub position: Option<(f64, f64)>,
+    pub detail: String,
+}
+
+impl Problem {
+    fn on_way( check: Check, way: i64, node: Option<i64>, position: Option<(f64, f64)>, detail: String ) -> Self {
+        Self { check, way: Some(way), node, position, detail }
+    }
+
+    ///
+    /// way écartée à la lecture du fichier (cf. reader::load_osm), située par son premier noeud connu
+    ///
+    pub fn rejected_way( check: Check, way: i64, refs: &[i64], nodes: &HashMap<i64, TNode>, detail: String ) -> Self {
+        let position = refs.iter().find_map( |n| nodes.get( n ) ).map( |n| (n.lat(), n.lon()) );
+        Self::on_way( check, way, None, position, detail )
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        if let Some(w) = self.way { write!( f, "way {} ", w )?; }
+        if let Some(n) = self.node { write!( f, "noeud {} ", n )?; }
+        write!( f, ": {}", self.detail )?;
+        if let Some((lat, lon)) = self.position { write!( f, " ({:.7}, {:.7})", lat, lon )?; }
+        Ok(())
+    }
+}
+
+///
+/// rapport de validation : problèmes triés par contrôle, puis par way et par noeud
+///
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub problems: Vec<Problem>,
+}
+
+impl Report {
+    pub fn is_empty( &self ) -> bool {
+        self.problems.is_empty()
+    }
+
+    ///
+    /// problèmes relevés par un contrôle
+    ///
+    pub fn of( &self, check: Check ) -> Vec<&Problem> {
+        self.problems.iter().filter( |p| p.check == check ).collect()
+    }
+}
+
+///
+/// nombre de problèmes par contrôle et premiers problèmes de chacun ; les highway non gérés sont
+/// comptés par valeur
+///
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{} problème(s)", self.problems.len() )?;
+        for check in Check::ALL {
+            let problems = self.of( check );
+            if problems.is_empty() { continue; }
+            write!( f, "\n\t{} : {}", check.label(), problems.len() )?;
+            if check == Check::Highway {
+                let mut values: BTreeMap<&str, usize> = BTreeMap::new();
+                for p in problems.iter() { *values.entry( p.detail.as_str() ).or_insert( 0 ) += 1; }
+                for (v, count) in values { write!( f, "\n\t\t{} : {} way(s)", v, count )?; }
+                continue;
+            }
+            for p in problems.iter().take( SHOWN_PROBLEMS ) {
+                write!( f, "\n\t\t{}", p )?;
+            }
+            if problems.len() > SHOWN_PROBLEMS {
+                write!( f, "\n\t\t... et {} autres", problems.len() - SHOWN_PROBLEMS )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+
+///
+/// valeur de maxspeed : nombre (km/h, ou suivi de mph / knots), valeur symbolique ou zone ("FR:urban")
+///
+fn maxspeed_valid( v: &str ) -> bool {
+    let number = v.strip_suffix( " mph" ).or( v.strip_suffix( " knots" ) ).unwrap_or( v );
+    number.parse::<f64>().is_ok_and( |s| s > 0.0 )
+        || matches!( v, "none" | "signals" | "variable" | "walk" )
+        || v.split_once( ':' ).is_some_and( |(country, zone)| country.len() == 2 && !zone.is_empty() )
+}
+
+fn oneway_valid( v: &str ) -> bool {
+    matches!( v, "yes" | "no" | "-1" | "1" | "true" | "false" | "reversible" | "alternating" )
+}
+
+///
+/// coordonnées hors des bornes, non définies ou (0, 0)
+///
+fn implausible( lat: f64, lon: f64 ) -> bool {
+    !( lat.is_finite() && lon.is_finite() ) || lat.abs() > 90.0 || lon.abs() > 180.0 || ( lat == 0.0 && lon == 0.0 )
+}
+
+///
+/// intersection des segments [a, b] et [c, d] (x, y) quand ils se croisent strictement
+///
+fn crossing( a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64) ) -> Option<(f64, f64)> {
+    let cross = |o: (f64, f64), p: (f64, f64), q: (f64, f64)| ( p.0 - o.0 ) * ( q.1 - o.1 ) - ( p.1 - o.1 ) * ( q.0 - o.0 );
+    let ( d1, d2 ) = ( cross( c, d, a ), cross( c, d, b ) );
+    let ( d3, d4 ) = ( cross( a, b, c ), cross( a, b, d ) );
+    if d1 * d2 >= 0.0 || d3 * d4 >= 0.0 { return None; }
+    let t = d1 / ( d1 - d2 );
+    Some( ( a.0 + t * ( b.0 - a.0 ), a.1 + t * ( b.1 - a.1 ) ) )
+}
+
+///
+/// croisements entre segments non contigus de la way, et noeuds par lesquels elle repasse
+/// (le noeud de fermeture d'une way fermée excepté)
+///
+fn self_intersections( id: i64, w: &TWay, nodes: &HashMap<i64, TNode> ) -> Vec<Problem> {
+    let refs = w.refs();
+    let Some(points) = refs.iter().map( |n| nodes.get( n ).map( |n| (n.lat(), n.lon()) ) ).collect::<Option<Vec<(f64, f64)>>>() else { return Vec::new() };
+    // plan local : longitudes ramenées à l'échelle des latitudes
+    let scale = points[0].0.to_radians().cos();
+    let xy: Vec<(f64, f64)> = points.iter().map( |(lat, lon)| ( lon * scale, *lat ) ).collect();
+    let closed = refs.len() > 2 && w.start() == w.end();
+    let last = refs.len().saturating_sub( 1 );
+
+    let mut problems = Vec::new();
+    for (i, n) in refs.iter().enumerate().skip( 1 ) {
+        let again = refs[..i - 1].iter().position( |m| m == n ).filter( |j| !( closed && *j == 0 && i == last ) );
+        if again.is_some() {
+            problems.push( Problem::on_way( Check::SelfIntersection, id, Some(*n), Some( points[i] ), "la way repasse par ce noeud".to_string() ) );
+        }
+    }
+    for i in 0..last {
+        for j in i + 2..last {
+            if closed && i == 0 && j == last - 1 { continue; }
+            if let Some((x, y)) = crossing( xy[i], xy[i + 1], xy[j], xy[j + 1] ) {
+                let detail = format!( "segments {}-{} et {}-{} se croisent", refs[i], refs[i + 1], refs[j], refs[j + 1] );
+                problems.push( Problem::on_way( Check::SelfIntersection, id, None, Some( (y, x / scale) ), detail ) );
+            }
+        }
+    }
+    problems
+}
+
+///
+/// contrôles d'une way : noeuds en double, segments nuls, maxspeed / oneway, highway, croisements
+///
+fn check_way( id: i64, w: &TWay, nodes: &HashMap<i64, TNode> ) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let position = |n: &i64| nodes.get( n ).map( |n| (n.lat(), n.lon()) );
+    for pair in w.refs().windows( 2 ) {
+        let ( a, b ) = ( pair[0], pair[1] );
+        if a == b {
+            problems.push( Problem::on_way( Check::DuplicateNodes, id, Some(a), position( &a ), format!( "noeud {} répété", a ) ) );
+            continue;
+        }
+        if let ( Some(p), Some(q) ) = ( position( &a ), position( &b ) ) {
+            let d = distance_haversine( p.0, p.1, q.0, q.1 );
+            if d < ZERO_LENGTH {
+                problems.push( Problem::on_way( Check::ZeroLength, id, Some(a), Some(p), format!( "segment {}-{} de {:.3} m", a, b, d ) ) );
+            }
+        }
+    }
+    let start = position( &w.start() );
+    for key in ["maxspeed", "maxspeed:forward", "maxspeed:backward"] {
+        if let Some(v) = w.tags().get( key ).filter( |v| !maxspeed_valid( v ) ) {
+            problems.push( Problem::on_way( Check::Maxspeed, id, None, start, format!( "{}={}", key, v ) ) );
+        }
+    }
+    for key in ["oneway", "oneway:bicycle"] {
+        if let Some(v) = w.tags().get( key ).filter( |v| !oneway_valid( v ) ) {
+            problems.push( Problem::on_way( Check::Oneway, id, None, start, format!( "{}={}", key, v ) ) );
+        }
+    }
+    match w.tags().get( "highway" ) {
+        Some(h) if !ROUTABLE.contains( &h.as_str() ) => {
+            problems.push( Problem::on_way( Check::Highway, id, None, start, format!( "highway={}", h ) ) );
+        },
+        None if !is_ferry( w.tags() ) => {
+            problems.push( Problem::on_way( Check::Highway, id, None, start, "highway absent".to_string() ) );
+        },
+        _ => {},
+    }
+    problems.extend( self_intersections( id, w, nodes ) );
+    problems
+}
+
+///
+/// validation du graphe : ways écartées à la lecture (noeuds absents, highway non géré) et contrôles
+/// des ways et des noeuds gardés
+///
+pub fn validate( g: &Graph ) -> Report {
+    let mut problems: Vec<Problem> = g.rejected.clone();
+    for (id, w) in g.tways.iter() {
+        problems.extend( check_way( *id, w, &g.tnodes ) );
+    }
+    for (id, n) in g.tnodes.iter() {
+        if implausible( n.lat(), n.lon() ) {
+            let detail = format!( "lat {} lon {}", n.lat(), n.lon() );
+            problems.push( Problem { check: Check::Coordinates, way: n.ways().first().copied(), node: Some(*id), position: None, detail } );
+        }
+    }
+    problems.sort_by_key( |p| ( p.check, p.way, p.node ) );
+    Report { problems }
+}
+
+
+#[cfg(test)]
+mod validate_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    #[test]
+    fn values() {
+        assert!( maxspeed_valid( "50" ) && maxspeed_valid( "30 mph" ) && maxspeed_valid( "FR:urban" ) && maxspeed_valid( "none" ) );
+        assert!( !maxspeed_valid( "50kmh" ) && !maxspeed_valid( "fast" ) && !maxspeed_valid( "0" ) );
+        assert!( oneway_valid( "-1" ) && !oneway_valid( "Yes" ) );
+        assert!( implausible( 0.0, 0.0 ) && implausible( 91.0, 2.0 ) && implausible( f64::NAN, 2.0 ) && !implausible( 48.0, -2.0 ) );
+        assert_eq!( Some((0.5, 0.5)), crossing( (0.0, 0.0), (1.0, 1.0), (0.0, 1.0), (1.0, 0.0) ) );
+        assert_eq!( None, crossing( (0.0, 0.0), (1.0, 1.0), (1.0, 1.0), (2.0, 0.0) ) );
+    }
+
+    #[test]
+    fn validate_graph() {
+        // noeuds 5 et 6 au même endroit ; 1 -> 2 puis 3 -> 4 : le second segment recoupe le premier
+        let mut g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.995), (4, 47.99, -1.995), (5, 48.02, -2.0), (6, 48.02, -2.0)],
+                                &[(10, &[1, 2, 3, 4], &[("highway", "residential"), ("maxspeed", "50 km/h")]),
+                                  (11, &[5, 6, 6, 1], &[("highway", "corridor"), ("oneway", "yes;no")]),
+                                  (12, &[1, 2, 3, 1], &[("highway", "service")])] );
+        g.rejected.push( Problem::rejected_way( Check::MissingNodes, 13, &[7, 2], &g.tnodes, "1 noeud(s) absent(s) : 7".to_string() ) );
+        let report = validate( &g );
+
+        let ways = |check: Check| report.of( check ).iter().map( |p| p.way.unwrap() ).collect::<Vec<i64>>();
+        assert_eq!( vec![13], ways( Check::MissingNodes ) );
+        assert_eq!( Some((48.0, -1.99)), report.of( Check::MissingNodes )[0].position );
+        assert_eq!( vec![11], ways( Check::DuplicateNodes ) );
+        assert_eq!( vec![11], ways( Check::ZeroLength ) );
+        assert_eq!( vec![10], ways( Check::SelfIntersection ) );
+        assert_eq!( vec![10], ways( Check::Maxspeed ) );
+        assert_eq!( vec![11], ways( Check::Oneway ) );
+        assert_eq!( vec![11], ways( Check::Highway ) );
+        assert!( report.of( Check::Coordinates ).is_empty() );
+        let p = report.of( Check::SelfIntersection )[0].position.unwrap();
+        assert!( ( p.0 - 48.0 ).abs() < 1e-9 && ( p.1 + 1.995 ).abs() < 1e-9 );
+        assert!( report.to_string().starts_with( "7 problème(s)\n\tways référençant des noeuds absents : 1" ) );
+        assert!( report.to_string().contains( "highway=corridor : 1 way(s)" ) );
+    }
+}
@@ -0,0 +1,21 @@
+//!
+//! Calcul d'itinéraires sur les données OpenStreetMap (fichiers osm.pbf).
+//!
+//! La façade [`RoutingEngine`] charge le graphe et répond aux demandes courantes (point le plus
+//! proche, itinéraire, matrice de durées, isochrone) par des structures de données, sans affichage.
+//! Les modules `graphe`, `routing` et `export` donnent accès aux briques utilisées : lecture du
+//! fichier, index spatial, géocodage, map matching, tournées, exports GPX et GeoJSON ; `server`
+//! expose le moteur par une API HTTP compatible OSRM.
+//!
+
+pub mod engine;
+pub mod export;
+pub mod graphe;
+pub mod routing;
+pub mod server;
+
+pub use engine::{EngineError, Isochrone, Itinerary, Matrix, Metric, RouteOptions, RoutingEngine, Snap};
+pub use routing::avoid::Avoid;
+pub use graphe::Graph;
+pub use routing::profile::Profile;
+pub use routing::route::Route;
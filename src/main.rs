@@ -1,213 +1,631 @@
-use std::io::{self,Write};
 use std::fs::File;
+use std::path::Path;
 use std::collections::HashMap;
 
 use ansi_term::Colour;
-use gpx::{Gpx,GpxVersion,Metadata,Track,TrackSegment, Waypoint,write};
-use geo_types::{Point, Rect, coord};
+use chrono::NaiveDateTime;
+use rustyline::error::ReadlineError;
 
 
-use crate::cli::get_input_filename;
-use crate::graphe::reader::read_osm;
-use crate::routing::location::{Address,get_location_from_nominatim, nearest_node};
-use crate::routing::dijkstra::shortest_path;
+use route::{Avoid, Isochrone, Metric, RouteOptions, RoutingEngine};
+use route::export::{ExportError, Format, geojson, gpx_file, render};
+use route::export::gpx_file::GpxOptions;
+use route::export::output;
+use route::graphe::Graph;
+use route::graphe::elevation::{Climb, Dem};
+use route::graphe::validate::{Report, validate};
+use route::routing::distances::DistanceMetric;
+use route::routing::geocoder::{Chain, Geocoder, remote};
+use route::routing::geocoder::local::Local;
+use route::routing::matching::read_gpx_points;
+use route::routing::places::{Place, Places};
+use route::routing::profile::Profile;
+use route::routing::roundtrip::round_trip;
+use route::routing::route::Route;
+use route::routing::reverse::reverse;
+use route::routing::vrp::{Problem, solve};
+use route::routing::waypoints::{read_waypoints, route_waypoints};
+use route::server;
+
+use crate::cli::{Command, get_command};
 
 pub mod cli;
-pub mod graphe;
-pub mod routing;
+pub mod commands;
+pub mod repl;
+
+
+///
+/// dernier résultat affiché, enregistré par la commande save
+///
+enum Selection {
+    Nothing,
+    Nodes(Vec<i64>),
+    Ways(Vec<i64>),
+    Route(Route),
+    Isochrone(Isochrone),
+    Report(Report),
+}
 
 
 pub fn main() {
-    let filename = get_input_filename();
+    let (df, command) = get_command();
 
-    let g = read_osm( filename.as_str() );
+    let mut engine = match RoutingEngine::load( df.get_ifilepath() ) {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!( "{} : {}", Colour::Red.paint("Erreur"), e );
+            std::process::exit( commands::FAILURE );
+        },
+    };
+    if df.get_distance() != DistanceMetric::default() {
+        engine = engine.with_metric( df.get_distance() );
+    }
+    if let Some(min) = df.get_prune() {
+        let (pruned, removed) = engine.prune_islands( min );
+        engine = pruned;
+        eprintln!( "{} ways supprimées (îlots de moins de {} noeuds)", removed, min );
+    }
+    if let Some(dir) = df.get_dem() {
+        match Dem::load_dir( dir ) {
+            Ok(dem) => {
+                let (with_ele, count) = engine.with_elevation( &dem );
+                engine = with_ele;
+                eprintln!( "altitude de {} noeuds sur {} ({} tuiles)", count, engine.graph().tnodes.len(), dem.len() );
+            },
+            Err(e) => {
+                eprintln!( "{} : {}", Colour::Red.paint("Erreur"), e );
+                std::process::exit( commands::FAILURE );
+            },
+        }
+    }
+    match command {
+        Some(Command::Serve { listen, threads }) => {
+            match tiny_http::Server::http( &listen ) {
+                Ok(s) => {
+                    println!( "serveur en écoute sur http://{}", s.server_addr() );
+                    server::serve( &engine, &s, threads );
+                },
+                Err(e) => {
+                    println!( "{} : {}", Colour::Red.paint("Erreur"), e );
+                    std::process::exit( commands::FAILURE );
+                },
+            }
+            return;
+        },
+        Some(c) => { std::process::exit( commands::run( &engine, &df, c ) ); },
+        None => {},
+    }
+    let g = engine.graph();
+    let mut last = Selection::Nothing;
+
+    let mut geocoders: Vec<Box<dyn Geocoder>> = vec![Box::new( Local::new( &g.addresses, 5 ) )];
+    if df.get_geocoder() != "none" {
+        match remote( df.get_geocoder(), df.get_geocoder_url(), Some( df.get_cachefilepath() ) ) {
+            Ok(r) => { geocoders.push( r ); },
+            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+        }
+    }
+    let mut geocoder = Chain::new( geocoders );
+
+    let mut places = match Places::open( df.get_placesfilepath() ) {
+        Ok(p) => p,
+        Err(e) => {
+            // favoris gardés le temps de la session, sans écraser le fichier illisible
+            println!( "{} : {}", Colour::Red.paint("Erreur"), e );
+            Places::default()
+        },
+    };
+    let mut rl = match repl::editor() {
+        Ok(rl) => rl,
+        Err(e) => {
+            eprintln!( "{} : {}", Colour::Red.paint("Erreur"), e );
+            std::process::exit( commands::FAILURE );
+        },
+    };
+    // pas encore d'historique au premier lancement
+    let _ = rl.load_history( df.get_historyfilepath() );
+    if let Some(h) = rl.helper_mut() { h.set_places( &places ); }
 
     loop {
-        print!( "{}", Colour::Yellow.paint("> " ) );
-        io::stdout().flush().unwrap();
-        let mut raw_input = String::new();
-        match io::stdin().read_line(&mut raw_input) {
-            Ok(_n) => {
-                let input: Vec<&str> = raw_input.trim().split( ' ' ).collect() ;
-                match input[0] {
-                    "exit" | "quit" => { break; },
-                    "show" => {
-                        match input[1] {
-                            "node" | "peak" => {
-                                let collect = convert_vec( input );
+        let raw_input = match rl.readline( "> " ) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => { continue; },
+            Err(ReadlineError::Eof) => { break; },
+            Err(error) => {
+                println!( "{} : {}", Colour::Red.paint("Erreur"), error );
+                break;
+            },
+        };
+        let words = repl::words( &raw_input );
+        let input: Vec<&str> = words.iter().map( |w| w.as_str() ).collect();
+        if input.is_empty() { continue; }
+        let _ = rl.add_history_entry( raw_input.trim() );
+        if let Err(e) = repl::check( &input ) {
+            println!( "{} : {}", Colour::Red.paint("Erreur"), e );
+            continue;
+        }
+        // id osm affichés, proposés ensuite à la complétion
+        let mut seen: Vec<i64> = Vec::new();
+        match input[0] {
+            "exit" | "quit" => { break; },
+            "show" => {
+                match input[1] {
+                    "node" | "peak" => {
+                        match convert_vec( &input ) {
+                            Ok(collect) => {
                                 print_elts( &g.tnodes, &collect );
+                                seen.extend( &collect );
+                                last = Selection::Nodes( collect );
                             },
-                            "way" | "bow" => {
-                                let collect = convert_vec( input );
-                                print_elts( &g.tways, &collect );
-                            },
-                            "nodes" | "peaks" => {
-                                let collect = rand_elts( &g.tnodes  );
-                                print_elts( &g.tnodes, &collect );
-                              },
-                            "ways" | "bows" => {
-                                let collect = rand_elts( &g.tways );
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    "way" | "bow" => {
+                        match convert_vec( &input ) {
+                            Ok(collect) => {
                                 print_elts( &g.tways, &collect );
-                              },
-                            _ => { println!( "{}", Colour::Red.paint("Choix non valide." ) ); },
-                        }
-                    },
-                    "info" => {
-                        println!( "{}", g );
-                    },
-                    "locate" => {
-                        let addr: Vec<&str> = (&input[1..]).to_vec();
-                        let ad: Address =  Address { ad: addr.join( " " ) };
-                        println!("\n{} :", Colour::Yellow.paint( &ad.ad ) );
-                        match get_location_from_nominatim( &ad ) {
-                            Ok(lv) => {
-                                for l in &lv {
-                                    let mut v: Vec<i64> = Vec::new();
-                                    v.push( l.osm_id );
-                                    match l.osm_type.as_str() {
-                                        "way" => { print_elts( &g.tways, &v ); },
-                                        "node" => { print_elts( &g.tnodes, &v ); },
-                                        _ => { println!("{} => {}", Colour::Red.paint("type non géré"), l ); },
-                                    }
-                                }
+                                seen.extend( &collect );
+                                last = Selection::Ways( collect );
                             },
-                            Err(e) => { println!("Erreur {}", e); },
-                        };
-                    },
-                    "nearest" => {
-                        // nearest 48.44725 -2.86572 --> Pascal&Nathalie (2345943396 : 48.4471493 , -2.8655416 )
-                        // nearest 48.40631 -2.81467 --> garage (10748130358 : 48.4063898 , -2.8150775 )
-                        // nearest 48.40627 -2.81457 --> cuisine (10748130360 : 48.4060126 , -2.8146323 )
-                        // nearest 48.40672 -2.81433 --> Maryse (4779385124 : 48.4067937 , -2.8145653 )
-                        // nearest 48.41119 -2.81940 --> Pharmacie Plaintel (7194631845 : 48.411183 , -2.8197704 )
-                        // nearest 48.34743 -2.75695 --> Parmacie Ploeuc (10048845537 : 48.3473733 , -2.7570492 )
-                        // nearest 48.51973 -2.78808 --> Dr Smau ( 2000599137 : 48.5197604 , -2.7879812000000004 )
-                        // nearest 48.49618 -2.68939 --> Denis Rebours ( 2971599465 : 48.496328000000005 , -2.6892531)
-                        let ( id, dist ) = nearest_node( input[1].parse::<f64>().unwrap(), input[2].parse::<f64>().unwrap(), &g.tnodes );
-                        let distance = format!( "{:.2}", dist );
-                        println!( "le point {} est le plus proche à {} m", Colour::Blue.paint( id.to_string() ), Colour::Green.paint( distance ) );
-                    },
-                    "route" => {
-                        // route distance 10748130360 4779385124 : cuisine-maryse => 359.85m (361m osm)
-                        // route distance 10748130358 4779385124 : garage-maryse => 82.37m (83m osm)
-                        // route distance 10748130358 7194631845 : garage-pharmacie Plaintel => 678.56m (679m osm)
-                        // route distance 10748130358 10048845537 : garage-pharmacie ploeuc => 8989.94m (9km osm)
-                        // route distance 10748130358 2345943396 : garage-Pascal&Nathalie => 10522.86m (9km osm) ???
-                        // route distance 10748130358 2000599137 : garage-Dr_Smau => 15228.37m (16km osm)
-                        // route distance 10748130358 2971599465 : garage-Denis_Rebours => 17313.70m (18km osm)
-                        match shortest_path( input[1], &g.get_directed(), input[2].parse::<i64>().unwrap(), input[3].parse::<i64>().unwrap() ) {
-                            Some(bt) => {
-                                for (k, v) in bt.iter() {
-                                    println!( "{} : ", Colour::Yellow.paint( format!( "{} m", (*k as f64 / 100.0) ) ) );
-                                    print_elts( &g.tnodes, &vec![*v] );
-                                    // affichage des way id et des noms de rue
-                                    match g.tnodes.get( v ) {
-                                        Some(n) => {
-                                            for id in n.ways() {
-                                                match g.tways.get( &id ) {
-                                                    Some(w) => {
-                                                        for (t,val) in w.tags() {
-                                                            if ( t == "name" ) | ( t == "ref" ) {
-                                                                println!( "\t{} : {}", id, val );
-                                                                break;
-                                                            }
-                                                        }
-                                                    },
-                                                    None => {
-                                                        println!( "way id {} must be in db", id );
-                                                    },
-                                                }
-                                            }
-                                        },
-                                        None => {
-                                            println!( "node id {} must be in db", v );
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    "nodes" | "peaks" => {
+                        let collect = rand_elts( &g.tnodes  );
+                        print_elts( &g.tnodes, &collect );
+                        seen.extend( &collect );
+                        last = Selection::Nodes( collect );
+                      },
+                    "ways" | "bows" => {
+                        let collect = rand_elts( &g.tways );
+                        print_elts( &g.tways, &collect );
+                        seen.extend( &collect );
+                        last = Selection::Ways( collect );
+                      },
+                    _ => { println!( "{} : {}", Colour::Red.paint("Choix non valide"), repl::verb( "show" ).map( |v| v.usage() ).unwrap_or_default() ); },
+                }
+            },
+            "place" => {
+                // place add garage 10748130358 | place add maryse 48.40672 -2.81433 | place list | place rm maryse
+                // place add Pharmacie Plaintel 7194631845 | route distance garage "Pharmacie Plaintel"
+                match ( input[1], input.get( 2 ) ) {
+                    ( "list", _ ) => {
+                        for (name, p) in places.iter() {
+                            println!( "{} : {}", Colour::Yellow.paint( name ), p );
+                        }
+                        if places.is_empty() { println!( "aucun lieu, 'place add nom node_id|lat lon|lieu'" ); }
+                    },
+                    ( "add", Some(_) ) => {
+                        let added = repl::new_place( &input ).and_then( |(name, at)| {
+                            let place = match input[at].parse::<i64>() {
+                                Ok(id) if at == input.len() - 1 => g.tnodes.get( &id ).map( |n| Place { lat: n.lat(), lon: n.lon(), node: Some(id) } )
+                                    .ok_or_else( || format!( "{} : noeud absent du graphe", id ) ),
+                                _ => repl::position( &places, &input, at ).map( |(lat, lon, _)| Place { lat, lon, node: None } ),
+                            }?;
+                            places.insert( &name, place ).map_err( |e| e.to_string() )?;
+                            Ok( (name, place) )
+                        } );
+                        match added {
+                            Ok((name, p)) => { println!( "{} : {}", Colour::Green.paint( name ), p ); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    ( "rm", Some(_) ) => {
+                        let name = input[2..].join( " " );
+                        match places.remove( &name ) {
+                            Ok(p) => { println!( "{} : {} supprimé", Colour::Yellow.paint( name ), p ); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    _ => { println!( "{} : usage : {}", Colour::Red.paint("Erreur"), repl::verb( "place" ).map( |v| v.usage() ).unwrap_or_default() ); },
+                }
+            },
+            "help" => {
+                match repl::help( input.get( 1 ).copied() ) {
+                    Ok(text) => { println!( "{}", text ); },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            },
+            "info" => {
+                println!( "{}", g );
+                println!( "composantes fortement connexes :" );
+                for profile in [Profile::Car, Profile::Bicycle, Profile::Foot] {
+                    println!( "\t{} : {}", Colour::Cyan.paint( profile.to_string() ), engine.components( profile ) );
+                }
+            },
+            "validate" => {
+                let report = validate( g );
+                let color = if report.is_empty() { Colour::Green } else { Colour::Yellow };
+                println!( "{}", color.paint( report.to_string() ) );
+                last = Selection::Report( report );
+            },
+            "locate" => {
+                // géocodeur local d'abord, service en ligne si rien n'est trouvé
+                let query = input[1..].join( " " );
+                println!("\n{} :", Colour::Yellow.paint( &query ) );
+                match geocoder.search( &query ) {
+                    Ok(hits) => {
+                        for h in hits.iter() {
+                            match h.score {
+                                Some(score) => { println!( "{} {}", Colour::Green.paint( format!( "{:.2}", score ) ), h ); },
+                                None => { println!( "{}", h ); },
+                            }
+                            match ( h.osm_type.as_deref(), h.osm_id ) {
+                                ( Some("way"), Some(id) ) if g.tways.contains_key( &id ) => { print_elts( &g.tways, &[id] ); },
+                                ( Some("node"), Some(id) ) if g.tnodes.contains_key( &id ) => { print_elts( &g.tnodes, &[id] ); },
+                                _ => {},
+                            }
+                            match engine.nearest( Profile::Car, h.lat, h.lon ) {
+                                Some(snap) => {
+                                    seen.push( snap.node );
+                                    println!( "\tle point {} est le plus proche à {:.2} m\n", snap.node, snap.distance );
+                                },
+                                None => { println!( "\t{}\n", Colour::Red.paint("aucune voie praticable à proximité") ); },
+                            }
+                        }
+                        if hits.is_empty() { println!( "{}", Colour::Red.paint("aucun résultat") ); }
+                    },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                };
+            },
+            "nearest" => {
+                // nearest 48.44725 -2.86572 --> Pascal&Nathalie (2345943396 : 48.4471493 , -2.8655416 )
+                // nearest 48.40631 -2.81467 --> garage (10748130358 : 48.4063898 , -2.8150775 )
+                // nearest 48.40627 -2.81457 --> cuisine (10748130360 : 48.4060126 , -2.8146323 )
+                // nearest 48.40672 -2.81433 --> Maryse (4779385124 : 48.4067937 , -2.8145653 )
+                // nearest 48.41119 -2.81940 --> Pharmacie Plaintel (7194631845 : 48.411183 , -2.8197704 )
+                // nearest 48.34743 -2.75695 --> Parmacie Ploeuc (10048845537 : 48.3473733 , -2.7570492 )
+                // nearest 48.51973 -2.78808 --> Dr Smau ( 2000599137 : 48.5197604 , -2.7879812000000004 )
+                // nearest 48.49618 -2.68939 --> Denis Rebours ( 2971599465 : 48.496328000000005 , -2.6892531)
+                // nearest maryse : position d'un lieu nommé
+                // nearest 48.40631 -2.81467 foot : noeud d'une voie praticable à pied (voiture par défaut)
+                let args = repl::position( &places, &input, 1 ).and_then( |(lat, lon, next)| {
+                    let profile = input.get( next ).map_or( Some(Profile::Car), |p| Profile::from_name( p ) )
+                        .ok_or_else( || format!( "{} : profil inconnu", input[next] ) )?;
+                    Ok( (lat, lon, profile) )
+                } );
+                match args.and_then( |(lat, lon, profile)| engine.nearest( profile, lat, lon ).ok_or( "aucune voie praticable à proximité".to_string() ) ) {
+                    Ok(snap) => {
+                        let distance = format!( "{:.2}", snap.distance );
+                        println!( "le point {} est le plus proche à {} m", Colour::Blue.paint( snap.node.to_string() ), Colour::Green.paint( distance ) );
+                        seen.push( snap.node );
+                    },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            },
+            "whereis" => {
+                // whereis 48.51973 -2.78808 --> rue, numéro et commune
+                match ( input[1].parse::<f64>(), input[2].parse::<f64>() ) {
+                    ( Ok(lat), Ok(lon) ) => {
+                        let w = reverse( g, engine.index(), lat, lon );
+                        println!( "{}", Colour::Green.paint( w.to_string() ) );
+                        if let Some(way) = w.way {
+                            println!( "\tway {} à {:.2} m", Colour::Blue.paint( way.to_string() ), w.distance );
+                            seen.push( way );
+                        }
+                    },
+                    _ => { println!( "{} : whereis lat lon", Colour::Red.paint("Erreur") ); },
+                }
+            },
+            "route" => {
+                // route distance 10748130360 4779385124 : cuisine-maryse => 359.85m (361m osm)
+                // route distance 10748130358 4779385124 : garage-maryse => 82.37m (83m osm)
+                // route distance 10748130358 7194631845 : garage-pharmacie Plaintel => 678.56m (679m osm)
+                // route distance 10748130358 10048845537 : garage-pharmacie ploeuc => 8989.94m (9km osm)
+                // route distance 10748130358 2345943396 : garage-Pascal&Nathalie => 10522.86m (9km osm) ???
+                // route distance 10748130358 2000599137 : garage-Dr_Smau => 15228.37m (16km osm)
+                // route distance 10748130358 2971599465 : garage-Denis_Rebours => 17313.70m (18km osm)
+                match route( &engine, &places, &input ) {
+                    Ok((metric, r, unsatisfied, _)) => {
+                        let unit = if metric == Metric::Time { "s" } else { "m" };
+                        let mut total = 0.0;
+                        for (i, v) in r.nodes.iter().enumerate() {
+                            if i > 0 { total += r.edges.get( i - 1 ).map( |e| metric.of( e ) ).unwrap_or( 0.0 ); }
+                            println!( "{} : ", Colour::Yellow.paint( format!( "{:.2} {}", total, unit ) ) );
+                            print_elts( &g.tnodes, &[*v] );
+                            // affichage des way id et des noms de rue
+                            match g.tnodes.get( v ) {
+                                Some(n) => {
+                                    for id in n.ways() {
+                                        match Route::way_name( g, *id ) {
+                                            Some(name) if Route::is_ferry( g, *id ) => { println!( "\t{} : {} {}", id, name, Colour::Cyan.paint( "(bac)" ) ); },
+                                            None if Route::is_ferry( g, *id ) => { println!( "\t{} : {}", id, Colour::Cyan.paint( "(bac)" ) ); },
+                                            Some(name) => { println!( "\t{} : {}", id, name ); },
+                                            None if !g.tways.contains_key( id ) => { println!( "way id {} must be in db", id ); },
+                                            None => {},
                                         }
                                     }
-                                    // fin affichage
+                                },
+                                None => {
+                                    println!( "node id {} must be in db", v );
+                                }
+                            }
+                            // fin affichage
+                        }
+                        if let Some(climb) = Climb::of( g, &r.nodes ) {
+                            println!( "dénivelé : {}", Colour::Yellow.paint( climb.to_string() ) );
+                        }
+                        warn_unsatisfied( &unsatisfied );
+                        seen.extend( r.nodes.first().into_iter().chain( r.nodes.last() ) );
+                        last = Selection::Route( r );
+                    },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            }
+            "gpx" => {
+                // gpx distance 10748130360 4779385124 : cuisine-maryse => 359.85m (361m osm)
+                // gpx distance 10748130358 4779385124 : garage-maryse => 82.37m (83m osm)
+                // gpx distance 10748130358 7194631845 : garage-pharmacie Plaintel => 678.56m (679m osm)
+                // gpx distance 10748130358 10048845537 : garage-pharmacie ploeuc => 8989.94m (9km osm)
+                // gpx distance 10748130358 2345943396 : garage-Pascal&Nathalie => 10522.86m (9km osm) ???
+                // gpx distance 10748130358 2000599137 : garage-Dr_Smau => 15228.37m (16km osm)
+                // gpx distance 10748130358 2971599465 : garage-Denis_Rebours => 17313.70m (18km osm)
+                // gpx time 10748130358 4779385124 2026-10-19T08:00 ./data/maryse.gpx : horodaté, dans le fichier choisi
+                let (input, file) = match input.split_last() {
+                    Some((f, rest)) if input.len() > 4 && Format::of( Path::new( f ) ).is_ok() => ( rest.to_vec(), Path::new( *f ) ),
+                    _ => ( input.clone(), df.get_ofilepath().as_path() ),
+                };
+                match route( &engine, &places, &input ) {
+                    Ok((_, r, unsatisfied, depart)) => {
+                        warn_unsatisfied( &unsatisfied );
+                        last = Selection::Route( r );
+                        match save( g, &last, &gpx_options( depart ), file ) {
+                            Ok(()) => { println!( "{} : {}", Colour::Green.paint("enregistré"), file.display() ); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            }
+            "roundtrip" => {
+                // roundtrip bike 10748130358 40 : boucle de 40 km depuis le garage
+                // roundtrip foot 10748130358 10 3 : autre boucle (graine 3)
+                // roundtrip bike garage 40 : depuis un lieu nommé
+                // roundtrip bike garage 40 ./data/boucle.geojson : dans le fichier choisi
+                let (input, file) = match input.split_last() {
+                    Some((f, rest)) if input.len() > 4 && Format::of( Path::new( f ) ).is_ok() => ( rest.to_vec(), Path::new( *f ) ),
+                    _ => ( input.clone(), df.get_ofilepath().as_path() ),
+                };
+                let profile = Profile::from_name( input[1] );
+                let args = ( repl::node( &engine, &places, profile.unwrap_or( Profile::Car ), input[2], "node_id" ), repl::arg::<f64>( &input, 3, "km" ),
+                    if input.len() > 4 { repl::arg::<u64>( &input, 4, "graine" ) } else { Ok(0) } );
+                match ( profile, args ) {
+                    ( Some(profile), ( Ok(start), Ok(km), Ok(seed) ) ) => {
+                        match round_trip( g, &profile, start, km * 1000.0, seed ) {
+                            Some(rt) => {
+                                println!( "boucle {} : {} m dont {} m déjà parcourus",
+                                    profile,
+                                    Colour::Green.paint( format!( "{:.2}", rt.distance ) ),
+                                    Colour::Yellow.paint( format!( "{:.2}", rt.reused ) ) );
+                                last = Selection::Route( Route::from_nodes( g, engine.directed( profile ), rt.nodes ) );
+                                match save( g, &last, &gpx_options( None ), file ) {
+                                    Ok(()) => { println!( "{} : {}", Colour::Green.paint("enregistré"), file.display() ); },
+                                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
                                 }
                             },
-                            None => { println!( "impossible  de trouver un chemin"); },
-                        }
-                    }
-                    "gpx" => {
-                        // gpx distance 10748130360 4779385124 : cuisine-maryse => 359.85m (361m osm)
-                        // gpx distance 10748130358 4779385124 : garage-maryse => 82.37m (83m osm)
-                        // gpx distance 10748130358 7194631845 : garage-pharmacie Plaintel => 678.56m (679m osm)
-                        // gpx distance 10748130358 10048845537 : garage-pharmacie ploeuc => 8989.94m (9km osm)
-                        // gpx distance 10748130358 2345943396 : garage-Pascal&Nathalie => 10522.86m (9km osm) ???
-                        // gpx distance 10748130358 2000599137 : garage-Dr_Smau => 15228.37m (16km osm)
-                        // gpx distance 10748130358 2971599465 : garage-Denis_Rebours => 17313.70m (18km osm)
-                        match shortest_path( input[1], &g.get_directed(), input[2].parse::<i64>().unwrap(), input[3].parse::<i64>().unwrap() ) {
-                            Some(bt) => {
-                                let mut data : Gpx = Default::default();
-                                data.version = GpxVersion::Gpx11;
-
-                                let mut trkseg: TrackSegment = TrackSegment::new();
-                                let mut track: Track = Track::new();
-
-                                let mut lat_min: f64 = 95.0;
-                                let mut lat_max: f64 = -95.0;
-                                let mut lon_min: f64 = 180.0;
-                                let mut lon_max: f64 = -180.0;
-
-                                for (_k, v) in bt.iter() {
-                                    match &g.tnodes.get(&v) {
-                                        Some(n) => {
-                                            let lat = n.lat();
-                                            let lon = n.lon();
-
-                                            if lat < lat_min { lat_min = lat; }
-                                            if lat > lat_max { lat_max = lat; }
-                                            if lon < lon_min { lon_min = lon; }
-                                            if lon > lon_max { lon_max = lon; }
-                                            let pt = Waypoint::new( Point::new( lon, lat ) );
-                                            trkseg.points.push( pt );
-                                        },
-                                        None => {
-                                            println!("node id {} must be in db", v );
+                            None => { println!( "impossible  de trouver une boucle"); },
+                        }
+                    },
+                    ( None, _ ) => { println!( "{} : {}", input[1], Colour::Red.paint("profil inconnu") ); },
+                    ( _, ( Err(e), _, _ ) | ( _, Err(e), _ ) | ( _, _, Err(e) ) ) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            }
+            "match" => {
+                // match ./data/footing.gpx [bike] : trace enregistrée -> ways osm
+                // match ./data/footing.gpx bike ./data/footing_osm.gpx : trace corrigée dans le fichier choisi
+                let (input, file) = match input.split_last() {
+                    Some((f, rest)) if input.len() > 2 && Format::of( Path::new( f ) ).is_ok() => ( rest.to_vec(), Path::new( *f ) ),
+                    _ => ( input.clone(), df.get_ofilepath().as_path() ),
+                };
+                let profile = if input.len() > 2 { Profile::from_name( input[2] ) } else { Some(Profile::Car) };
+                match ( read_gpx_points( input[1] ), profile ) {
+                    ( Ok(points), Some(profile) ) => {
+                        let r = engine.matcher( profile ).match_points( &points );
+                        for (i, p) in r.points.iter().enumerate() {
+                            match p.matched {
+                                Some(m) => {
+                                    println!( "{:>5} : way {} à {} m, confiance {}", i,
+                                        Colour::Blue.paint( m.segment.way.to_string() ),
+                                        Colour::Green.paint( format!( "{:.2}", m.distance ) ),
+                                        Colour::Yellow.paint( format!( "{:.2}", p.confidence ) ) );
+                                },
+                                None => { println!( "{:>5} : {}", i, Colour::Red.paint("aucune voie proche") ); },
+                            }
+                        }
+                        let ways: Vec<String> = r.ways.iter().map( |w| w.to_string() ).collect();
+                        println!( "ways : {}", ways.join( " " ) );
+                        last = Selection::Route( r.route() );
+                        match save( g, &last, &gpx_options( None ), file ) {
+                            Ok(()) => { println!( "{} : {}", Colour::Green.paint("enregistré"), file.display() ); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    ( Err(e), _ ) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                    ( _, None ) => { println!( "{} : {}", input[2], Colour::Red.paint("profil inconnu") ); },
+                }
+            }
+            "import" => {
+                // import ./data/tournee.gpx [bike] : itinéraire par les points du fichier (gpx, geojson ou csv)
+                let profile = if input.len() > 2 { Profile::from_name( input[2] ) } else { Some(Profile::Car) };
+                match ( input.get( 1 ).filter( |s| !s.is_empty() ), profile ) {
+                    ( Some(file), Some(profile) ) => {
+                        match read_waypoints( Path::new( file ) ).and_then( |points| route_waypoints( &engine, profile, Metric::Time, &points, &RouteOptions::default() ).map( |t| (points, t) ) ) {
+                            Ok((points, tour)) => {
+                                for (i, p) in points.iter().enumerate() {
+                                    match ( tour.kept.iter().position( |k| *k == i ), tour.errors.iter().find( |(e, _)| *e == i ) ) {
+                                        ( Some(k), _ ) => {
+                                            let s = &tour.itinerary.waypoints[k];
+                                            println!( "{:>5} : {} -> noeud {} à {} m", i + 1, p, Colour::Blue.paint( s.node.to_string() ), Colour::Green.paint( format!( "{:.2}", s.distance ) ) );
                                         },
+                                        ( None, Some((_, e)) ) => { println!( "{:>5} : {} : {}", i + 1, p, Colour::Red.paint( e.to_string() ) ); },
+                                        _ => {},
                                     }
                                 }
-                                let mut meta: Metadata = Default::default();
-                                let rect = Rect::new(
-                                    coord! { x: lon_min, y: lat_min},
-                                    coord! { x: lon_max, y: lat_max},
-                                );
-                                meta.bounds = Some( rect );
-                                data.metadata = Some( meta );
-                                let f = File::create("./data/trace.gpx").expect("Unable to create file");
-                                track.segments.push( trkseg );
-                                data.tracks.push( track );
-                                write(&data, f).unwrap();
+                                let r = tour.itinerary.route();
+                                println!( "{} m en {} s par {} points", Colour::Green.paint( format!( "{:.2}", r.distance ) ),
+                                    Colour::Yellow.paint( format!( "{:.0}", r.time ) ), tour.kept.len() );
+                                warn_unsatisfied( &tour.itinerary.unsatisfied );
+                                last = Selection::Route( r );
+                            },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    ( None, _ ) => { println!( "{} : import fichier.gpx|fichier.geojson|fichier.csv [profile]", Colour::Red.paint("Erreur") ); },
+                    ( _, None ) => { println!( "{} : {}", input[2], Colour::Red.paint("profil inconnu") ); },
+                }
+            }
+            "isochrone" => {
+                // isochrone car 48.40631 -2.81467 10 : zone accessible en 10 minutes
+                // isochrone bike garage 20 : depuis un lieu nommé
+                let args = repl::position( &places, &input, 2 )
+                    .and_then( |(lat, lon, next)| Ok( (lat, lon, repl::arg::<f64>( &input, next, "minutes" )?) ) );
+                match ( Profile::from_name( input[1] ), args ) {
+                    ( Some(profile), Ok((lat, lon, minutes)) ) => {
+                        match engine.isochrone( profile, lat, lon, minutes * 60.0 ) {
+                            Ok(iso) => {
+                                let far = iso.reached.values().map( |c| c.1 ).fold( 0.0, f64::max );
+                                println!( "{} noeuds accessibles depuis {} en {} min, jusqu'à {} m",
+                                    Colour::Green.paint( iso.reached.len().to_string() ),
+                                    Colour::Blue.paint( iso.origin.node.to_string() ),
+                                    minutes,
+                                    Colour::Yellow.paint( format!( "{:.2}", far ) ) );
+                                last = Selection::Isochrone( iso );
                             },
-                            None => { println!( "impossible  de trouver un chemin"); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                        }
+                    },
+                    ( None, _ ) => { println!( "{} : {}", input[1], Colour::Red.paint("profil inconnu") ); },
+                    ( _, Err(e) ) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            }
+            "save" => {
+                // save ./data/route.geojson : dernier résultat (show, route, gpx, roundtrip)
+                // save graph ./data/graph.geojson : toutes les voies du graphe
+                let result = match ( input[1], input.get( 2 ) ) {
+                    ( "graph", Some(file) ) => Some( geojson::save( &geojson::graph( g ), Path::new( file ) ) ),
+                    ( "graph", None ) => None,
+                    ( file, _ ) => Some( save( g, &last, &gpx_options( None ), Path::new( file ) ) ),
+                };
+                match result {
+                    Some(Ok(())) => { println!( "{}", Colour::Green.paint("enregistré") ); },
+                    Some(Err(e)) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                    None => { println!( "{} : argument manquant, usage : save graph fichier.geojson", Colour::Red.paint("Erreur") ); },
+                }
+            }
+            "render" => {
+                // render ./data/carte.svg [largeur hauteur] [min_lon,min_lat,max_lon,max_lat] :
+                // voies du cadre, ou autour du dernier itinéraire (ou tout le graphe)
+                let (frame, size): (Vec<&str>, Vec<&str>) = input.iter().skip( 2 ).partition( |s| s.contains( ',' ) );
+                let bbox = frame.first().map( |s| cli::bbox( s ) ).transpose();
+                let size = ( size.first().map_or( Ok(1024), |s| s.parse::<u32>() ), size.get( 1 ).map_or( Ok(768), |s| s.parse::<u32>() ) );
+                match ( input.get( 1 ).filter( |s| !s.is_empty() ), size, bbox ) {
+                    ( Some(file), ( Ok(width), Ok(height) ), Ok(bbox) ) => {
+                        let r = if let Selection::Route(r) = &last { Some(r) } else { None };
+                        match render::render( g, r, width, height, bbox ).and_then( |map| render::save( &map, Path::new( file ) ) ) {
+                            Ok(()) => { println!( "{} : {}", Colour::Green.paint("enregistré"), file ); },
+                            Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
                         }
-                    }
-                    &_ => {
-                        println!( "{} : {}", input[0], Colour::Red.paint("Commande inconnue") );
                     },
+                    ( _, _, Err(e) ) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                    _ => { println!( "{} : render fichier.svg|fichier.png [largeur hauteur] [min_lon,min_lat,max_lon,max_lat]", Colour::Red.paint("Erreur") ); },
                 }
             }
-            Err(error) => println!("Erreur: {error}"),
+            "vrp" => {
+                // vrp ./data/tournee.json ./data/tournee_solution.json
+                match Problem::from_file( input[1] ).and_then( |p| solve( &p, engine.directed( Profile::Car ), |lat, lon| engine.nearest( Profile::Car, lat, lon ).map( |s| s.node ) ) ) {
+                    Ok(s) => {
+                        println!( "{}", s );
+                        if let Some(file) = input.get( 2 ) {
+                            let written = File::create( file ).map_err( |e| e.to_string() )
+                                .and_then( |f| serde_json::to_writer_pretty( f, &s ).map_err( |e| e.to_string() ) );
+                            if let Err(e) = written { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); }
+                        }
+                    },
+                    Err(e) => { println!( "{} : {}", Colour::Red.paint("Erreur"), e ); },
+                }
+            }
+            &_ => {
+                println!( "{} : {}", input[0], Colour::Red.paint("Commande inconnue") );
+            },
         }
-    };
+        if let Some(h) = rl.helper_mut() {
+            h.remember( &seen );
+            h.set_places( &places );
+        }
+    }
+    if let Err(e) = rl.save_history( df.get_historyfilepath() ) {
+        println!( "{} : {}", Colour::Red.paint("Erreur"), e );
+    }
 }
 
 
 ///
-/// convertir la liste d'id nodes ou ways (de type str) en i64
+/// itinéraire en voiture de la commande route ou gpx : mode node_1 node_2 [départ] [avoid=toll,motorway],
+/// les noeuds pouvant être désignés par un lieu nommé,
+/// le départ ("2026-10-19T08:00") appliquant les restrictions conditionnelles ; renvoie aussi les
+/// voies à éviter que l'itinéraire emprunte faute d'alternative et le départ
 ///
-fn convert_vec( mut v: Vec<&str> ) -> Vec<i64> {
-    let mut collect: Vec<i64> = Vec::new();
-
-    if v.len() > 2 {
-        for i in v.split_off(2).iter() {
-            collect.push( i.parse::<i64>().unwrap() );
+fn route( engine: &RoutingEngine, places: &Places, input: &[&str] ) -> Result<(Metric, Route, Vec<Avoid>, Option<NaiveDateTime>), String> {
+    let usage = || format!( "{} mode node_1|lieu node_2|lieu [AAAA-MM-JJTHH:MM] [avoid=toll,motorway,ferry,unpaved,tunnel,bridge]", input[0] );
+    let metric = input.get( 1 ).and_then( |m| Metric::from_name( m ) ).ok_or_else( usage )?;
+    let from = repl::node( engine, places, Profile::Car, input.get( 2 ).ok_or_else( usage )?, "node_1" )?;
+    let to = repl::node( engine, places, Profile::Car, input.get( 3 ).ok_or_else( usage )?, "node_2" )?;
+    let mut options = RouteOptions::default();
+    for arg in input.iter().skip( 4 ) {
+        match arg.strip_prefix( "avoid=" ) {
+            Some(list) => { options.avoid = Avoid::parse_list( list )?; },
+            None => { options.depart = Some( cli::datetime( arg )? ); },
         }
-    } else {
-        println!( "{}\nuse 'show nodes' cde instead", Colour::Red.paint("need one or more node_id." ) );
     }
-    collect
+    let r = engine.route_nodes_with( Profile::Car, metric, from, to, &options ).map_err( |e| e.to_string() )?;
+    let unsatisfied = Avoid::unsatisfied( &options.avoid, engine.graph(), &r );
+    Ok( (metric, r, unsatisfied, options.depart) )
+}
+
+///
+/// gpx des itinéraires du menu : manœuvres en <rte>, départ et arrivée en <wpt>, points horodatés
+/// si le départ est donné
+///
+fn gpx_options( depart: Option<NaiveDateTime> ) -> GpxOptions {
+    GpxOptions { depart, instructions: true, waypoints: true, ..Default::default() }
+}
+
+///
+/// avertissement pour les voies à éviter empruntées
+///
+fn warn_unsatisfied( unsatisfied: &[Avoid] ) {
+    if unsatisfied.is_empty() { return; }
+    let names: Vec<String> = unsatisfied.iter().map( |a| a.to_string() ).collect();
+    println!( "{} : {} non évité(s), pas d'autre itinéraire", Colour::Yellow.paint("Attention"), names.join( ", " ) );
+}
+
+///
+/// enregistrer le dernier résultat, au format déduit de l'extension du fichier
+///
+fn save( g: &Graph, last: &Selection, gpx: &GpxOptions, path: &Path ) -> Result<(), ExportError> {
+    match ( Format::of( path )?, last ) {
+        ( _, Selection::Nothing ) => Err( ExportError::Unsupported( "rien à enregistrer".to_string() ) ),
+        ( _, Selection::Route(r) ) => output::save_route( g, r, gpx, path ),
+        ( Format::Gpx, Selection::Nodes(v) ) => gpx_file::save_nodes( g, v, path ),
+        ( Format::Gpx, Selection::Ways(_) ) => Err( ExportError::Unsupported( "ways en gpx, utiliser .geojson".to_string() ) ),
+        ( Format::GeoJson, Selection::Nodes(v) ) => geojson::save( &geojson::nodes( g, v ), path ),
+        ( Format::GeoJson, Selection::Ways(v) ) => geojson::save( &geojson::ways( g, v ), path ),
+        ( Format::Gpx, Selection::Isochrone(iso) ) => gpx_file::save_points( &iso.polygon, path ),
+        ( Format::GeoJson, Selection::Isochrone(iso) ) => geojson::save( &geojson::isochrone( iso ), path ),
+        ( Format::Gpx, Selection::Report(_) ) => Err( ExportError::Unsupported( "rapport en gpx, utiliser .geojson".to_string() ) ),
+        ( Format::GeoJson, Selection::Report(r) ) => geojson::save( &geojson::report( r ), path ),
+        ( Format::Html, _ ) | ( Format::Polyline(_), _ ) => Err( ExportError::Unsupported( "seuls les itinéraires s'exportent en html ou polyline".to_string() ) ),
+    }
+}
+
+///
+/// convertir la liste d'id nodes ou ways (de type str) en i64
+///
+fn convert_vec( v: &[&str] ) -> Result<Vec<i64>, String> {
+    if v.len() < 3 {
+        return Err( "un ou plusieurs id attendus, 'show nodes' ou 'show ways' pour un tirage au hasard".to_string() );
+    }
+    v.iter().skip( 2 ).map( |i| i.parse::<i64>().map_err( |_| format!( "{} : id invalide", i ) ) ).collect()
 }
 
 
@@ -223,6 +641,7 @@ fn rand_elts<T>(bt: &HashMap<i64,T>) -> Vec<i64> {
     for (k, _) in bt.iter() { keys.push( *k ); }
 
     let max = keys.len();
+    if max == 0 { return Vec::new(); }
     let mut i = 0_i32;
     let mut collect: Vec<i64> = Vec::new();
     while i < 5 {
@@ -237,11 +656,22 @@ fn rand_elts<T>(bt: &HashMap<i64,T>) -> Vec<i64> {
 ///
 /// afficher les élèments de la liste (way ou node)
 ///
-fn print_elts<T: std::fmt::Display>( bt: &HashMap<i64,T>, indexes: &Vec<i64>  ) {
+fn print_elts<T: std::fmt::Display>( bt: &HashMap<i64,T>, indexes: &[i64]  ) {
     for i in indexes.iter() {
-        match bt.get( &i ) {
+        match bt.get( i ) {
             Some(s) => { println!( "{}\n{}", Colour::Blue.paint(i.to_string()), s ); },
             None => { println!( "{}", Colour::Red.paint("id not found") ); }
         }
     }
 }
+
+
+
+// données des tests de la bibliothèque, sous crate::graphe::testing comme dans celle-ci
+#[cfg(test)]
+mod graphe {
+    pub use route::graphe::*;
+    #[allow(dead_code)]
+    #[path = "testing.rs"]
+    pub(crate) mod testing;
+}
@@ -0,0 +1,393 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use ansi_term::Colour;
+use route::{EngineError, RoutingEngine};
+use route::routing::places::{PlaceError, Places};
+use route::routing::profile::Profile;
+use rustyline::{Context, Editor, Helper};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+
+
+// nombre d'id osm proposés à la complétion
+const SEEN: usize = 100;
+
+///
+/// commande du menu : nom, arguments attendus et description pour help
+///
+pub struct Verb {
+    pub name: &'static str,
+    pub args: &'static str,
+    /// nombre minimal d'arguments
+    pub min: usize,
+    pub help: &'static str,
+}
+
+pub const VERBS: &[Verb] = &[
+    Verb { name: "info", args: "", min: 0, help: "résumé du graphe et composantes fortement connexes par profil" },
+    Verb { name: "validate", args: "", min: 0, help: "contrôle de cohérence du graphe (noeuds orphelins, ways dégénérées, ...)" },
+    Verb { name: "show", args: "node|way id [id ...] | nodes|ways", min: 1, help: "afficher des noeuds ou des ways, 5 au hasard avec nodes ou ways" },
+    Verb { name: "locate", args: "adresse", min: 1, help: "géocodage d'une adresse et noeud le plus proche de chaque résultat" },
+    Verb { name: "nearest", args: "lat lon|lieu [car|bike|foot]", min: 1, help: "noeud d'une voie praticable le plus proche d'une position" },
+    Verb { name: "whereis", args: "lat lon", min: 2, help: "rue, numéro et commune d'une position" },
+    Verb { name: "route", args: "distance|time|cost node_1|lieu node_2|lieu [AAAA-MM-JJTHH:MM] [avoid=toll,motorway,...]", min: 3, help: "itinéraire en voiture entre deux noeuds" },
+    Verb { name: "gpx", args: "distance|time|cost node_1|lieu node_2|lieu [AAAA-MM-JJTHH:MM] [avoid=...] [fichier]", min: 3, help: "itinéraire en voiture enregistré en gpx (ou au format de l'extension du fichier)" },
+    Verb { name: "roundtrip", args: "car|bike|foot node_id|lieu km [graine] [fichier]", min: 3, help: "boucle d'environ km kilomètres, enregistrée en gpx (ou au format de l'extension du fichier)" },
+    Verb { name: "match", args: "fichier.gpx [car|bike|foot] [fichier]", min: 1, help: "rattacher une trace enregistrée aux ways osm, trace corrigée enregistrée en gpx (ou au format de l'extension)" },
+    Verb { name: "import", args: "fichier.gpx|fichier.geojson|fichier.csv [car|bike|foot]", min: 1, help: "itinéraire passant par les points du fichier" },
+    Verb { name: "isochrone", args: "car|bike|foot lat lon|lieu minutes", min: 3, help: "zone accessible en un temps donné" },
+    Verb { name: "save", args: "fichier | graph fichier", min: 1, help: "enregistrer le dernier résultat (format déduit de l'extension) ou tout le graphe" },
+    Verb { name: "render", args: "fichier.svg|fichier.png [largeur hauteur] [min_lon,min_lat,max_lon,max_lat]", min: 1, help: "carte des voies du cadre, ou autour du dernier itinéraire (ou de tout le graphe)" },
+    Verb { name: "vrp", args: "problem.json [solution.json]", min: 1, help: "tournées de véhicules" },
+    Verb { name: "place", args: "add nom node_id|lat lon|lieu | list | rm nom", min: 1, help: "lieux nommés, utilisables à la place d'un id de noeud ou d'une position" },
+    Verb { name: "help", args: "[commande]", min: 0, help: "liste des commandes ou aide d'une commande" },
+    Verb { name: "exit", args: "", min: 0, help: "quitter (aussi quit ou Ctrl-D)" },
+    Verb { name: "quit", args: "", min: 0, help: "quitter (aussi exit ou Ctrl-D)" },
+];
+
+///
+/// description d'une commande du menu
+///
+pub fn verb( name: &str ) -> Option<&'static Verb> {
+    VERBS.iter().find( |v| v.name == name )
+}
+
+impl Verb {
+    pub fn usage( &self ) -> String {
+        if self.args.is_empty() { self.name.to_string() } else { format!( "{} {}", self.name, self.args ) }
+    }
+}
+
+///
+/// aide : liste des commandes, ou usage et description d'une seule
+///
+pub fn help( topic: Option<&str> ) -> Result<String, String> {
+    match topic {
+        Some(name) => {
+            let v = verb( name ).ok_or_else( || format!( "{} : commande inconnue", name ) )?;
+            Ok( format!( "{}\n\t{}", Colour::Yellow.paint( v.usage() ), v.help ) )
+        },
+        None => {
+            let lines: Vec<String> = VERBS.iter().map( |v| format!( "{} {}", Colour::Yellow.paint( format!( "{:<10}", v.name ) ), v.help ) ).collect();
+            Ok( format!( "{}\n'help commande' pour les arguments", lines.join( "\n" ) ) )
+        },
+    }
+}
+
+///
+/// commande connue et nombre d'arguments suffisant, sinon message avec l'usage
+///
+pub fn check( input: &[&str] ) -> Result<(), String> {
+    let name = input.first().copied().unwrap_or( "" );
+    let v = verb( name ).ok_or_else( || format!( "{} : commande inconnue, taper help", name ) )?;
+    if input.len() - 1 < v.min {
+        return Err( format!( "argument manquant, usage : {}", v.usage() ) );
+    }
+    Ok(())
+}
+
+///
+/// mots d'une ligne du menu, un texte entre guillemets formant un seul mot ("Pharmacie Plaintel")
+///
+pub fn words( line: &str ) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => { quoted = !quoted; word.get_or_insert_with( String::new ); },
+            c if c.is_whitespace() && !quoted => { words.extend( word.take() ); },
+            c => { word.get_or_insert_with( String::new ).push( c ); },
+        }
+    }
+    words.extend( word );
+    words
+}
+
+///
+/// nom d'un lieu, entre guillemets s'il compte plusieurs mots
+///
+pub fn quote( name: &str ) -> String {
+    if name.contains( char::is_whitespace ) { format!( "\"{}\"", name ) } else { name.to_string() }
+}
+
+///
+/// place add : le nom est fait des mots entre "add" et le noeud, la position ou le lieu qui terminent
+/// la commande ; renvoie aussi l'indice de ce dernier argument
+///
+pub fn new_place( input: &[&str] ) -> Result<(String, usize), String> {
+    let n = input.len();
+    let integer = |s: &str| s.parse::<i64>().is_ok();
+    let number = |s: &str| s.parse::<f64>().is_ok();
+    // lat lon, sauf deux entiers : un nom finissant par un nombre suivi d'un id de noeud
+    let at = if n > 4 && number( input[n - 2] ) && number( input[n - 1] ) && !( integer( input[n - 2] ) && integer( input[n - 1] ) ) { n - 2 } else { n - 1 };
+    if input.get( 1 ) != Some(&"add") || at < 3 {
+        return Err( format!( "argument manquant, usage : {}", verb( "place" ).map( |v| v.usage() ).unwrap_or_default() ) );
+    }
+    Ok( (input[2..at].join( " " ), at) )
+}
+
+///
+/// argument n°i converti, message lisible s'il manque ou ne se lit pas
+///
+pub fn arg<T: FromStr>( input: &[&str], i: usize, name: &str ) -> Result<T, String> {
+    let s = input.get( i ).ok_or_else( || format!( "{} manquant", name ) )?;
+    s.parse::<T>().map_err( |_| format!( "{} : {} invalide", s, name ) )
+}
+
+///
+/// noeud désigné par son id ou par un lieu nommé, une position étant rattachée au noeud le plus proche ;
+/// le message d'erreur nomme l'argument `name` en cause
+///
+pub fn node( engine: &RoutingEngine, places: &Places, profile: Profile, s: &str, name: &str ) -> Result<i64, String> {
+    if let Ok(id) = s.parse::<i64>() {
+        return if engine.graph().tnodes.contains_key( &id ) { Ok( id ) } else { Err( format!( "{} : noeud absent du graphe ({})", id, name ) ) };
+    }
+    let place = places.get( s ).ok_or_else( || format!( "{} ({})", PlaceError::Unknown( s.to_string() ), name ) )?;
+    match place.node {
+        Some(id) => Ok( id ),
+        None => engine.nearest( profile, place.lat, place.lon ).map( |snap| snap.node )
+            .ok_or_else( || EngineError::NoSegment( place.lat, place.lon ).to_string() ),
+    }
+}
+
+///
+/// position à partir de l'argument n°i, "lat lon" ou nom d'un lieu ; renvoie aussi l'indice de l'argument suivant
+///
+pub fn position( places: &Places, input: &[&str], i: usize ) -> Result<(f64, f64, usize), String> {
+    let s = input.get( i ).ok_or_else( || "lat manquant".to_string() )?;
+    match places.get( s ) {
+        Some(p) => Ok( (p.lat, p.lon, i + 1) ),
+        None if s.parse::<f64>().is_ok() => Ok( (arg( input, i, "lat" )?, arg( input, i + 1, "lon" )?, i + 2) ),
+        None => Err( PlaceError::Unknown( s.to_string() ).to_string() ),
+    }
+}
+
+
+///
+/// complétion du menu : commandes, mots clés de la commande, lieux nommés, id osm récemment affichés et fichiers
+///
+pub struct ReplHelper {
+    seen: VecDeque<i64>,
+    places: Vec<String>,
+    files: FilenameCompleter,
+}
+
+impl ReplHelper {
+    pub fn new() -> Self {
+        ReplHelper { seen: VecDeque::new(), places: Vec::new(), files: FilenameCompleter::new() }
+    }
+
+    ///
+    /// noms des lieux proposés à la complétion
+    ///
+    pub fn set_places( &mut self, places: &Places ) {
+        self.places = places.iter().map( |(name, _)| name.clone() ).collect();
+    }
+
+    ///
+    /// retenir des id osm pour la complétion, les plus récents en premier
+    ///
+    pub fn remember( &mut self, ids: &[i64] ) {
+        for id in ids.iter() {
+            self.seen.retain( |s| s != id );
+            self.seen.push_front( *id );
+        }
+        self.seen.truncate( SEEN );
+    }
+
+    // mots clés attendus en position `n` (1 pour le premier argument) de la commande
+    fn keywords( command: &str, n: usize ) -> Vec<&'static str> {
+        match ( command, n ) {
+            ( "show", 1 ) => vec!["node", "way", "nodes", "ways"],
+            ( "route" | "gpx", 1 ) => vec!["distance", "time", "cost"],
+            ( "route" | "gpx", n ) if n > 3 => vec!["avoid="],
+            ( "roundtrip" | "isochrone", 1 ) | ( "match" | "import", 2 ) | ( "nearest", 2 | 3 ) => vec!["car", "bike", "foot"],
+            ( "save", 1 ) => vec!["graph"],
+            ( "place", 1 ) => vec!["add", "list", "rm"],
+            ( "help", 1 ) => VERBS.iter().map( |v| v.name ).collect(),
+            _ => vec![],
+        }
+    }
+
+    // un lieu nommé est accepté en position `n` de la commande
+    fn takes_place( before: &[&str] ) -> bool {
+        matches!( ( before[0], before.len() ),
+            ( "route" | "gpx", 2 | 3 ) | ( "nearest", 1 ) | ( "isochrone" | "roundtrip", 2 ) )
+            || before[..] == ["place", "rm"]
+    }
+}
+
+impl Default for ReplHelper {
+    fn default() -> Self {
+        ReplHelper::new()
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete( &self, line: &str, pos: usize, ctx: &Context<'_> ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let head = &line[..pos];
+        // nom de lieu entre guillemets en cours de saisie : il peut contenir des espaces
+        let quoted = head.matches( '"' ).count() % 2 == 1;
+        let start = if quoted { head.rfind( '"' ).unwrap_or( 0 ) } else { head.rfind( ' ' ).map_or( 0, |i| i + 1 ) };
+        let word = if quoted { &head[start + 1..] } else { &head[start..] };
+        let words = words( &head[..start] );
+        let before: Vec<&str> = words.iter().map( |w| w.as_str() ).collect();
+        let pair = |s: String| Pair { display: s.clone(), replacement: s };
+
+        let candidates: Vec<Pair> = match before.first() {
+            None => VERBS.iter().filter( |v| v.name.starts_with( word ) ).map( |v| pair( v.name.to_string() ) ).collect(),
+            Some(_) if !quoted && word.starts_with( |c: char| c.is_ascii_digit() ) => {
+                self.seen.iter().map( |id| id.to_string() ).filter( |id| id.starts_with( word ) ).map( pair ).collect()
+            },
+            Some(command) => {
+                let mut keywords: Vec<Pair> = if quoted { Vec::new() } else {
+                    ReplHelper::keywords( command, before.len() ).into_iter()
+                        .filter( |k| k.starts_with( word ) ).map( |k| pair( k.to_string() ) ).collect()
+                };
+                if ReplHelper::takes_place( &before ) {
+                    let name = |p: &String| if quoted { format!( "\"{}\"", p ) } else { quote( p ) };
+                    keywords.extend( self.places.iter().filter( |p| p.starts_with( word ) ).map( |p| pair( name( p ) ) ) );
+                }
+                if keywords.is_empty() { return self.files.complete( line, pos, ctx ); }
+                keywords
+            },
+        };
+        Ok( (start, candidates) )
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>( &'s self, prompt: &'p str, _default: bool ) -> Cow<'b, str> {
+        Cow::Owned( Colour::Yellow.paint( prompt ).to_string() )
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+///
+/// éditeur de ligne du menu, avec complétion
+///
+pub fn editor() -> rustyline::Result<Editor<ReplHelper, FileHistory>> {
+    let mut rl = Editor::<ReplHelper, FileHistory>::new()?;
+    rl.set_helper( Some( ReplHelper::new() ) );
+    Ok( rl )
+}
+
+
+#[cfg(test)]
+mod repl_tests {
+    use super::*;
+    use rustyline::history::DefaultHistory;
+    use route::routing::places::Place;
+
+    fn complete( h: &ReplHelper, line: &str ) -> Vec<String> {
+        let history = DefaultHistory::new();
+        let (_, pairs) = h.complete( line, line.len(), &Context::new( &history ) ).unwrap();
+        pairs.into_iter().map( |p| p.replacement ).collect()
+    }
+
+    #[test]
+    fn arguments() {
+        assert!( check( &["info"] ).is_ok() );
+        assert!( check( &["route", "time", "1", "2"] ).is_ok() );
+        assert_eq!( Err( "argument manquant, usage : whereis lat lon".to_string() ), check( &["whereis", "48.5"] ) );
+        assert!( check( &["frobnicate"] ).unwrap_err().contains( "commande inconnue" ) );
+
+        let input = ["nearest", "48.5", "abc"];
+        assert_eq!( Ok(48.5), arg::<f64>( &input, 1, "lat" ) );
+        assert_eq!( Err( "abc : lon invalide".to_string() ), arg::<f64>( &input, 2, "lon" ) );
+        assert_eq!( Err( "seed manquant".to_string() ), arg::<u64>( &input, 3, "seed" ) );
+
+        assert!( help( Some( "isochrone" ) ).unwrap().contains( "isochrone car|bike|foot lat lon|lieu minutes" ) );
+        assert!( help( Some( "nope" ) ).is_err() );
+        assert!( VERBS.iter().all( |v| help( None ).unwrap().contains( v.name ) ) );
+    }
+
+    #[test]
+    fn completion() {
+        let mut h = ReplHelper::new();
+        assert_eq!( vec!["route", "roundtrip", "render"], complete( &h, "r" ) );
+        assert_eq!( vec!["info", "import", "isochrone"], complete( &h, "i" ) );
+        assert_eq!( vec!["nodes".to_string()], complete( &h, "show nodes" ) );
+        assert_eq!( vec!["time".to_string()], complete( &h, "route t" ) );
+        assert_eq!( vec!["bike".to_string()], complete( &h, "isochrone b" ) );
+
+        assert!( complete( &h, "show node 1" ).is_empty() );
+        h.remember( &[123, 456, 124] );
+        h.remember( &[123] );
+        assert_eq!( vec!["123".to_string(), "124".to_string()], complete( &h, "route time 12" ) );
+        assert_eq!( vec!["456".to_string()], complete( &h, "show node 123 4" ) );
+
+        let mut places = Places::default();
+        places.insert( "garage", Place { lat: 48.4063898, lon: -2.8150775, node: Some( 10748130358 ) } ).unwrap();
+        places.insert( "gare", Place { lat: 48.5, lon: -2.7, node: None } ).unwrap();
+        h.set_places( &places );
+        assert_eq!( vec!["garage", "gare"], complete( &h, "route time ga" ) );
+        assert_eq!( vec!["gare"], complete( &h, "isochrone bike gare" ) );
+        assert_eq!( vec!["garage"], complete( &h, "place rm garag" ) );
+        assert!( complete( &h, "show ga" ).is_empty() );
+
+        // nom de plusieurs mots : proposé entre guillemets, aussi une fois le guillemet ouvert
+        places.insert( "Pharmacie Plaintel", Place { lat: 48.41, lon: -2.82, node: None } ).unwrap();
+        h.set_places( &places );
+        assert_eq!( vec!["\"Pharmacie Plaintel\""], complete( &h, "route time Ph" ) );
+        assert_eq!( vec!["\"Pharmacie Plaintel\""], complete( &h, "route time \"Pharmacie P" ) );
+        assert_eq!( vec!["garage"], complete( &h, "route time \"Pharmacie Plaintel\" gara" ) );
+    }
+
+    #[test]
+    fn places() {
+        let mut places = Places::default();
+        places.insert( "maryse", Place { lat: 48.40672, lon: -2.81433, node: None } ).unwrap();
+        let input = ["isochrone", "car", "maryse", "10"];
+        assert_eq!( Ok( (48.40672, -2.81433, 3) ), position( &places, &input, 2 ) );
+        let input = ["isochrone", "car", "48.4", "-2.8", "10"];
+        assert_eq!( Ok( (48.4, -2.8, 4) ), position( &places, &input, 2 ) );
+        assert_eq!( Err( "lon manquant".to_string() ), position( &places, &["nearest", "48.4"], 1 ) );
+        assert_eq!( Err( "garage : lieu inconnu".to_string() ), position( &places, &["nearest", "garage"], 1 ) );
+    }
+
+    #[test]
+    fn quoted_names() {
+        assert_eq!( vec!["route", "time", "Pharmacie Plaintel", "garage"], words( "route  time \"Pharmacie Plaintel\" garage" ) );
+        assert_eq!( vec!["place", "rm", "Le Bourg"], words( "place rm \"Le Bourg" ) );
+        assert_eq!( vec!["x", ""], words( "x \"\"" ) );
+        assert_eq!( "\"Le Bourg\"", quote( "Le Bourg" ) );
+
+        let add = |line: &str| new_place( &words( line ).iter().map( |w| w.as_str() ).collect::<Vec<&str>>() );
+        assert_eq!( Ok( ("Pharmacie Plaintel".to_string(), 4) ), add( "place add Pharmacie Plaintel 7194631845" ) );
+        assert_eq!( Ok( ("Pharmacie Plaintel".to_string(), 3) ), add( "place add \"Pharmacie Plaintel\" 7194631845" ) );
+        assert_eq!( Ok( ("chez Maryse".to_string(), 4) ), add( "place add chez Maryse 48.40672 -2.81433" ) );
+        assert_eq!( Ok( ("Route 12".to_string(), 4) ), add( "place add Route 12 7194631845" ) );
+        assert_eq!( Ok( ("maison".to_string(), 3) ), add( "place add maison garage" ) );
+        assert!( add( "place add 7194631845" ).is_err() );
+    }
+
+    #[test]
+    fn checked_nodes() {
+        use crate::graphe::testing::test_graph;
+
+        let engine = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99)], &[(10, &[1, 2], &[("highway", "residential")])] ) );
+        let mut places = Places::default();
+        places.insert( "Le Bourg", Place { lat: 48.0, lon: -1.9901, node: None } ).unwrap();
+
+        assert_eq!( Ok(1), node( &engine, &places, Profile::Car, "1", "node_1" ) );
+        assert_eq!( Ok(2), node( &engine, &places, Profile::Car, "Le Bourg", "node_1" ) );
+        assert_eq!( Err( "3 : noeud absent du graphe (node_2)".to_string() ), node( &engine, &places, Profile::Car, "3", "node_2" ) );
+        assert_eq!( Err( "gare : lieu inconnu (node_2)".to_string() ), node( &engine, &places, Profile::Car, "gare", "node_2" ) );
+    }
+}
@@ -2,3 +2,15 @@
 pub mod location;
 pub mod distances;
 pub mod dijkstra;
+pub mod vrp;
+pub mod profile;
+pub mod conditional;
+pub mod avoid;
+pub mod roundtrip;
+pub mod matching;
+pub mod route;
+pub mod instructions;
+pub mod reverse;
+pub mod geocoder;
+pub mod waypoints;
+pub mod places;
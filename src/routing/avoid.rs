@@ -0,0 +1,121 @@
+use std::fmt;
+use std::collections::HashMap;
+
+use crate::graphe::Graph;
+use crate::routing::route::Route;
+
+
+// facteur appliqué au poids des arcs à éviter quand l'itinéraire ne peut pas s'en passer
+pub const AVOID_PENALTY: f64 = 100.0;
+
+///
+/// type de voie que l'itinéraire doit éviter, d'après les tags de la way
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Avoid {
+    Toll,
+    Motorway,
+    Ferry,
+    Unpaved,
+    Tunnel,
+    Bridge,
+}
+
+impl Avoid {
+    pub const ALL: [Avoid; 6] = [Avoid::Toll, Avoid::Motorway, Avoid::Ferry, Avoid::Unpaved, Avoid::Tunnel, Avoid::Bridge];
+
+    ///
+    /// option à partir de son nom ("toll", "motorway", ...)
+    ///
+    pub fn from_name( s: &str ) -> Option<Avoid> {
+        match s {
+            "toll" | "tolls" | "peage" | "péage" => Some(Avoid::Toll),
+            "motorway" | "motorways" | "autoroute" => Some(Avoid::Motorway),
+            "ferry" | "ferries" | "bac" => Some(Avoid::Ferry),
+            "unpaved" => Some(Avoid::Unpaved),
+            "tunnel" | "tunnels" => Some(Avoid::Tunnel),
+            "bridge" | "bridges" | "pont" => Some(Avoid::Bridge),
+            _ => None,
+        }
+    }
+
+    ///
+    /// liste d'options séparées par des virgules : "toll,motorway"
+    ///
+    pub fn parse_list( s: &str ) -> Result<Vec<Avoid>, String> {
+        let mut avoid = Vec::new();
+        for name in s.split( ',' ).map( |n| n.trim() ).filter( |n| !n.is_empty() ) {
+            let a = Avoid::from_name( name ).ok_or( format!( "option d'évitement inconnue : {}", name ) )?;
+            if !avoid.contains( &a ) { avoid.push( a ); }
+        }
+        Ok(avoid)
+    }
+
+    ///
+    /// la way décrite par ces tags est-elle concernée par l'option
+    ///
+    pub fn matches( &self, tags: &HashMap<String, String> ) -> bool {
+        let value = |k: &str| tags.get( k ).map( |v| v.as_str() );
+        match self {
+            Avoid::Toll => value( "toll" ) == Some( "yes" ),
+            Avoid::Motorway => value( "highway" ).is_some_and( |h| h.starts_with( "motorway" ) ),
+            Avoid::Ferry => value( "route" ) == Some( "ferry" ),
+            Avoid::Unpaved => matches!( value( "surface" ), Some( "unpaved" | "gravel" | "fine_gravel" | "compacted" | "dirt" |
+                "earth" | "ground" | "grass" | "sand" | "mud" | "pebblestone" | "rock" | "woodchips" | "grass_paver" ) )
+                || matches!( value( "tracktype" ), Some( "grade3" | "grade4" | "grade5" ) ),
+            Avoid::Tunnel => value( "tunnel" ).is_some_and( |v| v != "no" ),
+            Avoid::Bridge => value( "bridge" ).is_some_and( |v| v != "no" ),
+        }
+    }
+
+    ///
+    /// la way `id` du graphe est-elle concernée par l'une des options
+    ///
+    pub fn any( avoid: &[Avoid], g: &Graph, id: i64 ) -> bool {
+        !avoid.is_empty() && g.tways.get( &id ).is_some_and( |w| avoid.iter().any( |a| a.matches( w.tags() ) ) )
+    }
+
+    ///
+    /// options que l'itinéraire n'a pas pu respecter : celles d'une des ways empruntées
+    ///
+    pub fn unsatisfied( avoid: &[Avoid], g: &Graph, r: &Route ) -> Vec<Avoid> {
+        avoid.iter()
+            .filter( |a| r.ways.iter().filter_map( |w| g.tways.get( w ) ).any( |w| a.matches( w.tags() ) ) )
+            .copied()
+            .collect()
+    }
+}
+
+impl fmt::Display for Avoid {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            Avoid::Toll => write!( f, "toll" ),
+            Avoid::Motorway => write!( f, "motorway" ),
+            Avoid::Ferry => write!( f, "ferry" ),
+            Avoid::Unpaved => write!( f, "unpaved" ),
+            Avoid::Tunnel => write!( f, "tunnel" ),
+            Avoid::Bridge => write!( f, "bridge" ),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod avoid_tests {
+    use super::*;
+    use crate::graphe::testing::tags;
+
+    #[test]
+    fn avoid_tags() {
+        assert_eq!( Ok(vec![Avoid::Toll, Avoid::Motorway]), Avoid::parse_list( "toll, motorway,toll" ) );
+        assert!( Avoid::parse_list( "toll,highway" ).is_err() );
+        assert!( Avoid::ALL.iter().all( |a| Avoid::from_name( &a.to_string() ) == Some(*a) ) );
+
+        let a63 = tags( &[("highway", "motorway_link"), ("toll", "yes"), ("bridge", "viaduct")] );
+        assert!( Avoid::Motorway.matches( &a63 ) && Avoid::Toll.matches( &a63 ) && Avoid::Bridge.matches( &a63 ) );
+        assert!( !Avoid::Tunnel.matches( &a63 ) && !Avoid::Unpaved.matches( &a63 ) );
+        assert!( Avoid::Unpaved.matches( &tags( &[("highway", "track"), ("tracktype", "grade4")] ) ) );
+        assert!( Avoid::Ferry.matches( &tags( &[("route", "ferry")] ) ) );
+        assert!( !Avoid::Tunnel.matches( &tags( &[("tunnel", "no")] ) ) );
+    }
+}
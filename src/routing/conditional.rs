@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, NaiveDateTime, Timelike};
+
+use crate::graphe::Graph;
+use crate::graphe::elements::Edge;
+use crate::routing::profile::Profile;
+
+
+const DAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+///
+/// une règle d'horaires : mois, jours de la semaine et plages horaires (minutes depuis minuit),
+/// chaque sélecteur absent valant « toujours »
+///
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    months: Option<Vec<bool>>,
+    days: Option<Vec<bool>>,
+    times: Option<Vec<(u32, u32)>>,
+    off: bool,
+}
+
+impl Rule {
+    fn parse( s: &str ) -> Option<Rule> {
+        let mut rule = Rule { months: None, days: None, times: None, off: false };
+        // "Mo-Fr 07:00-09:00,16:00-18:00" : les listes ne contiennent pas d'espace
+        for token in s.split_whitespace() {
+            if token == "24/7" { continue; }
+            if token == "off" || token == "closed" { rule.off = true; continue; }
+            if token.contains( ':' ) {
+                rule.times = Some( token.split( ',' ).map( times ).collect::<Option<Vec<_>>>()? );
+            } else if MONTHS.iter().any( |m| token.starts_with( m ) ) {
+                rule.months = Some( selection( token, &MONTHS )? );
+            } else {
+                // PH (jours fériés) et SH (vacances scolaires) ne sont pas connus : jamais sélectionnés
+                let token: String = token.split( ',' ).filter( |d| *d != "PH" && *d != "SH" ).collect::<Vec<_>>().join( "," );
+                rule.days = Some( if token.is_empty() { vec![false; 7] } else { selection( &token, &DAYS )? } );
+            }
+        }
+        Some(rule)
+    }
+
+    // la règle concerne-t-elle ce jour (mois et jour de la semaine)
+    fn day( &self, month: usize, weekday: usize ) -> bool {
+        self.months.as_ref().map( |m| m[month] ).unwrap_or( true ) && self.days.as_ref().map( |d| d[weekday] ).unwrap_or( true )
+    }
+}
+
+// "Mo-Fr,Su" ou "Jan-Mar" : les éléments sélectionnés dans la liste des noms, les intervalles pouvant
+// passer la fin de la liste (Fr-Mo, Nov-Feb)
+fn selection( s: &str, names: &[&str] ) -> Option<Vec<bool>> {
+    let index = |n: &str| names.iter().position( |x| *x == n );
+    let mut selected = vec![false; names.len()];
+    for part in s.split( ',' ) {
+        let (first, last) = match part.split_once( '-' ) {
+            Some((a, b)) => ( index( a )?, index( b )? ),
+            None => ( index( part )?, index( part )? ),
+        };
+        let mut i = first;
+        loop {
+            selected[i] = true;
+            if i == last { break; }
+            i = (i + 1) % names.len();
+        }
+    }
+    Some(selected)
+}
+
+// "07:30-09:00" en minutes depuis minuit, la fin pouvant valoir 24:00 ou précéder le début (nuit)
+fn times( s: &str ) -> Option<(u32, u32)> {
+    let minutes = |hm: &str| -> Option<u32> {
+        let (h, m) = hm.split_once( ':' )?;
+        let ( h, m ) = ( h.parse::<u32>().ok()?, m.parse::<u32>().ok()? );
+        if h > 24 || m > 59 { return None; }
+        Some( h * 60 + m )
+    };
+    let (start, end) = s.split_once( '-' )?;
+    Some( ( minutes( start )?, minutes( end )? ) )
+}
+
+
+///
+/// horaires au format opening_hours (sous-ensemble : mois, jours, plages horaires, off, 24/7).
+/// Les règles séparées par ';' sont lues dans l'ordre, la dernière qui concerne le jour l'emporte.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningHours {
+    rules: Vec<Rule>,
+}
+
+impl OpeningHours {
+    ///
+    /// None si la valeur ne décrit pas un horaire (wet, weight>7.5, ...)
+    ///
+    pub fn parse( s: &str ) -> Option<OpeningHours> {
+        let rules = s.split( ';' )
+            .map( |r| r.trim() )
+            .filter( |r| !r.is_empty() )
+            .map( Rule::parse )
+            .collect::<Option<Vec<Rule>>>()?;
+        if rules.is_empty() { None } else { Some( OpeningHours { rules } ) }
+    }
+
+    ///
+    /// l'horaire est-il en vigueur à cet instant
+    ///
+    pub fn is_open( &self, at: NaiveDateTime ) -> bool {
+        let minute = at.hour() * 60 + at.minute();
+        let month = at.month0() as usize;
+        let weekday = at.weekday().num_days_from_monday() as usize;
+        // la veille, pour les plages qui passent minuit
+        let before = at - Duration::days( 1 );
+        let ( month_before, weekday_before ) = ( before.month0() as usize, before.weekday().num_days_from_monday() as usize );
+
+        let mut open = false;
+        for rule in self.rules.iter() {
+            let today = rule.day( month, weekday );
+            let yesterday = rule.day( month_before, weekday_before );
+            let inside = match &rule.times {
+                None => today,
+                Some(ranges) => ranges.iter().any( |(start, end)| if start < end {
+                    today && *start <= minute && minute < *end
+                } else {
+                    ( today && *start <= minute ) || ( yesterday && minute < *end )
+                } ),
+            };
+            // une règle remplace les précédentes pour les jours qu'elle sélectionne
+            if inside { open = !rule.off; } else if today { open = false; }
+        }
+        open
+    }
+}
+
+
+///
+/// une restriction conditionnelle : valeur @ (condition) ; les conditions reliées par AND
+/// doivent toutes être remplies, seules les conditions horaires sont connues
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conditional {
+    pub value: String,
+    hours: Vec<Option<OpeningHours>>,
+}
+
+impl Conditional {
+    ///
+    /// "no @ (Mo-Fr 07:00-09:00); destination @ (Sa 08:00-12:00)"
+    ///
+    pub fn parse( s: &str ) -> Vec<Conditional> {
+        split_outside( s, ';' ).iter()
+            .filter_map( |part| part.split_once( '@' ) )
+            .map( |(value, condition)| {
+                let condition = condition.trim();
+                let condition = condition.strip_prefix( '(' ).and_then( |c| c.strip_suffix( ')' ) ).unwrap_or( condition );
+                Conditional {
+                    value: value.trim().to_string(),
+                    hours: condition.split( " AND " ).map( |c| OpeningHours::parse( c.trim() ) ).collect(),
+                }
+            } )
+            .collect()
+    }
+
+    ///
+    /// la condition est-elle remplie à cet instant (jamais si elle porte sur autre chose que l'heure)
+    ///
+    pub fn applies( &self, at: NaiveDateTime ) -> bool {
+        self.hours.iter().all( |h| h.as_ref().map( |h| h.is_open( at ) ).unwrap_or( false ) )
+    }
+}
+
+// découpage sur le séparateur hors des parenthèses
+fn split_outside( s: &str, separator: char ) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let ( mut depth, mut start ) = ( 0, 0 );
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => { parts.push( &s[start..i] ); start = i + 1; },
+            _ => {},
+        }
+    }
+    parts.push( &s[start..] );
+    parts
+}
+
+
+///
+/// la voie porte-t-elle des tags conditionnels (access:conditional, maxspeed:conditional, ...)
+///
+pub fn has_conditional( tags: &HashMap<String, String> ) -> bool {
+    tags.keys().any( |k| k.ends_with( ":conditional" ) )
+}
+
+///
+/// tags en vigueur à cet instant : chaque clé:conditional remplace la clé si une de ses conditions
+/// est remplie (la dernière condition remplie l'emporte)
+///
+pub fn resolve( tags: &HashMap<String, String>, at: NaiveDateTime ) -> HashMap<String, String> {
+    let mut resolved = tags.clone();
+    for (k, v) in tags.iter() {
+        let Some(key) = k.strip_suffix( ":conditional" ) else { continue };
+        if let Some(c) = Conditional::parse( v ).into_iter().rev().find( |c| c.applies( at ) ) {
+            resolved.insert( key.to_string(), c.value );
+        }
+    }
+    resolved
+}
+
+
+///
+/// les voies à restrictions conditionnelles d'un profil : leurs arcs (cf. Graph::get_conditional_for)
+/// sont réévalués à l'heure de passage
+///
+#[derive(Debug, Clone)]
+pub struct Conditions {
+    profile: Profile,
+    ways: HashSet<i64>,
+    // noeuds des voies ouvertes au profil (cf. Graph::open_nodes)
+    open: HashSet<i64>,
+}
+
+impl Conditions {
+    pub fn new( g: &Graph, profile: Profile ) -> Self {
+        let ways = g.tways.iter().filter( |(_, w)| has_conditional( w.tags() ) ).map( |(k, _)| *k ).collect();
+        Self { profile, ways, open: g.open_nodes( &profile ) }
+    }
+
+    pub fn is_empty( &self ) -> bool {
+        self.ways.is_empty()
+    }
+
+    ///
+    /// l'arc from -> e.node à l'instant donné : None si la voie est alors fermée (accès, sens unique),
+    /// sinon l'arc avec le temps et le coût de la restriction en vigueur (cf. Graph::edge)
+    ///
+    pub fn edge_at( &self, g: &Graph, from: i64, e: &Edge, at: NaiveDateTime ) -> Option<Edge> {
+        if !self.ways.contains( &e.way ) { return Some( e.clone() ); }
+        let w = g.tways.get( &e.way )?;
+        let tags = resolve( w.tags(), at );
+        let usage = self.profile.way( &tags )?;
+        let forward = w.refs().windows( 2 ).any( |s| s[0] == from && s[1] == e.node );
+        if !forward && self.profile.oneway( &tags ) { return None; }
+
+        // même calcul que pour le graphe orienté, avec les tags en vigueur
+        g.edge( &self.profile, &self.open, e.way, &tags, usage, (from, e.node) )
+    }
+}
+
+
+#[cfg(test)]
+mod conditional_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+    use chrono::NaiveDate;
+
+    fn at( day: u32, h: u32, m: u32 ) -> NaiveDateTime {
+        // octobre 2026 : le 19 est un lundi
+        NaiveDate::from_ymd_opt( 2026, 10, day ).unwrap().and_hms_opt( h, m, 0 ).unwrap()
+    }
+
+    #[test]
+    fn opening_hours() {
+        let school = OpeningHours::parse( "Mo-Fr 07:30-09:00,16:00-17:30" ).unwrap();
+        assert!( school.is_open( at( 19, 8, 0 ) ) );
+        assert!( !school.is_open( at( 19, 9, 0 ) ) );
+        assert!( school.is_open( at( 23, 17, 0 ) ) );
+        assert!( !school.is_open( at( 24, 8, 0 ) ) );
+
+        // marché le samedi matin, sauf en hiver ; nuit à cheval sur minuit
+        let market = OpeningHours::parse( "Sa 06:00-13:00; Dec-Feb off" ).unwrap();
+        assert!( market.is_open( at( 24, 7, 0 ) ) );
+        assert!( !OpeningHours::parse( "Sa 06:00-13:00; Oct off" ).unwrap().is_open( at( 24, 7, 0 ) ) );
+        let night = OpeningHours::parse( "Fr-Sa 22:00-06:00" ).unwrap();
+        assert!( night.is_open( at( 24, 23, 0 ) ) && night.is_open( at( 25, 5, 0 ) ) );
+        assert!( !night.is_open( at( 26, 5, 0 ) ) && !night.is_open( at( 22, 23, 0 ) ) );
+        assert!( OpeningHours::parse( "24/7" ).unwrap().is_open( at( 20, 3, 0 ) ) );
+        assert!( OpeningHours::parse( "wet" ).is_none() );
+    }
+
+    #[test]
+    fn conditional_tags() {
+        let c = Conditional::parse( "no @ (Mo-Fr 07:00-09:00); destination @ (weight>3.5 AND Sa 08:00-12:00)" );
+        assert_eq!( 2, c.len() );
+        assert_eq!( "no", c[0].value );
+        assert!( c[0].applies( at( 19, 8, 0 ) ) );
+        assert!( !c[1].applies( at( 24, 9, 0 ) ) );
+
+        let mut tags = HashMap::new();
+        tags.insert( "maxspeed".to_string(), "50".to_string() );
+        tags.insert( "maxspeed:conditional".to_string(), "30 @ (Mo-Fr 07:00-19:00); 20 @ (Mo-Fr 08:00-09:00)".to_string() );
+        assert!( has_conditional( &tags ) );
+        assert_eq!( "20", resolve( &tags, at( 19, 8, 30 ) )["maxspeed"] );
+        assert_eq!( "30", resolve( &tags, at( 19, 10, 0 ) )["maxspeed"] );
+        assert_eq!( "50", resolve( &tags, at( 25, 10, 0 ) )["maxspeed"] );
+    }
+
+    #[test]
+    fn edges_as_directed() {
+        use crate::routing::profile::RESTRICTED;
+
+        let night = ("motor_vehicle:conditional", "no @ (22:00-06:00)");
+        // bac de 30 minutes, puis deux voies privées qui se suivent
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.0, -1.98), (4, 48.0, -1.97), (5, 48.0, -1.96)],
+                            &[(10, &[1, 2], &[("highway", "residential")]),
+                              (11, &[2, 3], &[("route", "ferry"), ("duration", "00:30"), night]),
+                              (12, &[3, 4], &[("highway", "service"), ("access", "private"), night]),
+                              (13, &[4, 5], &[("highway", "service"), ("access", "private"), night])] );
+
+        // hors restriction, les arcs réévalués sont ceux du graphe orienté
+        let (adj, c) = ( g.get_directed_for( &Profile::Car ), Conditions::new( &g, Profile::Car ) );
+        for (from, to) in [(2, 3), (3, 4), (4, 5)] {
+            let e = adj[&from].iter().find( |e| e.node == to ).unwrap();
+            let now = c.edge_at( &g, from, e, at( 19, 12, 0 ) ).unwrap();
+            assert!( ( now.time - e.time ).abs() < 1e-9 && ( now.cost - e.cost ).abs() < 1e-9 && now.penalty == e.penalty );
+        }
+        let edge = |from: i64, to: i64, h: u32| c.edge_at( &g, from, adj[&from].iter().find( |e| e.node == to ).unwrap(), at( 19, h, 0 ) );
+        // durée du bac, et zone privée qui commence au noeud 3, pas entre les deux voies privées
+        assert!( ( edge( 2, 3, 12 ).unwrap().time - 1800.0 ).abs() < 1e-6 );
+        assert_eq!( (RESTRICTED, 0.0), ( edge( 3, 4, 12 ).unwrap().penalty, edge( 4, 5, 12 ).unwrap().penalty ) );
+        assert!( edge( 2, 3, 23 ).is_none() );
+    }
+}
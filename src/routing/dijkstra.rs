@@ -1,5 +1,6 @@
 use std::cmp::Ordering;
 use std::collections::{HashMap,BTreeMap,BinaryHeap};
+use crate::engine::Metric;
 use crate::graphe::elements::Edge;
 
 #[derive(Copy, Clone)]
@@ -86,21 +87,20 @@ pub fn simple_shortest_path(adj_list: &HashMap<i64, Vec<Edge>>, start: i64, goal
 
                     // If so, add it to the frontier and continue
                     match dist.get_mut( &next.position ) {
-                        Some(n) => {
-                            if next.cost < *n {
-                                heap.push(next);
-                                // Relaxation, we have now found a better way
-                                *n = next.cost;
-                            }
+                        Some(n) if next.cost < *n => {
+                            heap.push(next);
+                            // Relaxation, we have now found a better way
+                            *n = next.cost;
                         },
-                        None => {
-                            println!("{:?}", edge );
-                        }
+                        // no better way, or edge towards a node outside the graph : skipped
+                        _ => {}
                     }
                 }
             }
         },
-        None => { println!( "start node must be in the graph" ); },
+        None => {
+            // the start node must be in the graph : no path
+        },
     }
     // Goal not reachable
     None
@@ -130,6 +130,22 @@ impl Seg {
 }
 
 
+// search mode : "time", "cost" (distance weighted by the profile) or distance
+fn metric(mode: &str) -> Metric {
+    Metric::from_name( mode ).unwrap_or( Metric::Distance )
+}
+
+// routing weight of an edge : its value for the mode plus its penalty (see Metric::weight)
+fn weight(mode: &str, edge: &Edge) -> f64 {
+    metric( mode ).weight( edge )
+}
+
+// real value of an edge for the mode, without its penalty
+fn value(mode: &str, edge: &Edge) -> f64 {
+    metric( mode ).of( edge )
+}
+
+
 // Dijkstra's shortest path algorithm.
 // with path return
 pub fn shortest_path(mode: &str, adj_list: &HashMap<i64, Vec<Edge>>, start: i64, goal: i64) -> Option<BTreeMap<i64,i64>> {
@@ -178,33 +194,125 @@ pub fn shortest_path(mode: &str, adj_list: &HashMap<i64, Vec<Edge>>, start: i64,
                 // For each node we can reach, see if we can find a way with
                 // a lower cost going through this node
                 for edge in adj_list.get( &position ).unwrap() {
-                    let c = if mode == "time" { edge.time } else { edge.distance };
+                    let c = weight( mode, edge );
                     let next = State { cost: cost + c, position: edge.node };
 
                     // If so, add it to the frontier and continue
                     match dist.get_mut( &next.position ) {
-                        Some(n) => {
-                            if next.cost < n.dist() {
-                                heap.push(next);
-                                // Relaxation, we have now found a better way
-                                n.set_dist( next.cost );
-                                n.set_old( position )
-                            }
+                        Some(n) if next.cost < n.dist() => {
+                            heap.push(next);
+                            // Relaxation, we have now found a better way
+                            n.set_dist( next.cost );
+                            n.set_old( position )
                         },
-                        None => {
-                            println!("{:?}", edge );
-                        }
+                        // no better way, or edge towards a node outside the graph : skipped
+                        _ => {}
                     }
                 }
             }
         },
-        None => { println!( "start node must be in the graph" ); },
+        None => {
+            // the start node must be in the graph : no path
+        },
     }
     // Goal not reachable
     None
 }
 
 
+// Dijkstra's algorithm without goal.
+// returns, for every node reachable from `start` with a weight (penalties included) lower than `limit`,
+// the value following `mode` (without the penalties) and the distance of the associated path
+pub fn one_to_all(mode: &str, adj_list: &HashMap<i64, Vec<Edge>>, start: i64, limit: f64) -> HashMap<i64,(f64,f64)> {
+    // dist[node] = (weight, value, distance) of the best path, the weight including the penalties
+    let mut dist: HashMap<i64,(f64,f64,f64)> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+
+    dist.insert( start, (0.0, 0.0, 0.0) );
+    heap.push( State { cost: 0.0, position: start } );
+
+    while let Some( State { cost, position } ) = heap.pop() {
+        let (best, v, d) = dist[&position];
+        // Important as we may have already found a better way
+        if cost > best { continue; }
+
+        if let Some(edges) = adj_list.get( &position ) {
+            for edge in edges {
+                let c = weight( mode, edge );
+                let next = State { cost: cost + c, position: edge.node };
+                if next.cost > limit { continue; }
+                let better = match dist.get( &next.position ) {
+                    Some((n, _, _)) => next.cost < *n,
+                    None => true,
+                };
+                if better {
+                    dist.insert( next.position, (next.cost, v + value( mode, edge ), d + edge.distance) );
+                    heap.push( next );
+                }
+            }
+        }
+    }
+    dist.into_iter().map( |(n, (_, v, d))| (n, (v, d)) ).collect()
+}
+
+
+
+// Dijkstra's algorithm on a time-dependent graph.
+// `at( from, edge, elapsed )` gives the edge as it is when reached `elapsed` seconds
+// after departure (time and cost updated), None if it can't be used at that time.
+// returns the edges of the path from `start` to `goal`, in order
+pub fn shortest_path_at<F>(mode: &str, adj_list: &HashMap<i64, Vec<Edge>>, start: i64, goal: i64, at: F) -> Option<Vec<Edge>>
+where F: Fn(i64, &Edge, f64) -> Option<Edge> {
+    shortest_path_with( mode, adj_list, start, goal, at, |_| 1.0 )
+}
+
+
+// same as shortest_path_at, the weight of each edge being multiplied by `factor( edge )`
+// (penalty on the edges to avoid) ; the edges returned keep their own values
+pub fn shortest_path_with<F, P>(mode: &str, adj_list: &HashMap<i64, Vec<Edge>>, start: i64, goal: i64, at: F, factor: P) -> Option<Vec<Edge>>
+where F: Fn(i64, &Edge, f64) -> Option<Edge>, P: Fn(&Edge) -> f64 {
+    // best[node] = (cost, elapsed time, previous node, edge used to reach the node)
+    let mut best: HashMap<i64,(f64, f64, i64, Option<Edge>)> = HashMap::new();
+    let mut heap = BinaryHeap::new();
+
+    if !adj_list.contains_key( &start ) { return None; }
+    best.insert( start, (0.0, 0.0, start, None) );
+    heap.push( State { cost: 0.0, position: start } );
+
+    while let Some( State { cost, position } ) = heap.pop() {
+        if position == goal {
+            let mut edges = Vec::new();
+            let mut cur = goal;
+            while let (_, _, previous, Some(edge)) = &best[&cur] {
+                edges.push( edge.clone() );
+                cur = *previous;
+            }
+            edges.reverse();
+            return Some(edges);
+        }
+        let (c, elapsed, _, _) = best[&position];
+        // Important as we may have already found a better way
+        if cost > c { continue; }
+
+        if let Some(edges) = adj_list.get( &position ) {
+            for edge in edges {
+                let Some(e) = at( position, edge, elapsed ) else { continue };
+                let next = State { cost: cost + weight( mode, &e ) * factor( &e ), position: e.node };
+                let better = match best.get( &next.position ) {
+                    Some((n, _, _, _)) => next.cost < *n,
+                    None => true,
+                };
+                if better {
+                    best.insert( next.position, (next.cost, elapsed + e.time, position, Some(e)) );
+                    heap.push( next );
+                }
+            }
+        }
+    }
+    // Goal not reachable
+    None
+}
+
 #[cfg(test)]
 mod dijkstra_tests {
     use super::*;
@@ -237,17 +345,17 @@ mod dijkstra_tests {
 
         let mut graph: HashMap<i64,Vec<Edge>> = HashMap::new();
         graph.insert(0,
-                vec![Edge { node: 2, cost: 10.0 },
-                     Edge { node: 1, cost: 1.0 }] );
+                vec![Edge { node: 2, distance: 10.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 },
+                     Edge { node: 1, distance: 1.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 }] );
         graph.insert(1,
-                vec![Edge { node: 3, cost: 2.0 }] );
+                vec![Edge { node: 3, distance: 2.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 }] );
         graph.insert(2,
-                vec![Edge { node: 1, cost: 1.0 },
-                     Edge { node: 3, cost: 3.0 },
-                     Edge { node: 4, cost: 1.0 }] );
+                vec![Edge { node: 1, distance: 1.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 },
+                     Edge { node: 3, distance: 3.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 },
+                     Edge { node: 4, distance: 1.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 }] );
         graph.insert(3,
-                vec![Edge { node: 0, cost: 7.0 },
-                     Edge { node: 4, cost: 2.0 }] );
+                vec![Edge { node: 0, distance: 7.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 },
+                     Edge { node: 4, distance: 2.0, time: 0.0, cost: 0.0, penalty: 0.0, way: 0 }] );
         graph.insert(4,
                 vec![] );
 
@@ -258,4 +366,50 @@ mod dijkstra_tests {
         assert_eq!(simple_shortest_path(&graph, 5, 2), None);
         assert_eq!(simple_shortest_path(&graph, 4, 0), None);
     }
+
+    #[test]
+    fn test_one_to_all() {
+        let mut graph: HashMap<i64,Vec<Edge>> = HashMap::new();
+        graph.insert(0, vec![Edge { node: 1, distance: 1.0, time: 5.0, cost: 0.0, penalty: 0.0, way: 0 },
+                             Edge { node: 2, distance: 4.0, time: 1.0, cost: 0.0, penalty: 0.0, way: 0 }] );
+        graph.insert(1, vec![Edge { node: 2, distance: 1.0, time: 5.0, cost: 0.0, penalty: 0.0, way: 0 }] );
+
+        let d = one_to_all( "distance", &graph, 0, f64::INFINITY );
+        assert_eq!( Some(&(2.0, 2.0)), d.get( &2 ) );
+        assert_eq!( None, one_to_all( "distance", &graph, 0, 1.5 ).get( &2 ) );
+        let t = one_to_all( "time", &graph, 0, f64::INFINITY );
+        assert_eq!( Some(&(1.0, 4.0)), t.get( &2 ) );
+        assert_eq!( None, t.get( &3 ) );
+
+        // the penalty discards the direct edge but is not part of the returned time
+        graph.get_mut( &0 ).unwrap()[1].penalty = 600.0;
+        let t = one_to_all( "time", &graph, 0, f64::INFINITY );
+        assert_eq!( Some(&(10.0, 2.0)), t.get( &2 ) );
+        let d = one_to_all( "distance", &graph, 0, f64::INFINITY );
+        assert_eq!( Some(&(2.0, 2.0)), d.get( &2 ) );
+    }
+
+    #[test]
+    fn test_shortest_path_at() {
+        let mut graph: HashMap<i64,Vec<Edge>> = HashMap::new();
+        graph.insert(0, vec![Edge { node: 1, distance: 1.0, time: 10.0, cost: 0.0, penalty: 0.0, way: 0 },
+                             Edge { node: 2, distance: 1.0, time: 30.0, cost: 0.0, penalty: 0.0, way: 1 }] );
+        graph.insert(1, vec![Edge { node: 2, distance: 1.0, time: 10.0, cost: 0.0, penalty: 0.0, way: 2 }] );
+        graph.insert(2, vec![] );
+
+        let nodes = |edges: Vec<Edge>| edges.iter().map( |e| e.node ).collect::<Vec<i64>>();
+        let always = |_: i64, e: &Edge, _: f64| Some( e.clone() );
+        assert_eq!( Some(vec![1, 2]), shortest_path_at( "time", &graph, 0, 2, always ).map( nodes ) );
+        // la voie 2 ferme 5 s après le départ : elle est atteinte trop tard
+        let closing = |_: i64, e: &Edge, elapsed: f64| if e.way == 2 && elapsed > 5.0 { None } else { Some( e.clone() ) };
+        assert_eq!( Some(vec![2]), shortest_path_at( "time", &graph, 0, 2, closing ).map( nodes ) );
+        assert_eq!( Some(vec![]), shortest_path_at( "time", &graph, 0, 0, always ).map( nodes ) );
+        assert!( shortest_path_at( "time", &graph, 2, 0, always ).is_none() );
+        // en distance, la voie 1 directe est la plus courte, sauf pénalisée d'un facteur 10
+        assert_eq!( Some(vec![2]), shortest_path_with( "distance", &graph, 0, 2, always, |_| 1.0 ).map( nodes ) );
+        let avoid = |e: &Edge| if e.way == 1 { 10.0 } else { 1.0 };
+        let edges = shortest_path_with( "distance", &graph, 0, 2, always, avoid ).unwrap();
+        assert_eq!( vec![1, 2], nodes( edges.clone() ) );
+        assert_eq!( 2.0, edges.iter().map( |e| e.distance ).sum::<f64>() );
+    }
 }
@@ -1,13 +1,35 @@
+use std::fmt;
+
+
+// ellipsoïde WGS84 : demi-grand axe (m) et aplatissement
+const WGS84_A: f64 = 6378137.0;
+const WGS84_F: f64 = 1.0 / 298.257223563;
+
+// rayon moyen (m) de la Terre sphérique
+const EARTH_RADIUS: f64 = 6371000.0;
+
+// itérations de la formule de Vincenty avant de renoncer (points presque antipodaux)
+const VINCENTY_ITERATIONS: usize = 200;
 
 ///
-/// calcul la distance entre 2 jeu de coordonnées lat, lon en utilisant pythagore
+/// calcul la distance entre 2 jeu de coordonnées lat, lon en utilisant pythagore : une minute
+/// d'arc vaut un mille marin, les écarts de longitude sont ramenés à la latitude moyenne
 ///
 pub fn distance_pythagore( xa: f64, ya: f64, xb: f64, yb:f64 ) -> f64 {
-    let x = ( xb - xa )*( ( ya + yb )/2.0 ).cos();
-    let y = yb - ya;
+    let x = ( yb - ya )*( ( ( xa + xb )/2.0 ).to_radians() ).cos();
+    let y = xb - xa;
     let z = ( x.powi(2) + y.powi(2) ).sqrt();
-    let d = 1852.0 * 60.0 * z;
-    d
+    1852.0 * 60.0 * z
+}
+
+///
+/// approximation équirectangulaire sur la sphère de rayon moyen : rapide, pour les boucles sur
+/// de nombreux points et les distances courtes (erreur inférieure au mètre sous 10 km en France)
+///
+pub fn distance_equirectangular( lat1: f64, lon1: f64, lat2: f64, lon2: f64 ) -> f64 {
+    let x = ( lon2 - lon1 ).to_radians() * ( ( lat1 + lat2 ) / 2.0 ).to_radians().cos();
+    let y = ( lat2 - lat1 ).to_radians();
+    EARTH_RADIUS * ( x * x + y * y ).sqrt()
 }
 
 ///
@@ -46,6 +68,125 @@ pub fn distance_haversine( xd: f64, yd: f64, zd: f64, td:f64 ) -> f64 {
     d
 }
 
+///
+/// géodésique sur l'ellipsoïde WGS84 (formule inverse de Vincenty) : distance (m) et cap initial
+/// (en degrés, 0 = nord, sens horaire) ; précise au millimètre. Pour des points presque antipodaux,
+/// où la formule ne converge pas, la distance et le cap sur la sphère sont renvoyés
+///
+pub fn geodesic( lat1: f64, lon1: f64, lat2: f64, lon2: f64 ) -> (f64, f64) {
+    let b = WGS84_A * ( 1.0 - WGS84_F );
+    let l = ( lon2 - lon1 ).to_radians();
+    // latitudes réduites
+    let u1 = ( ( 1.0 - WGS84_F ) * lat1.to_radians().tan() ).atan();
+    let u2 = ( ( 1.0 - WGS84_F ) * lat2.to_radians().tan() ).atan();
+    let ( sin_u1, cos_u1, sin_u2, cos_u2 ) = ( u1.sin(), u1.cos(), u2.sin(), u2.cos() );
+
+    let mut lambda = l;
+    for _ in 0..VINCENTY_ITERATIONS {
+        let ( sin_l, cos_l ) = ( lambda.sin(), lambda.cos() );
+        let sin_sigma = ( ( cos_u2 * sin_l ).powi(2) + ( cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_l ).powi(2) ).sqrt();
+        // points confondus
+        if sin_sigma == 0.0 { return (0.0, 0.0); }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_l;
+        let sigma = sin_sigma.atan2( cos_sigma );
+        let sin_alpha = cos_u1 * cos_u2 * sin_l / sin_sigma;
+        let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+        // sur l'équateur cos2_alpha est nul
+        let cos_2sm = if cos2_alpha != 0.0 { cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha } else { 0.0 };
+        let c = WGS84_F / 16.0 * cos2_alpha * ( 4.0 + WGS84_F * ( 4.0 - 3.0 * cos2_alpha ) );
+        let previous = lambda;
+        lambda = l + ( 1.0 - c ) * WGS84_F * sin_alpha
+            * ( sigma + c * sin_sigma * ( cos_2sm + c * cos_sigma * ( -1.0 + 2.0 * cos_2sm * cos_2sm ) ) );
+        if ( lambda - previous ).abs() > 1e-12 { continue; }
+
+        let u = cos2_alpha * ( WGS84_A * WGS84_A - b * b ) / ( b * b );
+        let aa = 1.0 + u / 16384.0 * ( 4096.0 + u * ( -768.0 + u * ( 320.0 - 175.0 * u ) ) );
+        let bb = u / 1024.0 * ( 256.0 + u * ( -128.0 + u * ( 74.0 - 47.0 * u ) ) );
+        let delta_sigma = bb * sin_sigma * ( cos_2sm + bb / 4.0 * ( cos_sigma * ( -1.0 + 2.0 * cos_2sm * cos_2sm )
+            - bb / 6.0 * cos_2sm * ( -3.0 + 4.0 * sin_sigma * sin_sigma ) * ( -3.0 + 4.0 * cos_2sm * cos_2sm ) ) );
+        let d = b * aa * ( sigma - delta_sigma );
+        let ( sin_l, cos_l ) = ( lambda.sin(), lambda.cos() );
+        let azimuth = ( cos_u2 * sin_l ).atan2( cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_l );
+        return ( d, ( azimuth.to_degrees() + 360.0 ) % 360.0 );
+    }
+    ( distance_haversine( lat1, lon1, lat2, lon2 ), bearing( lat1, lon1, lat2, lon2 ) )
+}
+
+///
+/// distance (m) sur l'ellipsoïde WGS84, cf. geodesic
+///
+pub fn distance_vincenty( lat1: f64, lon1: f64, lat2: f64, lon2: f64 ) -> f64 {
+    geodesic( lat1, lon1, lat2, lon2 ).0
+}
+
+///
+/// formule de distance utilisée pour la longueur des arcs du graphe et la recherche du point le plus
+/// proche : haversine (sphère, par défaut), Vincenty (ellipsoïde WGS84) ou équirectangulaire (rapide)
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DistanceMetric {
+    #[default]
+    Haversine,
+    Vincenty,
+    Equirectangular,
+}
+
+impl DistanceMetric {
+    pub fn from_name( s: &str ) -> Option<DistanceMetric> {
+        match s {
+            "haversine" => Some(DistanceMetric::Haversine),
+            "vincenty" | "ellipsoid" | "wgs84" => Some(DistanceMetric::Vincenty),
+            "equirectangular" | "fast" => Some(DistanceMetric::Equirectangular),
+            _ => None,
+        }
+    }
+
+    ///
+    /// distance (m) entre (lat1, lon1) et (lat2, lon2)
+    ///
+    pub fn distance( &self, lat1: f64, lon1: f64, lat2: f64, lon2: f64 ) -> f64 {
+        match self {
+            DistanceMetric::Haversine => distance_haversine( lat1, lon1, lat2, lon2 ),
+            DistanceMetric::Vincenty => distance_vincenty( lat1, lon1, lat2, lon2 ),
+            DistanceMetric::Equirectangular => distance_equirectangular( lat1, lon1, lat2, lon2 ),
+        }
+    }
+}
+
+impl fmt::Display for DistanceMetric {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            DistanceMetric::Haversine => write!( f, "haversine" ),
+            DistanceMetric::Vincenty => write!( f, "vincenty" ),
+            DistanceMetric::Equirectangular => write!( f, "equirectangular" ),
+        }
+    }
+}
+
+///
+/// calcul du point atteint depuis (lat, lon) en parcourant d mètres suivant le cap donné (en degrés)
+///
+pub fn destination( lat: f64, lon: f64, bearing: f64, d: f64 ) -> (f64, f64) {
+    let x = lat.to_radians();
+    let y = lon.to_radians();
+    let b = bearing.to_radians();
+    let a = d / ( 1000.0 * 6371.0 );
+
+    let lat2 = ( x.sin()*a.cos() + x.cos()*a.sin()*b.cos() ).asin();
+    let lon2 = y + ( b.sin()*a.sin()*x.cos() ).atan2( a.cos() - x.sin()*lat2.sin() );
+    (lat2.to_degrees(), lon2.to_degrees())
+}
+
+///
+/// cap initial (en degrés, 0 = nord, sens horaire) pour aller de (lat1, lon1) à (lat2, lon2)
+///
+pub fn bearing( lat1: f64, lon1: f64, lat2: f64, lon2: f64 ) -> f64 {
+    let (x, z) = ( lat1.to_radians(), lat2.to_radians() );
+    let dl = ( lon2 - lon1 ).to_radians();
+    let b = ( dl.sin()*z.cos() ).atan2( x.cos()*z.sin() - x.sin()*z.cos()*dl.cos() );
+    ( b.to_degrees() + 360.0 ) % 360.0
+}
+
 
 #[cfg(test)]
 mod distance_tests {
@@ -54,8 +195,45 @@ mod distance_tests {
 
     #[test]
     fn test_pythagore() {
-        assert_eq!( 111120.0 , distance_pythagore( 51.0, 2.0, 51.0, 3.0 ) );
-        assert_eq!( 111120.0 , distance_pythagore( 51.0, 3.0, 51.0, 2.0 ) );
+        assert_eq!( 111120.0 , distance_pythagore( 68.0, 51.0, 69.0, 51.0 ) );
+        // un degré de longitude à 51° de latitude
+        assert!( ( 111120.0 * 51f64.to_radians().cos() - distance_pythagore( 51.0, 2.0, 51.0, 3.0 ) ).abs() < 1e-6 );
+        assert!( ( distance_pythagore( 51.0, 3.0, 51.0, 2.0 ) - distance_pythagore( 51.0, 2.0, 51.0, 3.0 ) ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn test_equirectangular() {
+        let d = distance_haversine( 48.40639, -2.81508, 48.41, -2.80 );
+        assert!( ( d - distance_equirectangular( 48.40639, -2.81508, 48.41, -2.80 ) ).abs() < 1e-3 );
+        let d = distance_haversine( 48.4, -2.8, 48.45, -2.7 );
+        assert!( ( d - distance_equirectangular( 48.4, -2.8, 48.45, -2.7 ) ).abs() < 0.1 );
+    }
+
+    #[test]
+    fn test_vincenty() {
+        // Flinders Peak -> Buninyong (Vincenty, 1975) : 54 972,271 m, cap 306°52'05.37"
+        let dms = |d: f64, m: f64, s: f64| d.signum() * ( d.abs() + m / 60.0 + s / 3600.0 );
+        let (d, b) = geodesic( dms( -37.0, 57.0, 3.72030 ), dms( 144.0, 25.0, 29.52440 ), dms( -37.0, 39.0, 10.15610 ), dms( 143.0, 55.0, 35.38390 ) );
+        assert!( ( d - 54972.271 ).abs() < 1e-3 );
+        assert!( ( b - dms( 306.0, 52.0, 5.37 ) ).abs() < 1e-5 );
+        // JFK -> LHR (GeographicLib) : 5 551 759,400 m, cap 51,198883°
+        let (d, b) = geodesic( 40.6, -73.8, 51.6, -0.5 );
+        assert!( ( d - 5551759.400 ).abs() < 1e-3 );
+        assert!( ( b - 51.198883 ).abs() < 1e-6 );
+        // sur l'équateur, un degré de longitude vaut a * pi / 180
+        assert!( ( distance_vincenty( 0.0, 0.0, 0.0, 1.0 ) - 111319.490793 ).abs() < 1e-5 );
+        assert_eq!( (0.0, 0.0), geodesic( 48.4, -2.8, 48.4, -2.8 ) );
+        // points antipodaux : la sphère
+        assert_eq!( distance_haversine( 0.0, 0.0, 0.5, 179.7 ), distance_vincenty( 0.0, 0.0, 0.5, 179.7 ) );
+    }
+
+    #[test]
+    fn test_metric() {
+        assert_eq!( Some(DistanceMetric::Vincenty), DistanceMetric::from_name( "vincenty" ) );
+        assert_eq!( None, DistanceMetric::from_name( "manhattan" ) );
+        assert_eq!( DistanceMetric::Haversine, DistanceMetric::default() );
+        assert_eq!( "equirectangular", DistanceMetric::Equirectangular.to_string() );
+        assert_eq!( distance_vincenty( 48.0, -2.0, 48.1, -2.1 ), DistanceMetric::Vincenty.distance( 48.0, -2.0, 48.1, -2.1 ) );
     }
 
     #[test]
@@ -69,4 +247,21 @@ mod distance_tests {
         assert_eq!( 111194.9266445603 , distance_haversine( 68.0, 51.0, 69.0, 51.0 ) );
         assert_eq!( 111194.9266445603 , distance_haversine( 69.0, 51.0, 68.0, 51.0 ) );
     }
+
+    #[test]
+    fn test_destination() {
+        let (lat, lon) = destination( 48.40639, -2.81508, 90.0, 1000.0 );
+        assert!( ( 1000.0 - distance_haversine( 48.40639, -2.81508, lat, lon ) ).abs() < 1e-6 );
+        let (lat, lon) = destination( 68.0, 51.0, 0.0, 111194.9266445603 );
+        assert!( ( lat - 69.0 ).abs() < 1e-9 );
+        assert!( ( lon - 51.0 ).abs() < 1e-9 );
+    }
+
+    #[test]
+    fn test_bearing() {
+        assert!( ( bearing( 48.0, -2.0, 48.1, -2.0 ) - 0.0 ).abs() < 1e-9 );
+        assert!( ( bearing( 48.0, -2.0, 48.0, -1.9 ) - 90.0 ).abs() < 0.1 );
+        assert!( ( bearing( 48.0, -2.0, 47.9, -2.0 ) - 180.0 ).abs() < 1e-9 );
+        assert!( ( bearing( 48.0, -2.0, 48.0, -2.1 ) - 270.0 ).abs() < 0.1 );
+    }
 }
@@ -0,0 +1,344 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use std::thread;
+
+use reqwest::blocking::Client;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+pub mod nominatim;
+pub mod photon;
+pub mod local;
+
+
+#[derive(Debug)]
+pub enum GeoError {
+    Http(reqwest::Error),
+    Status(u16),
+    Json(serde_json::Error),
+    Malformed(String),
+    Io(std::io::Error),
+    Config(String),
+}
+
+impl fmt::Display for GeoError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            GeoError::Http(e) => write!( f, "requête impossible : {}", e ),
+            GeoError::Status(s) => write!( f, "réponse du service en erreur : HTTP {}", s ),
+            GeoError::Json(e) => write!( f, "réponse illisible : {}", e ),
+            GeoError::Malformed(s) => write!( f, "réponse incomplète : {}", s ),
+            GeoError::Io(e) => write!( f, "cache inaccessible : {}", e ),
+            GeoError::Config(s) => write!( f, "géocodeur inconnu : {}", s ),
+        }
+    }
+}
+
+impl From<reqwest::Error> for GeoError {
+    fn from(e: reqwest::Error) -> Self {
+        GeoError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for GeoError {
+    fn from(e: serde_json::Error) -> Self {
+        GeoError::Json(e)
+    }
+}
+
+impl From<std::io::Error> for GeoError {
+    fn from(e: std::io::Error) -> Self {
+        GeoError::Io(e)
+    }
+}
+
+
+///
+/// un résultat de géocodage
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hit {
+    pub lat: f64,
+    pub lon: f64,
+    pub label: String,
+    /// objet OSM d'origine : "node", "way" ou "relation"
+    pub osm_type: Option<String>,
+    pub osm_id: Option<i64>,
+    /// pertinence (géocodeur local uniquement)
+    pub score: Option<f64>,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{} ({} , {})", self.label, self.lat, self.lon )
+    }
+}
+
+
+///
+/// recherche d'une adresse : service en ligne ou index local
+///
+pub trait Geocoder {
+    ///
+    /// nom du géocodeur
+    ///
+    fn name(&self) -> &str;
+
+    ///
+    /// résultats pour l'adresse donnée, du plus pertinent au moins pertinent
+    ///
+    fn search( &mut self, query: &str ) -> Result<Vec<Hit>, GeoError>;
+}
+
+
+///
+/// géocodeurs essayés dans l'ordre : le premier qui trouve quelque chose répond
+///
+pub struct Chain<'a> {
+    geocoders: Vec<Box<dyn Geocoder + 'a>>,
+}
+
+impl<'a> Chain<'a> {
+    pub fn new( geocoders: Vec<Box<dyn Geocoder + 'a>> ) -> Self {
+        Self { geocoders }
+    }
+}
+
+impl Geocoder for Chain<'_> {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    fn search( &mut self, query: &str ) -> Result<Vec<Hit>, GeoError> {
+        let mut error = None;
+        for g in self.geocoders.iter_mut() {
+            match g.search( query ) {
+                Ok(hits) if !hits.is_empty() => { return Ok(hits); },
+                Ok(_) => {},
+                Err(e) => { error = Some(e); },
+            }
+        }
+        match error {
+            Some(e) => Err(e),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+
+///
+/// cache sur disque (JSON) requête -> résultats, partagé par les services en ligne
+///
+#[derive(Debug, Default)]
+pub struct Cache {
+    path: Option<PathBuf>,
+    entries: HashMap<String, Vec<Hit>>,
+}
+
+impl Cache {
+    ///
+    /// ouverture du cache, vide si le fichier n'existe pas encore
+    ///
+    pub fn open( path: &Path ) -> Result<Self, GeoError> {
+        let entries = if path.exists() { serde_json::from_reader( File::open( path )? )? } else { HashMap::new() };
+        Ok( Self { path: Some( path.to_path_buf() ), entries } )
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get( &self, key: &str ) -> Option<&Vec<Hit>> {
+        self.entries.get( key )
+    }
+
+    ///
+    /// ajout d'une entrée, aussitôt enregistrée sur disque
+    ///
+    pub fn insert( &mut self, key: &str, hits: Vec<Hit> ) -> Result<(), GeoError> {
+        self.entries.insert( key.to_string(), hits );
+        if let Some(path) = &self.path {
+            serde_json::to_writer( File::create( path )? , &self.entries )?;
+        }
+        Ok(())
+    }
+}
+
+
+///
+/// accès HTTP commun aux services en ligne : user agent, délai minimal entre deux requêtes et cache
+///
+pub struct Remote {
+    pub endpoint: String,
+    client: Client,
+    interval: Duration,
+    last: Option<Instant>,
+    cache: Option<Cache>,
+}
+
+impl Remote {
+    pub fn new( endpoint: &str, user_agent: &str ) -> Result<Self, GeoError> {
+        let client = Client::builder().user_agent( user_agent ).timeout( Duration::from_secs( 20 ) ).build()?;
+        Ok( Self {
+            endpoint: endpoint.trim_end_matches( '/' ).to_string(),
+            client,
+            interval: Duration::from_secs( 1 ),
+            last: None,
+            cache: None,
+        } )
+    }
+
+    pub fn with_interval( mut self, interval: Duration ) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn with_cache( mut self, cache: Cache ) -> Self {
+        self.cache = Some( cache );
+        self
+    }
+
+    ///
+    /// résultats en cache, sinon requête GET {endpoint}{path}?params décodée par `parse`
+    ///
+    pub fn fetch( &mut self, path: &str, params: &[(&str, &str)], parse: fn( Value ) -> Result<Vec<Hit>, GeoError> ) -> Result<Vec<Hit>, GeoError> {
+        let url = format!( "{}{}", self.endpoint, path );
+        let key = format!( "{} {:?}", url, params );
+        if let Some(hits) = self.cache.as_ref().and_then( |c| c.get( &key ) ) { return Ok( hits.clone() ); }
+
+        // limitation du débit (1 requête par seconde pour nominatim.openstreetmap.org)
+        if let Some(last) = self.last {
+            let elapsed = last.elapsed();
+            if elapsed < self.interval { thread::sleep( self.interval - elapsed ); }
+        }
+        self.last = Some( Instant::now() );
+
+        let res = self.client.get( &url ).query( params ).send()?;
+        if !res.status().is_success() { return Err( GeoError::Status( res.status().as_u16() ) ); }
+        let hits = parse( serde_json::from_str( &res.text()? )? )?;
+        if let Some(c) = self.cache.as_mut() { c.insert( &key, hits.clone() )?; }
+        Ok(hits)
+    }
+}
+
+
+///
+/// géocodeur en ligne d'après son nom ("nominatim" ou "photon"), à l'adresse donnée ou celle par défaut
+///
+pub fn remote( name: &str, endpoint: Option<&str>, cache: Option<&Path> ) -> Result<Box<dyn Geocoder>, GeoError> {
+    let cache = match cache { Some(p) => Some( Cache::open( p )? ), None => None };
+    match name {
+        "nominatim" => Ok( Box::new( nominatim::Nominatim::new( endpoint.unwrap_or( nominatim::ENDPOINT ), cache )? ) ),
+        "photon" => Ok( Box::new( photon::Photon::new( endpoint.unwrap_or( photon::ENDPOINT ), cache )? ) ),
+        _ => Err( GeoError::Config( name.to_string() ) ),
+    }
+}
+
+
+///
+/// serveur HTTP de test : répond les réponses données dans l'ordre, compte les requêtes
+///
+#[cfg(test)]
+pub mod stub {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub fn serve( responses: Vec<(u16, &'static str)> ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind( "127.0.0.1:0" ).unwrap();
+        let url = format!( "http://{}", listener.local_addr().unwrap() );
+        let count = Arc::new( AtomicUsize::new( 0 ) );
+        let counter = count.clone();
+        std::thread::spawn( move || {
+            for (status, body) in responses {
+                let Ok((mut stream, _)) = listener.accept() else { return };
+                let mut buf = [0u8; 4096];
+                let _ = stream.read( &mut buf );
+                counter.fetch_add( 1, Ordering::SeqCst );
+                let _ = write!( stream, "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body );
+            }
+        } );
+        (url, count)
+    }
+}
+
+
+#[cfg(test)]
+mod geocoder_tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    struct Fixed(Vec<Hit>);
+
+    impl Geocoder for Fixed {
+        fn name(&self) -> &str { "fixed" }
+        fn search( &mut self, _query: &str ) -> Result<Vec<Hit>, GeoError> { Ok( self.0.clone() ) }
+    }
+
+    struct Failing;
+
+    impl Geocoder for Failing {
+        fn name(&self) -> &str { "failing" }
+        fn search( &mut self, _query: &str ) -> Result<Vec<Hit>, GeoError> { Err( GeoError::Status( 503 ) ) }
+    }
+
+    fn hit( label: &str ) -> Hit {
+        Hit { lat: 48.0, lon: -2.0, label: label.to_string(), osm_type: None, osm_id: None, score: None }
+    }
+
+    #[test]
+    fn chain_fallback() {
+        let mut c = Chain::new( vec![Box::new( Fixed( Vec::new() ) ), Box::new( Failing ), Box::new( Fixed( vec![hit( "b" )] ) )] );
+        assert_eq!( "b", c.search( "x" ).unwrap()[0].label );
+        let mut c = Chain::new( vec![Box::new( Fixed( Vec::new() ) ), Box::new( Failing )] );
+        assert!( matches!( c.search( "x" ), Err(GeoError::Status(503)) ) );
+        assert!( matches!( remote( "google", None, None ), Err(GeoError::Config(_)) ) );
+    }
+
+    #[test]
+    fn persistent_cache() {
+        let path = std::env::temp_dir().join( format!( "route_geocoder_cache_{}.json", std::process::id() ) );
+        let _ = std::fs::remove_file( &path );
+        let mut c = Cache::open( &path ).unwrap();
+        assert!( c.is_empty() );
+        c.insert( "rue", vec![hit( "a" )] ).unwrap();
+
+        let c = Cache::open( &path ).unwrap();
+        assert_eq!( 1, c.len() );
+        assert_eq!( "a", c.get( "rue" ).unwrap()[0].label );
+
+        std::fs::write( &path, "{ pas du json" ).unwrap();
+        assert!( matches!( Cache::open( &path ), Err(GeoError::Json(_)) ) );
+        std::fs::remove_file( &path ).unwrap();
+    }
+
+    #[test]
+    fn remote_cache_and_rate_limit() {
+        fn parse( v: Value ) -> Result<Vec<Hit>, GeoError> {
+            let label = v["label"].as_str().ok_or( GeoError::Malformed( "label".to_string() ) )?;
+            Ok( vec![hit( label )] )
+        }
+        let (url, count) = stub::serve( vec![(200, r#"{"label":"a"}"#), (200, r#"{"label":"b"}"#), (500, "")] );
+        let mut r = Remote::new( &url, "route-test" ).unwrap()
+            .with_interval( Duration::from_millis( 200 ) )
+            .with_cache( Cache::default() );
+
+        let start = Instant::now();
+        assert_eq!( "a", r.fetch( "/search", &[("q", "rue")], parse ).unwrap()[0].label );
+        // même requête : servie par le cache
+        assert_eq!( "a", r.fetch( "/search", &[("q", "rue")], parse ).unwrap()[0].label );
+        assert_eq!( 1, count.load( Ordering::SeqCst ) );
+        assert_eq!( "b", r.fetch( "/search", &[("q", "place")], parse ).unwrap()[0].label );
+        assert!( start.elapsed() >= Duration::from_millis( 200 ) );
+        assert!( matches!( r.fetch( "/search", &[("q", "x")], parse ), Err(GeoError::Status(500)) ) );
+    }
+}
@@ -0,0 +1,58 @@
+use super::*;
+
+use crate::graphe::address::AddressIndex;
+
+
+///
+/// géocodeur hors ligne sur l'index des adresses du graphe (cf. graphe::address)
+///
+pub struct Local<'a> {
+    index: &'a AddressIndex,
+    limit: usize,
+}
+
+impl<'a> Local<'a> {
+    pub fn new( index: &'a AddressIndex, limit: usize ) -> Self {
+        Self { index, limit }
+    }
+}
+
+impl Geocoder for Local<'_> {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn search( &mut self, query: &str ) -> Result<Vec<Hit>, GeoError> {
+        Ok( self.index.search( query, self.limit ).into_iter().map( |(score, p)| {
+            let (osm_type, osm_id) = match ( p.ways.first(), p.nodes.first() ) {
+                ( Some(w), _ ) => ( Some( "way".to_string() ), Some(*w) ),
+                ( None, Some(n) ) => ( Some( "node".to_string() ), Some(*n) ),
+                _ => ( None, None ),
+            };
+            Hit { lat: p.lat, lon: p.lon, label: p.label(), osm_type, osm_id, score: Some(score) }
+        } ).collect() )
+    }
+}
+
+
+#[cfg(test)]
+mod local_tests {
+    use super::*;
+
+    #[test]
+    fn search_index() {
+        let mut tags = HashMap::new();
+        tags.insert( "addr:housenumber".to_string(), "3".to_string() );
+        tags.insert( "addr:street".to_string(), "Rue Notre-Dame".to_string() );
+        tags.insert( "addr:city".to_string(), "Plaintel".to_string() );
+        let mut idx = AddressIndex::default();
+        idx.add_node( 7, &tags, 48.4067, -2.8145 );
+        idx.finish();
+
+        let mut l = Local::new( &idx, 5 );
+        let hits = l.search( "3 rue notre dame plaintel" ).unwrap();
+        assert_eq!( "3 Rue Notre-Dame, Plaintel", hits[0].label );
+        assert_eq!( Some(7), hits[0].osm_id );
+        assert!( l.search( "boulevard" ).unwrap().is_empty() );
+    }
+}
@@ -0,0 +1,85 @@
+use super::*;
+
+
+///
+/// service public, à remplacer par une instance auto-hébergée pour un usage soutenu
+///
+pub const ENDPOINT: &str = "https://nominatim.openstreetmap.org";
+const USER_AGENT: &str = "teepi_route";
+const LIMIT: &str = "5";
+
+
+///
+/// géocodeur Nominatim (API /search, format jsonv2)
+///
+pub struct Nominatim {
+    remote: Remote,
+}
+
+impl Nominatim {
+    pub fn new( endpoint: &str, cache: Option<Cache> ) -> Result<Self, GeoError> {
+        let mut remote = Remote::new( endpoint, USER_AGENT )?;
+        if let Some(c) = cache { remote = remote.with_cache( c ); }
+        Ok( Self { remote } )
+    }
+
+    ///
+    /// accès au client HTTP (délai entre requêtes, ...)
+    ///
+    pub fn remote(&mut self) -> &mut Remote {
+        &mut self.remote
+    }
+}
+
+impl Geocoder for Nominatim {
+    fn name(&self) -> &str {
+        "nominatim"
+    }
+
+    fn search( &mut self, query: &str ) -> Result<Vec<Hit>, GeoError> {
+        self.remote.fetch( "/search", &[("q", query), ("format", "jsonv2"), ("limit", LIMIT)], parse )
+    }
+}
+
+// coordonnée fournie sous forme de texte par Nominatim
+fn coord( v: &Value, k: &str ) -> Result<f64, GeoError> {
+    v[k].as_str().and_then( |s| s.parse::<f64>().ok() ).ok_or( GeoError::Malformed( k.to_string() ) )
+}
+
+fn parse( v: Value ) -> Result<Vec<Hit>, GeoError> {
+    let places = v.as_array().ok_or( GeoError::Malformed( "liste de résultats attendue".to_string() ) )?;
+    places.iter().map( |p| Ok( Hit {
+        lat: coord( p, "lat" )?,
+        lon: coord( p, "lon" )?,
+        label: p["display_name"].as_str().unwrap_or( "" ).to_string(),
+        osm_type: p["osm_type"].as_str().map( |s| s.to_string() ),
+        osm_id: p["osm_id"].as_i64(),
+        score: None,
+    } ) ).collect()
+}
+
+
+#[cfg(test)]
+mod nominatim_tests {
+    use super::*;
+    use crate::routing::geocoder::stub;
+
+    const ANSWER: &str = r#"[{"place_id":1,"osm_type":"way","osm_id":225806813,"lat":"48.4449","lon":"-2.8402",
+        "display_name":"Route des Noës, Plaine-Haute, 22800","category":"highway","type":"residential"}]"#;
+
+    #[test]
+    fn search_stub_server() {
+        let (url, _) = stub::serve( vec![(200, ANSWER), (200, r#"[{"lat":"48.4"}]"#), (200, "<html>")] );
+        let mut n = Nominatim::new( &url, None ).unwrap();
+        n.remote().interval = Duration::ZERO;
+
+        let hits = n.search( "22 route des noels 22800 plaine-haute" ).unwrap();
+        assert_eq!( 1, hits.len() );
+        assert_eq!( Some("way".to_string()), hits[0].osm_type );
+        assert_eq!( Some(225806813), hits[0].osm_id );
+        assert_eq!( 48.4449, hits[0].lat );
+
+        assert!( matches!( n.search( "a" ), Err(GeoError::Malformed(_)) ) );
+        assert!( matches!( n.search( "b" ), Err(GeoError::Json(_)) ) );
+    }
+}
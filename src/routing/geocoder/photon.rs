@@ -0,0 +1,87 @@
+use super::*;
+
+
+pub const ENDPOINT: &str = "https://photon.komoot.io";
+const USER_AGENT: &str = "teepi_route";
+const LIMIT: &str = "5";
+
+
+///
+/// géocodeur Photon (API /api, réponse GeoJSON)
+///
+pub struct Photon {
+    remote: Remote,
+}
+
+impl Photon {
+    pub fn new( endpoint: &str, cache: Option<Cache> ) -> Result<Self, GeoError> {
+        let mut remote = Remote::new( endpoint, USER_AGENT )?;
+        if let Some(c) = cache { remote = remote.with_cache( c ); }
+        Ok( Self { remote } )
+    }
+
+    ///
+    /// accès au client HTTP (délai entre requêtes, ...)
+    ///
+    pub fn remote(&mut self) -> &mut Remote {
+        &mut self.remote
+    }
+}
+
+impl Geocoder for Photon {
+    fn name(&self) -> &str {
+        "photon"
+    }
+
+    fn search( &mut self, query: &str ) -> Result<Vec<Hit>, GeoError> {
+        self.remote.fetch( "/api", &[("q", query), ("limit", LIMIT)], parse )
+    }
+}
+
+// libellé composé à partir des propriétés : "1 Rue de Brocéliande, 22000 Saint-Brieuc"
+fn label( p: &Value ) -> String {
+    let s = |k: &str| p[k].as_str().unwrap_or( "" ).to_string();
+    let street = [s( "housenumber" ), s( "street" )].join( " " ).trim().to_string();
+    let locality = [s( "postcode" ), s( "city" )].join( " " ).trim().to_string();
+    [s( "name" ), street, locality].into_iter().filter( |x| !x.is_empty() ).collect::<Vec<String>>().join( ", " )
+}
+
+fn parse( v: Value ) -> Result<Vec<Hit>, GeoError> {
+    let features = v["features"].as_array().ok_or( GeoError::Malformed( "features".to_string() ) )?;
+    features.iter().map( |f| {
+        let c = &f["geometry"]["coordinates"];
+        let (Some(lon), Some(lat)) = ( c[0].as_f64(), c[1].as_f64() ) else {
+            return Err( GeoError::Malformed( "coordinates".to_string() ) );
+        };
+        let p = &f["properties"];
+        let osm_type = p["osm_type"].as_str().map( |t| match t {
+            "N" => "node", "W" => "way", "R" => "relation", other => other,
+        }.to_string() );
+        Ok( Hit { lat, lon, label: label( p ), osm_type, osm_id: p["osm_id"].as_i64(), score: None } )
+    } ).collect()
+}
+
+
+#[cfg(test)]
+mod photon_tests {
+    use super::*;
+    use crate::routing::geocoder::stub;
+
+    const ANSWER: &str = r#"{"type":"FeatureCollection","features":[{"type":"Feature",
+        "geometry":{"type":"Point","coordinates":[-2.7699,48.5101]},
+        "properties":{"osm_type":"N","osm_id":42,"housenumber":"1 bis","street":"Rue de Brocéliande","postcode":"22000","city":"Saint-Brieuc"}}]}"#;
+
+    #[test]
+    fn search_stub_server() {
+        let (url, _) = stub::serve( vec![(200, ANSWER), (200, r#"{"features":[{"geometry":{}}]}"#)] );
+        let mut p = Photon::new( &url, None ).unwrap();
+        p.remote().interval = Duration::ZERO;
+
+        let hits = p.search( "1 bis rue de broceliande" ).unwrap();
+        assert_eq!( "1 bis Rue de Brocéliande, 22000 Saint-Brieuc", hits[0].label );
+        assert_eq!( Some("node".to_string()), hits[0].osm_type );
+        assert_eq!( (48.5101, -2.7699), (hits[0].lat, hits[0].lon) );
+
+        assert!( matches!( p.search( "x" ), Err(GeoError::Malformed(_)) ) );
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+use crate::graphe::Graph;
+use crate::routing::distances::bearing;
+use crate::routing::route::Route;
+
+
+///
+/// une étape de l'itinéraire : manœuvre au début de l'étape puis parcours d'une même voie
+/// (types et modificateurs repris de l'API OSRM)
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// "depart", "turn", "new name", "continue" ou "arrive"
+    pub maneuver: &'static str,
+    /// "straight", "slight right", "right", "sharp right", "uturn", ... (None au départ et à l'arrivée)
+    pub modifier: Option<&'static str>,
+    /// nom (ou référence) de la voie parcourue, vide si elle n'en a pas
+    pub name: String,
+    pub way: i64,
+    /// position (lat, lon) de la manœuvre
+    pub location: (f64, f64),
+    pub bearing_before: f64,
+    pub bearing_after: f64,
+    /// distance (m) et durée (s) de l'étape
+    pub distance: f64,
+    pub duration: f64,
+    /// indices dans Route::coords du début et de la fin de l'étape
+    pub from: usize,
+    pub to: usize,
+    /// l'étape est une traversée en bac
+    pub ferry: bool,
+}
+
+impl Instruction {
+    ///
+    /// libellé de la manœuvre
+    ///
+    pub fn text(&self) -> String {
+        let on = if self.name.is_empty() { String::new() } else { format!( " sur {}", self.name ) };
+        let direction = match self.modifier {
+            Some("uturn") => "Faire demi-tour",
+            Some("sharp left") => "Tourner franchement à gauche",
+            Some("left") => "Tourner à gauche",
+            Some("slight left") => "Tourner légèrement à gauche",
+            Some("slight right") => "Tourner légèrement à droite",
+            Some("right") => "Tourner à droite",
+            Some("sharp right") => "Tourner franchement à droite",
+            _ => "Continuer tout droit",
+        };
+        match self.maneuver {
+            "arrive" => "Arrivée".to_string(),
+            _ if self.ferry => format!( "Prendre le bac{}", on ),
+            "depart" => format!( "Partir{}", on ),
+            _ => format!( "{}{}", direction, on ),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        write!( f, "{} ({:.0} m)", self.text(), self.distance )
+    }
+}
+
+
+///
+/// modificateur OSRM d'après le changement de cap (degrés, positif vers la droite)
+///
+pub fn modifier( before: f64, after: f64 ) -> &'static str {
+    let delta = ( after - before + 540.0 ) % 360.0 - 180.0;
+    match delta {
+        d if d.abs() < 20.0 => "straight",
+        d if d.abs() > 170.0 => "uturn",
+        d if d >= 120.0 => "sharp right",
+        d if d >= 60.0 => "right",
+        d if d > 0.0 => "slight right",
+        d if d <= -120.0 => "sharp left",
+        d if d <= -60.0 => "left",
+        _ => "slight left",
+    }
+}
+
+///
+/// découpage de l'itinéraire en étapes : une nouvelle étape à chaque changement de nom de voie
+///
+pub fn instructions( g: &Graph, r: &Route ) -> Vec<Instruction> {
+    let n = r.edges.len().min( r.coords.len().saturating_sub( 1 ) );
+    let head = |i: usize| bearing( r.coords[i].0, r.coords[i].1, r.coords[i+1].0, r.coords[i+1].1 );
+    let name = |way: i64| Route::way_name( g, way ).unwrap_or_default();
+    let ferry = |way: i64| Route::is_ferry( g, way );
+    let mut steps: Vec<Instruction> = Vec::new();
+
+    for i in 0..n {
+        let e = &r.edges[i];
+        let after = head( i );
+        match steps.last_mut() {
+            // même voie : l'étape en cours se prolonge (une traversée en bac reste une étape à part)
+            Some(s) if s.way == e.way || ( !s.name.is_empty() && s.name == name( e.way ) && s.ferry == ferry( e.way ) ) => {
+                s.distance += e.distance;
+                s.duration += e.time;
+                s.to = i + 1;
+            },
+            last => {
+                let before = if last.is_some() { head( i - 1 ) } else { 0.0 };
+                let (maneuver, m) = match last {
+                    None => ( "depart", None ),
+                    Some(_) => {
+                        let m = modifier( before, after );
+                        ( if m == "straight" { "new name" } else { "turn" }, Some(m) )
+                    },
+                };
+                steps.push( Instruction {
+                    maneuver, modifier: m, name: name( e.way ), way: e.way, location: r.coords[i],
+                    bearing_before: before, bearing_after: after,
+                    distance: e.distance, duration: e.time, from: i, to: i + 1, ferry: ferry( e.way ),
+                } );
+            },
+        }
+    }
+
+    // arrivée, ou départ et arrivée confondus si l'itinéraire n'a pas d'arc
+    let Some(end) = r.coords.get( n ) else { return steps };
+    if steps.is_empty() {
+        steps.push( Instruction {
+            maneuver: "depart", modifier: None, name: String::new(), way: 0, location: *end,
+            bearing_before: 0.0, bearing_after: 0.0, distance: 0.0, duration: 0.0, from: n, to: n, ferry: false,
+        } );
+    }
+    let last = steps.last().unwrap();
+    let before = if n > 0 { head( n - 1 ) } else { 0.0 };
+    let (way, name) = ( last.way, last.name.clone() );
+    steps.push( Instruction {
+        maneuver: "arrive", modifier: None, name, way, location: *end,
+        bearing_before: before, bearing_after: 0.0, distance: 0.0, duration: 0.0, from: n, to: n, ferry: false,
+    } );
+    steps
+}
+
+
+#[cfg(test)]
+mod instructions_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    #[test]
+    fn test_modifier() {
+        assert_eq!( "straight", modifier( 350.0, 5.0 ) );
+        assert_eq!( "right", modifier( 0.0, 90.0 ) );
+        assert_eq!( "left", modifier( 90.0, 0.0 ) );
+        assert_eq!( "slight left", modifier( 10.0, 330.0 ) );
+        assert_eq!( "sharp right", modifier( 0.0, 150.0 ) );
+        assert_eq!( "uturn", modifier( 0.0, 178.0 ) );
+    }
+
+    #[test]
+    fn steps_on_l_shape() {
+        // vers l'est sur la rue A (deux ways), puis au nord sur la rue B
+        let rue = |name| [("highway", "residential"), ("name", name)];
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99), (4, 48.005, -1.99)],
+                            &[(10, &[1, 2], &rue( "Rue A" )), (11, &[2, 3], &rue( "Rue A" )), (12, &[3, 4], &rue( "Rue B" ))] );
+        let r = Route::from_nodes( &g, &g.get_directed(), vec![1, 2, 3, 4] );
+
+        let steps = instructions( &g, &r );
+        assert_eq!( vec!["depart", "turn", "arrive"], steps.iter().map( |s| s.maneuver ).collect::<Vec<&str>>() );
+        assert_eq!( "Rue A", steps[0].name );
+        assert_eq!( (0, 2), (steps[0].from, steps[0].to) );
+        assert!( ( steps[0].distance - g.tways[&10].len() - g.tways[&11].len() ).abs() < 1e-6 );
+        assert_eq!( Some("left"), steps[1].modifier );
+        assert_eq!( "Tourner à gauche sur Rue B", steps[1].text() );
+        assert_eq!( (48.0, -1.99), steps[1].location );
+        assert_eq!( (48.005, -1.99), steps[2].location );
+
+        let single = Route::from_nodes( &g, &g.get_directed(), vec![1] );
+        assert_eq!( vec!["depart", "arrive"], instructions( &g, &single ).iter().map( |s| s.maneuver ).collect::<Vec<&str>>() );
+    }
+}
@@ -1,97 +1,53 @@
-use std::fmt;
-
-use serde::Deserialize;
 use std::collections::HashMap;
 
-use reqwest::{Error,header::HeaderValue};
 use crate::graphe::elements::TNode;
-use crate::routing::distances::distance_haversine;
-
-
-#[derive(Deserialize, Debug)]
-pub struct Location {
-    pub place_id: i64,
-    pub licence: String,
-    pub osm_type: String,
-    pub osm_id: i64,
-    pub boundingbox: Vec<String>,
-    pub lat: String,
-    pub lon: String,
-    pub display_name: String,
-    pub class: String,
-    pub r#type:String,
-    pub importance: f64,
-}
-
-impl fmt::Display for Location {
-
-    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
-        write!( f, "{} ({},{}): {}", self.osm_id, self.lat, self.lon, self.display_name )
-    }
-
-}
-
-#[derive(Debug)]
-pub struct Address {
-    pub ad: String,
-}
-
-impl Address {
-
-    pub fn get_url_string(&self) -> String {
-        self.ad.replace( ' ', &"+" )
-    }
-}
-
-
-pub fn get_location_from_nominatim(addr: &Address) -> Result<Vec<Location>, Error> {
-    let hv = HeaderValue::from_str( "teepi_route").unwrap();
-    let client = reqwest::blocking::Client::builder()
-            .user_agent( hv )
-            .build()
-            .unwrap();
-    let s: String = addr.get_url_string();
-    let request_url = format!("https://nominatim.openstreetmap.org/search?q={}&format=json", s);
-    let res = client.get(&request_url).send()?;
-    let content = res.text()?;
-
-    let vloc: Vec<Location> = serde_json::from_str( content.as_str() ).unwrap();
-    Ok(vloc)
-}
+use crate::routing::distances::{DistanceMetric, distance_equirectangular};
 
 
 /// find the nearest node to the specified coordinates (lat, lon) and the associated distance
 pub fn nearest_node( lat: f64, lon: f64, nodes: &HashMap<i64, TNode> ) -> (i64, f64) {
+    nearest_node_with( lat, lon, nodes, DistanceMetric::Haversine )
+}
+
+/// find the nearest node, scanning the nodes with the equirectangular approximation ; the distance
+/// to the node found is given by the metric
+pub fn nearest_node_with( lat: f64, lon: f64, nodes: &HashMap<i64, TNode>, metric: DistanceMetric ) -> (i64, f64) {
     let mut min_value:f64 = 100000000000.0;
     let mut id: i64 = 0;
 
     for (pid, p) in nodes {
-        let dist = distance_haversine( lat, lon, p.lat(), p.lon() );
+        let dist = distance_equirectangular( lat, lon, p.lat(), p.lon() );
         if dist < min_value {
             min_value = dist;
             id = *pid;
         }
     }
-    (id, min_value)
+    match nodes.get( &id ) {
+        Some(p) => (id, metric.distance( lat, lon, p.lat(), p.lon() )),
+        None => (id, min_value),
+    }
 }
 
 #[cfg(test)]
 mod location_tests {
     use super::*;
-    use crate::read_osm;
+    use crate::graphe::reader::read_osm;
+    use crate::routing::geocoder::Geocoder;
+    use crate::routing::geocoder::nominatim::{Nominatim, ENDPOINT};
 
     #[test]
+    #[ignore = "service Nominatim en ligne (cf. graphe::address pour le géocodeur local)"]
     fn test_nominatim() {
-        let addr = vec!["22", "route", "des", "noels", "22800", "plaine-haute"];
-        let ad: Address =  Address { ad: addr.join( " " ) };
-        match get_location_from_nominatim( &ad ) {
+        let addr = ["22", "route", "des", "noels", "22800", "plaine-haute"];
+        let mut n = Nominatim::new( ENDPOINT, None ).unwrap();
+        match n.search( &addr.join( " " ) ) {
             Ok(lv) => {
                 assert_eq!( 1, lv.len() );
-                assert_eq!( lv[0].osm_type, "way".to_string() );
-                assert_eq!( lv[0].osm_id, 225806813 );
+                assert_eq!( lv[0].osm_type, Some( "way".to_string() ) );
+                assert_eq!( lv[0].osm_id, Some(225806813) );
             },
-            Err(_e) => {
-                assert!( false );
+            Err(e) => {
+                panic!( "nominatim error : {}", e );
             },
         }
 
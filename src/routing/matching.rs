@@ -0,0 +1,414 @@
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::collections::HashMap;
+
+use gpx::errors::GpxError;
+
+use crate::graphe::Graph;
+use crate::graphe::elements::Edge;
+use crate::graphe::index::{SpatialIndex, Projection, project};
+use crate::routing::dijkstra::{one_to_all, shortest_path_at};
+use crate::routing::distances::distance_haversine;
+use crate::routing::route::Route;
+
+// écart type du bruit gps en mètres (Newson & Krumm)
+const SIGMA: f64 = 4.07;
+// paramètre de la loi exponentielle des transitions en mètres
+const BETA: f64 = 5.0;
+// rayon de recherche des segments candidats en mètres
+const RADIUS: f64 = 50.0;
+// nombre maximum de segments candidats par point
+const MAX_CANDIDATES: usize = 8;
+// au delà de ce rapport entre distance routière et distance à vol d'oiseau la transition est impossible
+const MAX_DETOUR: f64 = 5.0;
+
+
+#[derive(Debug)]
+pub enum MatchError {
+    Io(std::io::Error),
+    Gpx(GpxError),
+    NoPoint,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            MatchError::Io(e) => write!( f, "lecture impossible : {}", e ),
+            MatchError::Gpx(e) => write!( f, "gpx invalide : {}", e ),
+            MatchError::NoPoint => write!( f, "aucun point dans la trace" ),
+        }
+    }
+}
+
+impl From<std::io::Error> for MatchError {
+    fn from(e: std::io::Error) -> Self {
+        MatchError::Io(e)
+    }
+}
+
+impl From<GpxError> for MatchError {
+    fn from(e: GpxError) -> Self {
+        MatchError::Gpx(e)
+    }
+}
+
+///
+/// lecture des points (lat, lon) des traces d'un fichier gpx
+///
+pub fn read_gpx_points( filename: &str ) -> Result<Vec<(f64, f64)>, MatchError> {
+    let data = gpx::read( BufReader::new( File::open( filename )? ) )?;
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for track in data.tracks.iter() {
+        for seg in track.segments.iter() {
+            for wp in seg.points.iter() {
+                points.push( ( wp.point().y(), wp.point().x() ) );
+            }
+        }
+    }
+    if points.is_empty() { return Err( MatchError::NoPoint ); }
+    Ok(points)
+}
+
+
+///
+/// un point de la trace et son rattachement au graphe
+///
+#[derive(Debug, Clone)]
+pub struct MatchedPoint {
+    pub lat: f64,
+    pub lon: f64,
+    /// projection retenue sur une voie, None si aucun segment n'est assez proche
+    pub matched: Option<Projection>,
+    /// probabilité a posteriori du rattachement retenu (0.0 .. 1.0)
+    pub confidence: f64,
+}
+
+///
+/// résultat de la mise en correspondance
+///
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    pub points: Vec<MatchedPoint>,
+    /// suite des ways empruntées
+    pub ways: Vec<i64>,
+    /// trace nettoyée : points projetés et noeuds du graphe entre eux
+    pub track: Vec<(f64, f64)>,
+}
+
+impl MatchResult {
+    ///
+    /// la trace nettoyée comme un itinéraire (coordonnées, ways et distance, sans arcs), pour
+    /// l'enregistrer comme les autres itinéraires
+    ///
+    pub fn route( &self ) -> Route {
+        let distance = self.track.windows( 2 ).map( |s| distance_haversine( s[0].0, s[0].1, s[1].0, s[1].1 ) ).sum();
+        Route { coords: self.track.clone(), ways: self.ways.clone(), distance, ..Default::default() }
+    }
+}
+
+
+// un candidat : projection sur un segment orienté (from -> to)
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    proj: Projection,
+    from: i64,
+    to: i64,
+    frac: f64,
+    len: f64,
+}
+
+///
+/// mise en correspondance de traces gps avec le graphe (modèle de Markov caché), sur le graphe
+/// orienté d'un profil et l'index des segments du graphe (cf. RoutingEngine::matcher)
+///
+pub struct Matcher<'a> {
+    g: &'a Graph,
+    adj: &'a HashMap<i64, Vec<Edge>>,
+    index: &'a SpatialIndex,
+}
+
+fn log_sum_exp( v: &[f64] ) -> f64 {
+    let m = v.iter().cloned().fold( f64::NEG_INFINITY, f64::max );
+    if m == f64::NEG_INFINITY { return m; }
+    m + v.iter().map( |x| (x - m).exp() ).sum::<f64>().ln()
+}
+
+impl<'a> Matcher<'a> {
+    pub fn new( g: &'a Graph, adj: &'a HashMap<i64, Vec<Edge>>, index: &'a SpatialIndex ) -> Self {
+        Self { g, adj, index }
+    }
+
+    fn edge( &self, from: i64, to: i64 ) -> Option<&Edge> {
+        self.adj.get( &from ).and_then( |edges| edges.iter().find( |e| e.node == to ) )
+    }
+
+    ///
+    /// segments orientés candidats autour d'un point (les deux sens si la voie est à double sens)
+    ///
+    fn candidates( &self, lat: f64, lon: f64 ) -> Vec<Candidate> {
+        let mut result = Vec::new();
+        for p in self.index.within( lat, lon, RADIUS ).into_iter().take( MAX_CANDIDATES ) {
+            let s = p.segment;
+            if let Some(e) = self.edge( s.from, s.to ) {
+                result.push( Candidate { proj: p, from: s.from, to: s.to, frac: p.frac, len: e.distance } );
+            }
+            if let Some(e) = self.edge( s.to, s.from ) {
+                result.push( Candidate { proj: p, from: s.to, to: s.from, frac: 1.0 - p.frac, len: e.distance } );
+            }
+        }
+        result
+    }
+
+    ///
+    /// distance routière d'un candidat à l'autre à partir des distances calculées depuis a.to
+    ///
+    fn route_distance( &self, a: &Candidate, b: &Candidate, net: &HashMap<i64, (f64, f64)> ) -> Option<f64> {
+        if a.from == b.from && a.to == b.to && b.frac >= a.frac {
+            return Some( (b.frac - a.frac) * a.len );
+        }
+        net.get( &b.from ).map( |(d, _)| (1.0 - a.frac) * a.len + d + b.frac * b.len )
+    }
+
+    ///
+    /// mise en correspondance d'une suite de points (lat, lon)
+    ///
+    pub fn match_points( &self, points: &[(f64, f64)] ) -> MatchResult {
+        let mut result: Vec<MatchedPoint> = points.iter()
+            .map( |(lat, lon)| MatchedPoint { lat: *lat, lon: *lon, matched: None, confidence: 0.0 } )
+            .collect();
+
+        // les points trop proches du précédent point retenu sont ignorés (bruit à l'arrêt)
+        let mut kept: Vec<usize> = Vec::new();
+        let mut cands: Vec<Vec<Candidate>> = Vec::new();
+        for (i, (lat, lon)) in points.iter().enumerate() {
+            if let Some(last) = kept.last() {
+                let (plat, plon) = points[*last];
+                if distance_haversine( plat, plon, *lat, *lon ) < 2.0 * SIGMA { continue; }
+            }
+            let c = self.candidates( *lat, *lon );
+            if c.is_empty() { continue; }
+            kept.push( i );
+            cands.push( c );
+        }
+
+        // treillis : émissions, transitions et découpage en chaînes indépendantes
+        let emission: Vec<Vec<f64>> = cands.iter()
+            .map( |cs| cs.iter().map( |c| -0.5 * (c.proj.distance / SIGMA).powi( 2 ) ).collect() )
+            .collect();
+        let mut transitions: Vec<Vec<Vec<f64>>> = vec![Vec::new()];
+        let mut chains: Vec<(usize, usize)> = Vec::new();
+        let mut chain_start = 0;
+        for t in 1..cands.len() {
+            let (plat, plon) = points[kept[t-1]];
+            let (lat, lon) = points[kept[t]];
+            let gc = distance_haversine( plat, plon, lat, lon );
+            let limit = MAX_DETOUR * gc + 2.0 * RADIUS;
+
+            let mut nets: HashMap<i64, HashMap<i64, (f64, f64)>> = HashMap::new();
+            let mut tr = vec![vec![f64::NEG_INFINITY; cands[t].len()]; cands[t-1].len()];
+            let mut reachable = false;
+            for (i, a) in cands[t-1].iter().enumerate() {
+                let net = nets.entry( a.to ).or_insert_with( || one_to_all( "distance", self.adj, a.to, limit ) );
+                for (j, b) in cands[t].iter().enumerate() {
+                    if let Some(d) = self.route_distance( a, b, net ) {
+                        if d <= limit {
+                            tr[i][j] = -(d - gc).abs() / BETA;
+                            reachable = true;
+                        }
+                    }
+                }
+            }
+            if !reachable {
+                chains.push( (chain_start, t) );
+                chain_start = t;
+            }
+            transitions.push( tr );
+        }
+        if !cands.is_empty() { chains.push( (chain_start, cands.len()) ); }
+
+        // viterbi et probabilités a posteriori (forward-backward) sur chaque chaîne
+        let mut chosen: Vec<usize> = vec![0; cands.len()];
+        let mut confidence: Vec<f64> = vec![0.0; cands.len()];
+        for (s, e) in chains.iter() {
+            let (s, e) = (*s, *e);
+            let mut score: Vec<Vec<f64>> = vec![emission[s].clone()];
+            let mut back: Vec<Vec<usize>> = vec![Vec::new()];
+            let mut alpha: Vec<Vec<f64>> = vec![emission[s].clone()];
+            for t in s+1..e {
+                let prev = score.last().unwrap();
+                let aprev = alpha.last().unwrap();
+                let mut sc = Vec::new();
+                let mut bk = Vec::new();
+                let mut al = Vec::new();
+                for j in 0..cands[t].len() {
+                    let mut best = (f64::NEG_INFINITY, 0);
+                    let mut terms = Vec::new();
+                    for i in 0..cands[t-1].len() {
+                        let v = prev[i] + transitions[t][i][j];
+                        if v > best.0 { best = (v, i); }
+                        terms.push( aprev[i] + transitions[t][i][j] );
+                    }
+                    sc.push( best.0 + emission[t][j] );
+                    bk.push( best.1 );
+                    al.push( log_sum_exp( &terms ) + emission[t][j] );
+                }
+                score.push( sc );
+                back.push( bk );
+                alpha.push( al );
+            }
+
+            let mut beta: Vec<Vec<f64>> = vec![vec![0.0; cands[e-1].len()]];
+            for t in (s+1..e).rev() {
+                let next = beta.last().unwrap();
+                let bt: Vec<f64> = (0..cands[t-1].len())
+                    .map( |i| {
+                        let terms: Vec<f64> = (0..cands[t].len())
+                            .map( |j| transitions[t][i][j] + emission[t][j] + next[j] )
+                            .collect();
+                        log_sum_exp( &terms )
+                    } )
+                    .collect();
+                beta.push( bt );
+            }
+            beta.reverse();
+
+            let z = log_sum_exp( alpha.last().unwrap() );
+            let last = score.last().unwrap();
+            let mut cur = (0..last.len()).fold( 0, |b, j| if last[j] > last[b] { j } else { b } );
+            for t in (s..e).rev() {
+                // confiance : probabilité d'être sur le segment retenu, quel que soit le sens
+                chosen[t] = cur;
+                let segment = cands[t][cur].proj.segment;
+                confidence[t] = (0..cands[t].len())
+                    .filter( |j| cands[t][*j].proj.segment == segment )
+                    .map( |j| ( alpha[t-s][j] + beta[t-s][j] - z ).exp() )
+                    .sum::<f64>()
+                    .min( 1.0 );
+                if t > s { cur = back[t-s][cur]; }
+            }
+        }
+
+        // résultat : projection retenue, points ignorés rattachés au segment du point précédent
+        let mut k = 0;
+        let mut last: Option<(Candidate, f64)> = None;
+        for (i, p) in result.iter_mut().enumerate() {
+            if k < kept.len() && kept[k] == i {
+                let c = cands[k][chosen[k]];
+                p.matched = Some( c.proj );
+                p.confidence = confidence[k];
+                last = Some( (c, confidence[k]) );
+                k += 1;
+            } else if let Some((c, conf)) = last {
+                let (Some(a), Some(b)) = ( self.g.tnodes.get( &c.proj.segment.from ), self.g.tnodes.get( &c.proj.segment.to ) ) else { continue };
+                let (lat, lon, frac, distance) = project( p.lat, p.lon, (a.lat(), a.lon()), (b.lat(), b.lon()) );
+                if distance <= RADIUS {
+                    p.matched = Some( Projection { segment: c.proj.segment, lat, lon, frac, distance } );
+                    p.confidence = conf;
+                }
+            }
+        }
+
+        // chemin complet entre candidats successifs d'une même chaîne
+        let mut ways: Vec<i64> = Vec::new();
+        let mut track: Vec<(f64, f64)> = Vec::new();
+        for (s, e) in chains.iter() {
+            for t in *s..*e {
+                let b = cands[t][chosen[t]];
+                if t > *s {
+                    let a = cands[t-1][chosen[t-1]];
+                    if !( a.from == b.from && a.to == b.to && b.frac >= a.frac ) {
+                        // les arêtes retenues par dijkstra, chaque noeud du chemin compris
+                        if let Some(edges) = shortest_path_at( "distance", self.adj, a.to, b.from, |_, e, _| Some( e.clone() ) ) {
+                            for n in std::iter::once( a.to ).chain( edges.iter().map( |e| e.node ) ) {
+                                if let Some(tn) = self.g.tnodes.get( &n ) { track.push( (tn.lat(), tn.lon()) ); }
+                            }
+                            for edge in edges.iter() {
+                                if ways.last() != Some(&edge.way) { ways.push( edge.way ); }
+                            }
+                        }
+                    }
+                }
+                if ways.last() != Some(&b.proj.segment.way) { ways.push( b.proj.segment.way ); }
+                track.push( (b.proj.lat, b.proj.lon) );
+            }
+        }
+
+        MatchResult { points: result, ways, track }
+    }
+}
+
+
+#[cfg(test)]
+mod matching_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+    use crate::routing::distances::destination;
+    use crate::routing::profile::Profile;
+
+    // une rue en L (way 1 : 1 -> 2 -> 3) et une impasse parallèle proche (way 2 : 4 -> 5)
+    fn streets() -> Graph {
+        let rue = &[("highway", "residential")];
+        test_graph( &[(1, 48.4, -2.8), (2, 48.4, -2.79), (3, 48.41, -2.79), (4, 48.4003, -2.7995), (5, 48.4003, -2.7905)],
+                    &[(1, &[1, 2, 3], rue), (2, &[4, 5], rue)] )
+    }
+
+    #[test]
+    fn match_l_shaped_trace() {
+        let g = streets();
+        let (adj, index) = ( g.get_directed_for( &Profile::Car ), SpatialIndex::new( &g ) );
+        let m = Matcher::new( &g, &adj, &index );
+
+        // trace bruitée le long de la rue 1, avec un décalage alterné de 8 m
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for k in 0..15 {
+            let (lat, lon) = destination( 48.4, -2.8, 90.0, 50.0 * k as f64 );
+            points.push( destination( lat, lon, 0.0, if k % 2 == 0 { 8.0 } else { 12.0 } ) );
+        }
+        for k in 1..10 {
+            let (lat, lon) = destination( 48.4, -2.79, 0.0, 80.0 * k as f64 );
+            points.push( destination( lat, lon, 270.0, 6.0 ) );
+        }
+        let r = m.match_points( &points );
+
+        assert_eq!( vec![1], r.ways );
+        assert_eq!( points.len(), r.points.len() );
+        assert!( r.points.iter().all( |p| p.matched.map( |m| m.segment.way ) == Some(1) ) );
+        assert!( r.points.iter().all( |p| p.confidence > 0.9 && p.confidence <= 1.0 ) );
+        assert!( r.track.len() >= points.len() );
+        // la trace corrigée s'enregistre comme un itinéraire
+        let route = r.route();
+        assert_eq!( r.track, route.coords );
+        assert_eq!( r.ways, route.ways );
+        assert!( route.distance > 0.0 && route.edges.is_empty() );
+    }
+
+    #[test]
+    fn close_nodes_kept() {
+        // le virage de la rue en L en deux noeuds distants de 4 mm : la trace passe par les deux
+        let g = test_graph( &[(1, 48.4, -2.8), (2, 48.4, -2.79), (6, 48.40000004, -2.79), (3, 48.41, -2.79)],
+                            &[(1, &[1, 2, 6, 3], &[("highway", "residential")])] );
+        let (adj, index) = ( g.get_directed_for( &Profile::Car ), SpatialIndex::new( &g ) );
+        let m = Matcher::new( &g, &adj, &index );
+        let points: Vec<(f64, f64)> = [(48.4, -2.8, 90.0, 400.0), (48.4, -2.79, 0.0, 400.0)].iter()
+            .map( |(lat, lon, bearing, d)| destination( *lat, *lon, *bearing, *d ) )
+            .collect();
+        let r = m.match_points( &points );
+
+        assert_eq!( vec![1], r.ways );
+        let corner = r.track.iter().position( |p| *p == (48.4, -2.79) ).unwrap();
+        assert_eq!( (48.40000004, -2.79), r.track[corner + 1] );
+    }
+
+    #[test]
+    fn point_far_from_roads() {
+        let g = streets();
+        let (adj, index) = ( g.get_directed_for( &Profile::Car ), SpatialIndex::new( &g ) );
+        let m = Matcher::new( &g, &adj, &index );
+        let r = m.match_points( &[(48.5, -2.5)] );
+
+        assert!( r.points[0].matched.is_none() );
+        assert_eq!( 0.0, r.points[0].confidence );
+        assert!( r.ways.is_empty() );
+    }
+}
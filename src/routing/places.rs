@@ -0,0 +1,157 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+
+#[derive(Debug)]
+pub enum PlaceError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// nom vide ou lisible comme un nombre, confondu avec un id ou une coordonnée
+    Name(String),
+    Unknown(String),
+}
+
+impl fmt::Display for PlaceError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            PlaceError::Io(e) => write!( f, "favoris inaccessibles : {}", e ),
+            PlaceError::Json(e) => write!( f, "favoris illisibles : {}", e ),
+            PlaceError::Name(s) => write!( f, "{} : nom de lieu invalide (ni un nombre ni de guillemets)", s ),
+            PlaceError::Unknown(s) => write!( f, "{} : lieu inconnu", s ),
+        }
+    }
+}
+
+impl From<std::io::Error> for PlaceError {
+    fn from(e: std::io::Error) -> Self {
+        PlaceError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PlaceError {
+    fn from(e: serde_json::Error) -> Self {
+        PlaceError::Json(e)
+    }
+}
+
+
+///
+/// lieu nommé : un noeud osm (avec ses coordonnées) ou une simple position
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<i64>,
+}
+
+impl fmt::Display for Place {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self.node {
+            Some(id) => write!( f, "noeud {} ({}, {})", id, self.lat, self.lon ),
+            None => write!( f, "{}, {}", self.lat, self.lon ),
+        }
+    }
+}
+
+
+///
+/// favoris : lieux nommés enregistrés sur disque (JSON), triés par nom
+///
+#[derive(Debug, Default)]
+pub struct Places {
+    path: Option<PathBuf>,
+    places: BTreeMap<String, Place>,
+}
+
+impl Places {
+    ///
+    /// ouverture des favoris, vides si le fichier n'existe pas encore
+    ///
+    pub fn open( path: &Path ) -> Result<Self, PlaceError> {
+        let places = if path.exists() { serde_json::from_reader( File::open( path )? )? } else { BTreeMap::new() };
+        Ok( Self { path: Some( path.to_path_buf() ), places } )
+    }
+
+    pub fn len(&self) -> usize {
+        self.places.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+    }
+
+    pub fn get( &self, name: &str ) -> Option<&Place> {
+        self.places.get( name )
+    }
+
+    pub fn iter( &self ) -> impl Iterator<Item = (&String, &Place)> {
+        self.places.iter()
+    }
+
+    ///
+    /// ajout ou remplacement d'un lieu, aussitôt enregistré sur disque
+    ///
+    pub fn insert( &mut self, name: &str, place: Place ) -> Result<(), PlaceError> {
+        if name.trim().is_empty() || name.parse::<f64>().is_ok() || name.contains( '"' ) {
+            return Err( PlaceError::Name( name.to_string() ) );
+        }
+        self.places.insert( name.to_string(), place );
+        self.write()
+    }
+
+    ///
+    /// suppression d'un lieu, aussitôt enregistrée sur disque
+    ///
+    pub fn remove( &mut self, name: &str ) -> Result<Place, PlaceError> {
+        let place = self.places.remove( name ).ok_or_else( || PlaceError::Unknown( name.to_string() ) )?;
+        self.write()?;
+        Ok( place )
+    }
+
+    fn write( &self ) -> Result<(), PlaceError> {
+        if let Some(path) = &self.path {
+            serde_json::to_writer_pretty( File::create( path )?, &self.places )?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod places_tests {
+    use super::*;
+
+    #[test]
+    fn persistent_places() {
+        let path = std::env::temp_dir().join( format!( "route_places_{}.json", std::process::id() ) );
+        let _ = std::fs::remove_file( &path );
+        let mut p = Places::open( &path ).unwrap();
+        assert!( p.is_empty() );
+        p.insert( "garage", Place { lat: 48.4063898, lon: -2.8150775, node: Some( 10748130358 ) } ).unwrap();
+        p.insert( "maryse", Place { lat: 48.40672, lon: -2.81433, node: None } ).unwrap();
+        assert!( matches!( p.insert( "12", Place { lat: 0.0, lon: 0.0, node: None } ), Err(PlaceError::Name(_)) ) );
+        assert!( matches!( p.insert( "-2.5", Place { lat: 0.0, lon: 0.0, node: None } ), Err(PlaceError::Name(_)) ) );
+        assert!( matches!( p.insert( "le \"bon\" coin", Place { lat: 0.0, lon: 0.0, node: None } ), Err(PlaceError::Name(_)) ) );
+
+        let mut p = Places::open( &path ).unwrap();
+        assert_eq!( 2, p.len() );
+        assert_eq!( Some( 10748130358 ), p.get( "garage" ).unwrap().node );
+        assert_eq!( "48.40672, -2.81433", p.get( "maryse" ).unwrap().to_string() );
+        let names: Vec<&String> = p.iter().map( |(n, _)| n ).collect();
+        assert_eq!( vec!["garage", "maryse"], names );
+
+        p.remove( "maryse" ).unwrap();
+        assert!( matches!( p.remove( "maryse" ), Err(PlaceError::Unknown(_)) ) );
+        assert_eq!( 1, Places::open( &path ).unwrap().len() );
+
+        std::fs::write( &path, "{ pas du json" ).unwrap();
+        assert!( matches!( Places::open( &path ), Err(PlaceError::Json(_)) ) );
+        std::fs::remove_file( &path ).unwrap();
+    }
+}
@@ -0,0 +1,402 @@
+use std::fmt;
+use std::collections::HashMap;
+
+
+// pénalité (s) pour entrer dans une zone à accès restreint (access=destination, private, ...) :
+// la zone n'est empruntée que pour y commencer ou y terminer le trajet
+pub const RESTRICTED: f64 = 600.0;
+
+// vitesse (km/h) d'un bac dont la way n'a pas de tag duration
+pub const FERRY_SPEED: f64 = 20.0;
+
+// valeurs de highway=* gardées à la lecture du fichier et connues des profils
+pub const ROUTABLE: [&str; 23] = [
+    "motorway", "trunk", "primary", "secondary", "tertiary", "unclassified",
+    "residential", "service", "motorway_link", "trunk_link", "primary_link",
+    "secondary_link", "tertiary_link", "motorway_junction",
+    // voies cyclables et piétonnes
+    "living_street", "road", "track", "path", "cycleway", "footway",
+    "pedestrian", "bridleway", "steps",
+];
+
+///
+/// la way est-elle une liaison par bac (route=ferry)
+///
+pub fn is_ferry( tags: &HashMap<String, String> ) -> bool {
+    tags.get( "route" ).is_some_and( |r| r == "ferry" )
+}
+
+///
+/// durée (s) de la traversée d'après le tag duration : "HH:MM", "HH:MM:SS", minutes ou ISO 8601 ("PT1H30M")
+///
+pub fn ferry_duration( tags: &HashMap<String, String> ) -> Option<f64> {
+    let v = tags.get( "duration" )?.trim();
+    let seconds = if let Some(iso) = v.strip_prefix( "PT" ) {
+        let (mut total, mut number) = ( 0.0, String::new() );
+        for c in iso.chars() {
+            match c {
+                'H' | 'M' | 'S' => {
+                    let n = number.parse::<f64>().ok()?;
+                    total += n * match c { 'H' => 3600.0, 'M' => 60.0, _ => 1.0 };
+                    number.clear();
+                },
+                _ => number.push( c ),
+            }
+        }
+        total
+    } else {
+        let parts = v.split( ':' ).map( |p| p.parse::<f64>().ok() ).collect::<Option<Vec<f64>>>()?;
+        match parts.as_slice() {
+            [m] => m * 60.0,
+            [h, m] => h * 3600.0 + m * 60.0,
+            [h, m, s] => h * 3600.0 + m * 60.0 + s,
+            _ => { return None; },
+        }
+    };
+    if seconds > 0.0 { Some(seconds) } else { None }
+}
+
+///
+/// droit de passage d'un profil sur une voie ou un noeud
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Yes,
+    /// seulement pour desservir la zone : destination, private, customers, delivery
+    Destination,
+    No,
+}
+
+impl Access {
+    ///
+    /// droit de passage d'après la valeur d'un tag access=*, motor_vehicle=*, ...
+    ///
+    pub fn from_value( v: &str ) -> Access {
+        match v {
+            "no" | "agricultural" | "forestry" | "use_sidepath" | "dismount" => Access::No,
+            "destination" | "private" | "customers" | "delivery" => Access::Destination,
+            _ => Access::Yes,
+        }
+    }
+}
+
+///
+/// mode de déplacement utilisé pour construire le graphe orienté
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Car,
+    Bicycle,
+    Foot,
+}
+
+impl Profile {
+    ///
+    /// profil à partir de son nom ("car", "bike", "foot", ...)
+    ///
+    pub fn from_name( s: &str ) -> Option<Profile> {
+        match s {
+            "car" | "driving" | "voiture" => Some(Profile::Car),
+            "bike" | "bicycle" | "cycling" | "velo" | "vélo" => Some(Profile::Bicycle),
+            "foot" | "walking" | "running" | "pied" => Some(Profile::Foot),
+            _ => None,
+        }
+    }
+
+    ///
+    /// vitesse (km/h) sur un type de voie, None si la voie n'est pas praticable avec ce profil
+    ///
+    pub fn speed( &self, highway: &str ) -> Option<f64> {
+        match self {
+            Profile::Car => match highway {
+                "motorway" => Some(130.0),
+                "trunk" | "primary" => Some(110.0),
+                "secondary" => Some(80.0),
+                "motorway_link" | "primary_link" | "trunk_link" => Some(70.0),
+                "road" | "tertiary_link" | "secondary_link" | "residential" |
+                "unclassified" | "tertiary" => Some(50.0),
+                "service" | "motorway_junction" => Some(30.0),
+                "living_street" => Some(20.0),
+                _ => None,
+            },
+            Profile::Bicycle => match highway {
+                "motorway" | "motorway_link" | "motorway_junction" | "trunk" | "trunk_link" |
+                "footway" | "pedestrian" | "steps" | "bridleway" => None,
+                "track" | "path" => Some(12.0),
+                _ => Some(18.0),
+            },
+            Profile::Foot => match highway {
+                "motorway" | "motorway_link" | "motorway_junction" | "trunk" | "trunk_link" => None,
+                "steps" => Some(2.0),
+                _ => Some(5.0),
+            },
+        }
+    }
+
+    ///
+    /// facteur appliqué à la distance pour favoriser les voies calmes (1.0 = voie préférée)
+    ///
+    pub fn preference( &self, highway: &str ) -> f64 {
+        match self {
+            Profile::Car => 1.0,
+            Profile::Bicycle => match highway {
+                "cycleway" | "track" | "path" | "living_street" | "residential" | "service" |
+                "unclassified" | "ferry" => 1.0,
+                "tertiary" | "tertiary_link" | "road" => 1.3,
+                "secondary" | "secondary_link" => 1.8,
+                _ => 2.5,
+            },
+            Profile::Foot => match highway {
+                "footway" | "pedestrian" | "path" | "track" | "steps" | "living_street" | "ferry" => 1.0,
+                "residential" | "service" | "unclassified" | "cycleway" | "bridleway" => 1.1,
+                "tertiary" | "tertiary_link" | "road" => 1.5,
+                _ => 2.5,
+            },
+        }
+    }
+
+    ///
+    /// facteur appliqué à la durée et au coût d'un segment suivant sa pente (dénivelé / distance) :
+    /// la montée ralentit le vélo, la descente le rend plus rapide ; le piéton suit la loi de Tobler
+    /// (vitesse maximale en légère descente)
+    ///
+    pub fn grade_factor( &self, grade: f64 ) -> f64 {
+        match self {
+            Profile::Car => 1.0,
+            Profile::Bicycle if grade > 0.0 => 1.0 + 10.0 * grade,
+            Profile::Bicycle => ( 1.0 + 2.5 * grade ).max( 0.75 ),
+            Profile::Foot => ( 3.5 * ( ( grade + 0.05 ).abs() - 0.05 ) ).exp(),
+        }
+    }
+
+    ///
+    /// la voie décrite par ces tags est-elle à sens unique pour ce profil
+    ///
+    pub fn oneway( &self, tags: &HashMap<String, String> ) -> bool {
+        let is_yes = |k: &str| tags.get( k ).map( |v| v == "yes" );
+        match self {
+            Profile::Car => is_yes( "oneway" ).unwrap_or( false ),
+            Profile::Bicycle => is_yes( "oneway:bicycle" ).or( is_yes( "oneway" ) ).unwrap_or( false ),
+            Profile::Foot => false,
+        }
+    }
+}
+
+impl Profile {
+    ///
+    /// tags d'accès propres au profil, du plus précis au plus général (avant access)
+    ///
+    pub fn access_keys( &self ) -> &'static [&'static str] {
+        match self {
+            Profile::Car => &["motorcar", "motor_vehicle", "vehicle"],
+            Profile::Bicycle => &["bicycle", "vehicle"],
+            Profile::Foot => &["foot"],
+        }
+    }
+
+    ///
+    /// droit de passage donné par les tags propres au profil (motorcar -> motor_vehicle -> vehicle,
+    /// bicycle -> vehicle, foot), None s'ils sont absents
+    ///
+    pub fn own_access( &self, tags: &HashMap<String, String> ) -> Option<Access> {
+        self.access_keys().iter().find_map( |k| tags.get( *k ) ).map( |v| Access::from_value( v ) )
+    }
+
+    ///
+    /// droit de passage suivant la hiérarchie des tags OSM : le tag le plus précis l'emporte,
+    /// access=* en dernier ; None si aucun tag d'accès ne concerne le profil
+    ///
+    pub fn access( &self, tags: &HashMap<String, String> ) -> Option<Access> {
+        self.own_access( tags ).or( tags.get( "access" ).map( |v| Access::from_value( v ) ) )
+    }
+
+    ///
+    /// vitesse (km/h) sur une voie ouverte au profil par un tag explicite (bicycle=yes sur un footway, ...)
+    /// alors que son type ne l'est pas
+    ///
+    pub fn fallback_speed( &self ) -> f64 {
+        match self {
+            Profile::Car => 30.0,
+            Profile::Bicycle => 12.0,
+            Profile::Foot => 5.0,
+        }
+    }
+
+    ///
+    /// vitesse (km/h, maxspeed compris) et droit de passage du profil sur la voie décrite par ces tags,
+    /// None si la voie lui est fermée
+    ///
+    pub fn way( &self, tags: &HashMap<String, String> ) -> Option<(f64, Access)> {
+        let highway = tags.get( "highway" ).map( |h| h.as_str() ).unwrap_or( "" );
+        let mut speed = match ( self.speed( highway ), self.own_access( tags ) ) {
+            // bac : ouvert à tous les profils sauf tag contraire (motor_vehicle=no, ...)
+            _ if is_ferry( tags ) => FERRY_SPEED,
+            ( Some(speed), _ ) => speed,
+            // type de voie fermé au profil, sauf tag explicite (bicycle=designated sur un footway, ...)
+            ( None, Some(Access::Yes | Access::Destination) ) => self.fallback_speed(),
+            ( None, _ ) => { return None; },
+        };
+        if let Some(Ok(maxspeed)) = tags.get( "maxspeed" ).map( |v| v.parse::<f64>() ) {
+            speed = if *self == Profile::Car { maxspeed } else { speed.min( maxspeed ) };
+        }
+        match self.access( tags ).unwrap_or( Access::Yes ) {
+            Access::No => None,
+            access => Some( (speed, access) ),
+        }
+    }
+
+    ///
+    /// passage par un noeud d'après ses tags barrier=* et access=* : None si le noeud est
+    /// infranchissable avec ce profil, sinon la pénalité de temps (s) pour le franchir
+    ///
+    pub fn passage( &self, tags: &HashMap<String, String> ) -> Option<f64> {
+        let access = self.access( tags );
+        if access == Some(Access::No) { return None; }
+
+        let Some(barrier) = tags.get( "barrier" ) else { return Some(0.0) };
+        // pénalité des barrières franchissables, None pour celles qui bloquent le passage
+        let penalty = match self {
+            Profile::Car => match barrier.as_str() {
+                "no" | "entrance" | "height_restrictor" | "arch" | "sally_port" => Some(0.0),
+                "cattle_grid" => Some(5.0),
+                "lift_gate" | "swing_gate" => Some(15.0),
+                "gate" | "toll_booth" => Some(30.0),
+                "border_control" => Some(60.0),
+                _ => None,
+            },
+            Profile::Bicycle => match barrier.as_str() {
+                "wall" | "fence" | "hedge" | "retaining_wall" | "stile" | "turnstile" | "full-height_turnstile" => None,
+                "gate" | "lift_gate" | "swing_gate" | "chain" | "cycle_barrier" => Some(5.0),
+                "kissing_gate" => Some(15.0),
+                "border_control" => Some(60.0),
+                _ => Some(0.0),
+            },
+            Profile::Foot => match barrier.as_str() {
+                "wall" | "fence" | "hedge" | "retaining_wall" => None,
+                "gate" | "stile" | "turnstile" | "kissing_gate" => Some(5.0),
+                "border_control" => Some(60.0),
+                _ => Some(0.0),
+            },
+        };
+        // un accès explicite (bicycle=yes sur une barrière, motor_vehicle=yes sur des plots, ...) l'emporte
+        match ( penalty, access ) {
+            ( None, Some(_) ) => Some(0.0),
+            ( p, _ ) => p,
+        }
+    }
+
+    ///
+    /// pénalité de routage (s) d'un noeud à accès restreint (access=destination, private, ...),
+    /// comptée dans le poids des itinéraires mais pas dans leur durée
+    ///
+    pub fn restricted( &self, tags: &HashMap<String, String> ) -> f64 {
+        if self.access( tags ) == Some(Access::Destination) { RESTRICTED } else { 0.0 }
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            Profile::Car => write!( f, "car" ),
+            Profile::Bicycle => write!( f, "bicycle" ),
+            Profile::Foot => write!( f, "foot" ),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod profile_tests {
+    use super::*;
+    use crate::graphe::testing::tags;
+
+    #[test]
+    fn profile_names() {
+        assert_eq!( Some(Profile::Bicycle), Profile::from_name( "bike" ) );
+        assert_eq!( Some(Profile::Car), Profile::from_name( "car" ) );
+        assert_eq!( None, Profile::from_name( "boat" ) );
+    }
+
+    #[test]
+    fn profile_speeds() {
+        assert_eq!( Some(50.0), Profile::Car.speed( "residential" ) );
+        assert_eq!( None, Profile::Car.speed( "cycleway" ) );
+        assert_eq!( None, Profile::Bicycle.speed( "motorway" ) );
+        assert_eq!( Some(5.0), Profile::Foot.speed( "primary" ) );
+        assert!( Profile::Bicycle.preference( "primary" ) > Profile::Bicycle.preference( "cycleway" ) );
+    }
+
+    #[test]
+    fn profile_grade() {
+        assert_eq!( 1.0, Profile::Car.grade_factor( 0.1 ) );
+        assert_eq!( 1.0, Profile::Bicycle.grade_factor( 0.0 ) );
+        assert!( ( Profile::Bicycle.grade_factor( 0.05 ) - 1.5 ).abs() < 1e-9 );
+        assert_eq!( 0.75, Profile::Bicycle.grade_factor( -0.2 ) );
+        assert!( ( Profile::Foot.grade_factor( 0.0 ) - 1.0 ).abs() < 1e-9 );
+        assert!( Profile::Foot.grade_factor( -0.05 ) < 1.0 && Profile::Foot.grade_factor( -0.2 ) > 1.0 );
+        assert!( Profile::Foot.grade_factor( 0.1 ) > Profile::Foot.grade_factor( -0.1 ) );
+    }
+
+    #[test]
+    fn profile_ferry() {
+        let brehat = tags( &[("route", "ferry"), ("duration", "00:10"), ("motor_vehicle", "no")] );
+        assert!( is_ferry( &brehat ) );
+        assert_eq!( Some(600.0), ferry_duration( &brehat ) );
+        assert_eq!( None, Profile::Car.way( &brehat ) );
+        assert_eq!( Some((FERRY_SPEED, Access::Yes)), Profile::Foot.way( &brehat ) );
+        assert_eq!( Some(5400.0), ferry_duration( &tags( &[("duration", "PT1H30M")] ) ) );
+        assert_eq!( Some(2700.0), ferry_duration( &tags( &[("duration", "45")] ) ) );
+        assert_eq!( Some(3725.0), ferry_duration( &tags( &[("duration", "1:02:05")] ) ) );
+        assert_eq!( None, ferry_duration( &tags( &[("duration", "une heure")] ) ) );
+    }
+
+    #[test]
+    fn profile_oneway() {
+        let mut tags = HashMap::new();
+        tags.insert( "oneway".to_string(), "yes".to_string() );
+        tags.insert( "oneway:bicycle".to_string(), "no".to_string() );
+        assert!( Profile::Car.oneway( &tags ) );
+        assert!( !Profile::Bicycle.oneway( &tags ) );
+        assert!( !Profile::Foot.oneway( &tags ) );
+    }
+
+    #[test]
+    fn profile_passage() {
+        let bollard = tags( &[("barrier", "bollard")] );
+        assert_eq!( None, Profile::Car.passage( &bollard ) );
+        assert_eq!( Some(0.0), Profile::Bicycle.passage( &bollard ) );
+        assert_eq!( Some(0.0), Profile::Foot.passage( &bollard ) );
+        assert_eq!( Some(0.0), Profile::Car.passage( &tags( &[("barrier", "bollard"), ("motor_vehicle", "yes")] ) ) );
+        assert_eq!( None, Profile::Car.passage( &tags( &[("barrier", "block")] ) ) );
+
+        assert_eq!( Some(15.0), Profile::Car.passage( &tags( &[("barrier", "lift_gate")] ) ) );
+        assert_eq!( Some(30.0), Profile::Car.passage( &tags( &[("barrier", "toll_booth")] ) ) );
+        assert_eq!( Some(0.0), Profile::Bicycle.passage( &tags( &[("barrier", "toll_booth")] ) ) );
+        assert_eq!( Some(30.0), Profile::Car.passage( &tags( &[("barrier", "gate"), ("access", "private")] ) ) );
+        assert_eq!( RESTRICTED, Profile::Car.restricted( &tags( &[("barrier", "gate"), ("access", "private")] ) ) );
+        assert_eq!( 0.0, Profile::Car.restricted( &tags( &[("barrier", "gate")] ) ) );
+
+        // access=no sur un noeud, sauf pour les piétons
+        let no = tags( &[("access", "no"), ("foot", "yes")] );
+        assert_eq!( None, Profile::Car.passage( &no ) );
+        assert_eq!( None, Profile::Bicycle.passage( &no ) );
+        assert_eq!( Some(0.0), Profile::Foot.passage( &no ) );
+        assert_eq!( Some(0.0), Profile::Car.passage( &tags( &[("highway", "crossing")] ) ) );
+    }
+
+    #[test]
+    fn profile_access() {
+        let t = tags( &[("access", "no"), ("vehicle", "destination"), ("motorcar", "yes"), ("foot", "permissive")] );
+        assert_eq!( Some(Access::Yes), Profile::Car.access( &t ) );
+        assert_eq!( Some(Access::Destination), Profile::Bicycle.access( &t ) );
+        assert_eq!( Some(Access::Yes), Profile::Foot.access( &t ) );
+
+        let t = tags( &[("access", "private"), ("bicycle", "no")] );
+        assert_eq!( Some(Access::Destination), Profile::Car.access( &t ) );
+        assert_eq!( Some(Access::No), Profile::Bicycle.access( &t ) );
+        assert_eq!( None, Profile::Foot.own_access( &t ) );
+        assert_eq!( None, Profile::Car.access( &tags( &[("highway", "residential")] ) ) );
+        assert_eq!( Access::Yes, Access::from_value( "permissive" ) );
+        assert_eq!( Access::Destination, Access::from_value( "customers" ) );
+    }
+}
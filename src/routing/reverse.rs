@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::graphe::Graph;
+use crate::graphe::address::same_street;
+use crate::graphe::index::SpatialIndex;
+use crate::routing::route::Route;
+
+
+// rayon de recherche initial (m) de la voie nommée, élargi jusqu'à MAX_STREET
+const STREET: f64 = 50.0;
+const MAX_STREET: f64 = 3200.0;
+// distance (m) maximale d'un numéro sur la voie trouvée
+const HOUSE: f64 = 100.0;
+// en deçà (m), le point est sur le bâtiment : son adresse l'emporte sur la voie la plus proche
+const AT_HOUSE: f64 = 20.0;
+// distance (m) maximale d'une adresse pour reprendre son code postal et sa commune
+const LOCALITY: f64 = 1000.0;
+
+
+///
+/// résultat du géocodage inverse
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Whereabouts {
+    pub way: Option<i64>,
+    pub street: Option<String>,
+    /// distance (m) entre le point et la voie
+    pub distance: f64,
+    pub housenumber: Option<String>,
+    pub postcode: Option<String>,
+    pub city: Option<String>,
+    /// relation OSM de la commune
+    pub commune: Option<i64>,
+}
+
+impl fmt::Display for Whereabouts {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        match (&self.housenumber, &self.street) {
+            (Some(n), Some(s)) => parts.push( format!( "{} {}", n, s ) ),
+            (None, Some(s)) => parts.push( s.to_string() ),
+            _ => {},
+        }
+        let locality: Vec<&str> = [&self.postcode, &self.city].iter().filter_map( |x| x.as_deref() ).collect();
+        if !locality.is_empty() { parts.push( locality.join( " " ) ); }
+        if parts.is_empty() { write!( f, "lieu inconnu" ) } else { write!( f, "{}", parts.join( ", " ) ) }
+    }
+}
+
+///
+/// géocodage inverse : voie nommée la plus proche (géométrie des segments), numéro le plus proche
+/// sur cette voie (tags addr:*) et commune contenant le point (relations boundary=administrative)
+///
+pub fn reverse( g: &Graph, idx: &SpatialIndex, lat: f64, lon: f64 ) -> Whereabouts {
+    let mut w = Whereabouts::default();
+
+    // voie nommée la plus proche
+    let mut radius = STREET;
+    while w.way.is_none() && radius <= MAX_STREET && !idx.is_empty() {
+        if let Some((p, name)) = idx.within( lat, lon, radius ).into_iter()
+            .find_map( |p| Route::way_name( g, p.segment.way ).map( |n| (p, n) ) ) {
+            w.way = Some( p.segment.way );
+            w.street = Some( name );
+            w.distance = p.distance;
+        }
+        radius *= 4.0;
+    }
+
+    // numéro : sur la voie trouvée, ou l'adresse du bâtiment où se trouve le point
+    let houses = g.addresses.nearest( lat, lon, HOUSE );
+    let on_street = houses.iter().find( |(_, p)| w.street.as_ref().is_some_and( |s| same_street( s, &p.street ) ) );
+    let at_house = houses.first().filter( |(d, _)| *d <= AT_HOUSE );
+    if let Some((_, p)) = at_house.or( on_street ) {
+        w.housenumber = p.housenumber.clone();
+        w.street = Some( p.street.clone() );
+        w.postcode = p.postcode.clone();
+        w.city = p.city.clone();
+    }
+
+    // commune
+    if let Some(b) = g.boundaries.containing( lat, lon ) {
+        w.commune = Some( b.id );
+        w.city = Some( b.name.clone() );
+        if b.postcode.is_some() { w.postcode = b.postcode.clone(); }
+    }
+    if w.city.is_none() || w.postcode.is_none() {
+        if let Some((_, p)) = g.addresses.nearest( lat, lon, LOCALITY ).first() {
+            if w.city.is_none() { w.city = p.city.clone(); }
+            if w.postcode.is_none() { w.postcode = p.postcode.clone(); }
+        }
+    }
+    w
+}
+
+
+#[cfg(test)]
+mod reverse_tests {
+    use super::*;
+    use crate::graphe::testing::{tags, test_graph};
+    use crate::graphe::boundary::Boundary;
+
+    fn town() -> Graph {
+        let mut g = test_graph( &[(1, 48.510, -2.770), (2, 48.510, -2.760), (3, 48.520, -2.760)],
+                                &[(10, &[1, 2], &[("highway", "residential"), ("name", "Rue de Brocéliande")]),
+                                  (11, &[2, 3], &[("highway", "service")])] );
+        g.addresses.add_node( 100, &tags( &[("addr:housenumber", "1 bis"), ("addr:street", "Rue de Brocéliande"),
+            ("addr:postcode", "22000"), ("addr:city", "Saint-Brieuc")] ), 48.5102, -2.7690 );
+        g.addresses.add_node( 101, &tags( &[("addr:housenumber", "4"), ("addr:street", "Impasse des Lilas"),
+            ("addr:postcode", "22000"), ("addr:city", "Saint-Brieuc")] ), 48.5098, -2.7651 );
+        g.addresses.finish();
+        g.boundaries.push( Boundary::new( 7, "Saint-Brieuc", None,
+            vec![vec![(48.50, -2.78), (48.50, -2.75), (48.53, -2.75), (48.53, -2.78)]], Vec::new() ) );
+        g
+    }
+
+    #[test]
+    fn reverse_street_and_number() {
+        let g = town();
+        let idx = SpatialIndex::new( &g );
+
+        let w = reverse( &g, &idx, 48.5101, -2.7691 );
+        assert_eq!( Some(10), w.way );
+        assert_eq!( Some("1 bis".to_string()), w.housenumber );
+        assert_eq!( Some(7), w.commune );
+        assert_eq!( "1 bis Rue de Brocéliande, 22000 Saint-Brieuc", w.to_string() );
+
+        // sur le bâtiment de l'impasse, plus proche de la rue que de l'impasse
+        let w = reverse( &g, &idx, 48.5098, -2.7651 );
+        assert_eq!( "4 Impasse des Lilas, 22000 Saint-Brieuc", w.to_string() );
+
+        // la voie de service sans nom est ignorée, le code postal vient de l'adresse voisine
+        let w = reverse( &g, &idx, 48.515, -2.7601 );
+        assert_eq!( Some(10), w.way );
+        assert_eq!( None, w.housenumber );
+        assert_eq!( "Rue de Brocéliande, 22000 Saint-Brieuc", w.to_string() );
+
+        // hors de la commune, loin de tout
+        let w = reverse( &g, &idx, 47.0, -2.0 );
+        assert_eq!( "lieu inconnu", w.to_string() );
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap,HashSet};
+use std::f64::consts::PI;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::graphe::Graph;
+use crate::graphe::elements::Edge;
+use crate::routing::dijkstra::shortest_path_at;
+use crate::routing::distances::{destination, distance_haversine};
+use crate::routing::profile::Profile;
+
+// rapport moyen entre la longueur par la route et la longueur à vol d'oiseau
+const DETOUR: f64 = 1.3;
+// nombre de points de passage répartis sur le cercle (le départ compris)
+const WAYPOINTS: usize = 4;
+// multiplicateur du coût d'un arc déjà emprunté
+const REUSE_PENALTY: f64 = 8.0;
+// nombre d'essais pour approcher la longueur demandée
+const ATTEMPTS: usize = 4;
+
+
+///
+/// une boucle : la liste des noeuds parcourus (départ et arrivée identiques)
+///
+#[derive(Debug, Clone)]
+pub struct RoundTrip {
+    pub nodes: Vec<i64>,
+    /// longueur totale en mètres
+    pub distance: f64,
+    /// longueur (en mètres) parcourue sur des arcs déjà empruntés
+    pub reused: f64,
+}
+
+
+///
+/// génère une boucle d'environ `length` mètres depuis le noeud `start`
+/// les points de passage sont placés sur un cercle dont l'orientation dépend de `seed`
+///
+pub fn round_trip( g: &Graph, profile: &Profile, start: i64, length: f64, seed: u64 ) -> Option<RoundTrip> {
+    let adj = g.get_directed_for( profile );
+    if !adj.contains_key( &start ) { return None; }
+
+    let mut rng = StdRng::seed_from_u64( seed );
+    let bearing: f64 = rng.gen_range( 0.0..360.0 );
+    let clockwise: bool = rng.gen();
+
+    let mut radius = length / ( 2.0 * PI * DETOUR );
+    let mut best: Option<RoundTrip> = None;
+    for _ in 0..ATTEMPTS {
+        match attempt( g, &adj, start, bearing, clockwise, radius ) {
+            Some(rt) => {
+                let ratio = length / rt.distance;
+                let better = match &best {
+                    Some(b) => ( rt.distance - length ).abs() < ( b.distance - length ).abs(),
+                    None => true,
+                };
+                if better { best = Some(rt); }
+                if ( ratio - 1.0 ).abs() < 0.05 { break; }
+                radius *= ratio;
+            },
+            None => { radius *= 0.8; },
+        }
+    }
+    best
+}
+
+///
+/// une boucle pour un rayon donné : départ -> points de passage -> départ
+///
+fn attempt( g: &Graph, adj: &HashMap<i64, Vec<Edge>>, start: i64, bearing: f64, clockwise: bool, radius: f64 ) -> Option<RoundTrip> {
+    let origin = g.tnodes.get( &start )?;
+    let (clat, clon) = destination( origin.lat(), origin.lon(), bearing, radius );
+
+    // le départ est sur le cercle à l'opposé du cap, les autres points sont répartis à la suite
+    let mut stops = vec![start];
+    let step = if clockwise { 360.0 } else { -360.0 } / WAYPOINTS as f64;
+    for k in 1..WAYPOINTS {
+        let (lat, lon) = destination( clat, clon, bearing + 180.0 + step * k as f64, radius );
+        let wp = nearest_routable( lat, lon, g, adj )?;
+        if stops.last() != Some(&wp) { stops.push( wp ); }
+    }
+    stops.push( start );
+
+    // chaque arc emprunté voit son coût augmenter pour les étapes suivantes
+    let mut weighted = adj.clone();
+    let mut nodes: Vec<i64> = vec![start];
+    let mut edges: Vec<Edge> = Vec::new();
+    for leg in stops.windows( 2 ) {
+        if leg[0] == leg[1] { continue; }
+        // les arêtes retenues par dijkstra, chaque noeud du chemin compris
+        let path = shortest_path_at( "cost", &weighted, leg[0], leg[1], |_, e, _| Some( e.clone() ) )?;
+        let mut from = leg[0];
+        for e in path {
+            penalize( &mut weighted, from, e.node );
+            penalize( &mut weighted, e.node, from );
+            from = e.node;
+            nodes.push( e.node );
+            edges.push( e );
+        }
+    }
+
+    let mut distance = 0.0;
+    let mut reused = 0.0;
+    let mut seen: HashSet<(i64, i64)> = HashSet::new();
+    for (pair, e) in nodes.windows( 2 ).zip( &edges ) {
+        distance += e.distance;
+        if !seen.insert( ( pair[0].min( pair[1] ), pair[0].max( pair[1] ) ) ) { reused += e.distance; }
+    }
+    Some( RoundTrip { nodes, distance, reused } )
+}
+
+fn penalize( adj: &mut HashMap<i64, Vec<Edge>>, from: i64, to: i64 ) {
+    if let Some(edges) = adj.get_mut( &from ) {
+        for e in edges.iter_mut().filter( |e| e.node == to ) { e.cost *= REUSE_PENALTY; }
+    }
+}
+
+///
+/// le noeud le plus proche ayant au moins un arc sortant dans le graphe orienté
+///
+fn nearest_routable( lat: f64, lon: f64, g: &Graph, adj: &HashMap<i64, Vec<Edge>> ) -> Option<i64> {
+    let mut best: Option<(i64, f64)> = None;
+    for id in adj.keys() {
+        let Some(n) = g.tnodes.get( id ) else { continue };
+        let d = distance_haversine( lat, lon, n.lat(), n.lon() );
+        match best {
+            Some((bid, bd)) if bd < d || ( bd == d && bid < *id ) => {},
+            _ => { best = Some( (*id, d) ); },
+        }
+    }
+    best.map( |(id, _)| id )
+}
+
+
+#[cfg(test)]
+mod roundtrip_tests {
+    use super::*;
+    use crate::graphe::testing::{TestWay, test_graph};
+
+    // quadrillage de rues résidentielles d'environ 200 m de côté, noeud id = 100 * ligne + colonne
+    fn grid() -> Graph {
+        grid_with( |_, _| () )
+    }
+
+    // quadrillage modifié par `edit( noeuds, rues )` avant construction du graphe
+    fn grid_with<F>( edit: F ) -> Graph
+    where F: Fn( &mut Vec<(i64, f64, f64)>, &mut Vec<(i64, Vec<i64>)> ) {
+        let mut nodes: Vec<(i64, f64, f64)> = (0..11).flat_map( |i| (0..11).map( move |j| (100 * i + j, 48.4 + 0.0018 * i as f64, -2.8 + 0.0027 * j as f64) ) ).collect();
+        let mut lines: Vec<(i64, Vec<i64>)> = (0..11).flat_map( |i| [(i, (0..11).map( |j| 100 * i + j ).collect()),
+                                                                     (100 + i, (0..11).map( |j| 100 * j + i ).collect())] ).collect();
+        edit( &mut nodes, &mut lines );
+        let ways: Vec<TestWay> = lines.iter().map( |(id, refs)| (*id, refs.as_slice(), &[("highway", "residential")][..]) ).collect();
+        test_graph( &nodes, &ways )
+    }
+
+    #[test]
+    fn loop_on_grid() {
+        let g = grid();
+        let Some(rt) = round_trip( &g, &Profile::Bicycle, 505, 4000.0, 1 ) else { panic!( "a loop must exist" ) };
+
+        assert_eq!( Some(&505), rt.nodes.first() );
+        assert_eq!( Some(&505), rt.nodes.last() );
+        assert!( ( rt.distance - 4000.0 ).abs() < 1000.0 );
+        assert!( rt.reused < rt.distance / 4.0 );
+    }
+
+    #[test]
+    fn seed_changes_loop() {
+        let g = grid();
+        let a = round_trip( &g, &Profile::Foot, 505, 4000.0, 1 ).unwrap();
+        let b = round_trip( &g, &Profile::Foot, 505, 4000.0, 2 ).unwrap();
+        let c = round_trip( &g, &Profile::Foot, 505, 4000.0, 1 ).unwrap();
+
+        assert_ne!( a.nodes, b.nodes );
+        assert_eq!( a.nodes, c.nodes );
+    }
+
+    #[test]
+    fn close_nodes_kept() {
+        // un noeud à 4 mm du centre sur la rue de la ligne 5 : la boucle qui en part le garde
+        let g = grid_with( |nodes, lines| {
+            nodes.push( (1000, 48.4 + 0.0018 * 5.0, -2.8 + 0.0027 * 5.0 + 0.00000005) );
+            lines[10].1.insert( 6, 1000 );
+        } );
+        let rt = round_trip( &g, &Profile::Foot, 1000, 4000.0, 1 ).unwrap();
+
+        let adj = g.get_directed_for( &Profile::Foot );
+        assert_eq!( Some(&1000), rt.nodes.first() );
+        assert!( rt.nodes.windows( 2 ).all( |p| adj[&p[0]].iter().any( |e| e.node == p[1] ) ) );
+    }
+
+    #[test]
+    fn unknown_start() {
+        assert!( round_trip( &grid(), &Profile::Car, 42, 4000.0, 0 ).is_none() );
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{HashMap,BTreeMap};
+
+use crate::graphe::Graph;
+use crate::graphe::elements::Edge;
+use crate::routing::profile::is_ferry;
+
+
+///
+/// un itinéraire calculé : les noeuds parcourus et leurs coordonnées, la distance (m),
+/// le temps (s), la suite des ways empruntées et les arcs parcourus
+///
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    pub nodes: Vec<i64>,
+    pub coords: Vec<(f64, f64)>,
+    pub distance: f64,
+    pub time: f64,
+    pub ways: Vec<i64>,
+    /// un arc par couple de noeuds consécutifs (absent si les noeuds ne sont pas reliés)
+    pub edges: Vec<Edge>,
+}
+
+impl Route {
+    ///
+    /// construction à partir d'une suite de noeuds et du graphe orienté utilisé pour la calculer
+    ///
+    pub fn from_nodes( g: &Graph, adj: &HashMap<i64, Vec<Edge>>, nodes: Vec<i64> ) -> Self {
+        let mut r = Route { coords: Vec::with_capacity( nodes.len() ), ..Default::default() };
+        for n in nodes.iter() {
+            if let Some(tn) = g.tnodes.get( n ) { r.coords.push( (tn.lat(), tn.lon()) ); }
+        }
+        for pair in nodes.windows( 2 ) {
+            let edge = adj.get( &pair[0] )
+                .and_then( |edges| edges.iter().filter( |e| e.node == pair[1] ).min_by( |a, b| a.distance.total_cmp( &b.distance ) ) );
+            if let Some(e) = edge {
+                r.edges.push( e.clone() );
+                r.distance += e.distance;
+                r.time += e.time;
+                if r.ways.last() != Some(&e.way) { r.ways.push( e.way ); }
+            }
+        }
+        r.nodes = nodes;
+        r
+    }
+
+    ///
+    /// construction à partir du noeud de départ et des arcs parcourus (cf. dijkstra::shortest_path_at)
+    ///
+    pub fn from_edges( g: &Graph, start: i64, edges: Vec<Edge> ) -> Self {
+        let mut r = Route::default();
+        r.nodes.push( start );
+        r.nodes.extend( edges.iter().map( |e| e.node ) );
+        r.coords = r.nodes.iter().filter_map( |n| g.tnodes.get( n ) ).map( |tn| (tn.lat(), tn.lon()) ).collect();
+        for e in edges.iter() {
+            r.distance += e.distance;
+            r.time += e.time;
+            if r.ways.last() != Some(&e.way) { r.ways.push( e.way ); }
+        }
+        r.edges = edges;
+        r
+    }
+
+    ///
+    /// construction à partir du résultat de dijkstra::shortest_path
+    ///
+    pub fn from_path( g: &Graph, adj: &HashMap<i64, Vec<Edge>>, bt: &BTreeMap<i64, i64> ) -> Self {
+        Self::from_nodes( g, adj, bt.values().copied().collect() )
+    }
+
+    ///
+    /// nom (ou référence) d'une way de l'itinéraire
+    ///
+    pub fn way_name( g: &Graph, id: i64 ) -> Option<String> {
+        let w = g.tways.get( &id )?;
+        w.tags().get( "name" ).or( w.tags().get( "ref" ) ).cloned()
+    }
+
+    ///
+    /// la way de l'itinéraire est-elle une traversée en bac
+    ///
+    pub fn is_ferry( g: &Graph, id: i64 ) -> bool {
+        g.tways.get( &id ).is_some_and( |w| is_ferry( w.tags() ) )
+    }
+
+    ///
+    /// portions de l'itinéraire : indices (début, fin) dans coords et traversée en bac ou non
+    ///
+    pub fn sections( &self, g: &Graph ) -> Vec<(usize, usize, bool)> {
+        let mut sections: Vec<(usize, usize, bool)> = Vec::new();
+        for (i, e) in self.edges.iter().enumerate().take( self.coords.len().saturating_sub( 1 ) ) {
+            let ferry = Self::is_ferry( g, e.way );
+            match sections.last_mut() {
+                Some(s) if s.2 == ferry => { s.1 = i + 1; },
+                _ => { sections.push( (i, i + 1, ferry) ); },
+            }
+        }
+        if sections.is_empty() && !self.coords.is_empty() { sections.push( (0, self.coords.len() - 1, false) ); }
+        sections
+    }
+}
+
+
+#[cfg(test)]
+mod route_tests {
+    use super::*;
+    use crate::graphe::testing::{tags, test_graph};
+    use crate::graphe::elements::TWay;
+
+    #[test]
+    fn route_from_nodes() {
+        let g = test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.99), (3, 48.01, -1.99)],
+                            &[(7, &[1, 2], &[("highway", "residential"), ("name", "Rue du Beau Chemin")]),
+                              (8, &[2, 3], &[("highway", "residential"), ("ref", "D 27")])] );
+        let adj = g.get_directed();
+
+        let r = Route::from_nodes( &g, &adj, vec![1, 2, 3] );
+        assert_eq!( vec![7, 8], r.ways );
+        assert_eq!( 3, r.coords.len() );
+        assert_eq!( vec![2, 3], r.edges.iter().map( |e| e.node ).collect::<Vec<i64>>() );
+        assert!( ( r.distance - g.tways[&7].len() - g.tways[&8].len() ).abs() < 1e-6 );
+        assert!( ( r.time - r.distance / (50.0 / 3.6) ).abs() < 1e-6 );
+        assert_eq!( Some("Rue du Beau Chemin".to_string()), Route::way_name( &g, 7 ) );
+        assert_eq!( Some("D 27".to_string()), Route::way_name( &g, 8 ) );
+    }
+
+    #[test]
+    fn route_with_ferry() {
+        // embarcadère (1 -> 2), bac de 10 min vers l'île (2 -> 3), route de l'île (3 -> 4)
+        let g = test_graph( &[(1, 48.82, -3.01), (2, 48.83, -3.0), (3, 48.85, -3.0), (4, 48.86, -2.99)],
+                            &[(7, &[1, 2], &[("highway", "secondary")]),
+                              (8, &[2, 3], &[("route", "ferry"), ("name", "Bréhat"), ("duration", "00:10")]),
+                              (9, &[3, 4], &[("highway", "track")])] );
+        let adj = g.get_directed_for( &crate::routing::profile::Profile::Foot );
+
+        let r = Route::from_nodes( &g, &adj, vec![1, 2, 3, 4] );
+        assert!( ( r.edges[1].time - 600.0 ).abs() < 1e-6 );
+        assert!( Route::is_ferry( &g, 8 ) && !Route::is_ferry( &g, 7 ) );
+        assert_eq!( vec![(0, 1, false), (1, 2, true), (2, 3, false)], r.sections( &g ) );
+        let steps = crate::routing::instructions::instructions( &g, &r );
+        assert_eq!( "Prendre le bac sur Bréhat", steps[1].text() );
+
+        // sans durée, le bac va à FERRY_SPEED
+        let mut g = g;
+        g.tways.insert( 8, TWay::new( vec![2, 3], tags( &[("route", "ferry")] ), &g.tnodes ) );
+        let e = &g.get_directed_for( &crate::routing::profile::Profile::Foot )[&2];
+        let ferry = e.iter().find( |e| e.node == 3 ).unwrap();
+        assert!( ( ferry.time - ferry.distance / (crate::routing::profile::FERRY_SPEED / 3.6) ).abs() < 1e-6 );
+    }
+}
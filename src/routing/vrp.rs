@@ -0,0 +1,531 @@
+use std::fmt;
+use std::fs;
+use std::collections::HashMap;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::graphe::elements::Edge;
+use crate::routing::dijkstra::one_to_all;
+
+// pénalité (en secondes) d'une livraison non affectée à un véhicule
+const UNASSIGNED_PENALTY: f64 = 1_000_000.0;
+
+
+///
+/// un dépôt : un noeud du graphe ou des coordonnées (lat, lon)
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct Depot {
+    pub id: String,
+    pub node: Option<i64>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+}
+
+///
+/// un véhicule rattaché à un dépôt, avec sa capacité et ses horaires (en secondes depuis minuit)
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct Vehicle {
+    pub id: String,
+    pub depot: String,
+    pub capacity: u32,
+    #[serde(default = "full_day")]
+    pub shift: (f64, f64),
+}
+
+///
+/// une livraison (ou un enlèvement) chez un client
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    pub node: Option<i64>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    #[serde(default)]
+    pub demand: u32,
+    /// durée du service sur place en secondes
+    #[serde(default)]
+    pub service: f64,
+    #[serde(default = "full_day")]
+    pub time_window: (f64, f64),
+}
+
+///
+/// paramètres de la recherche locale
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct Options {
+    #[serde(default = "default_iterations")]
+    pub iterations: usize,
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self { iterations: default_iterations(), seed: 0 }
+    }
+}
+
+///
+/// le problème de tournées tel que lu dans le fichier json
+///
+#[derive(Deserialize, Debug, Clone)]
+pub struct Problem {
+    pub vehicles: Vec<Vehicle>,
+    pub depots: Vec<Depot>,
+    pub jobs: Vec<Job>,
+    #[serde(default)]
+    pub options: Options,
+}
+
+fn full_day() -> (f64, f64) {
+    (0.0, 86400.0)
+}
+
+fn default_iterations() -> usize {
+    2000
+}
+
+
+#[derive(Debug)]
+pub enum VrpError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnknownDepot(String),
+    NoLocation(String),
+    /// node explicite absent du graphe orienté du profil
+    NotRoutable(String, i64),
+    /// aucune voie praticable près des coordonnées
+    NoSnap(String),
+}
+
+impl fmt::Display for VrpError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            VrpError::Io(e) => write!( f, "lecture impossible : {}", e ),
+            VrpError::Json(e) => write!( f, "json invalide : {}", e ),
+            VrpError::UnknownDepot(d) => write!( f, "dépôt {} inconnu", d ),
+            VrpError::NoLocation(id) => write!( f, "{} n'a ni node ni coordonnées", id ),
+            VrpError::NotRoutable(id, n) => write!( f, "{} : le noeud {} n'est pas sur une voie praticable", id, n ),
+            VrpError::NoSnap(id) => write!( f, "{} : aucune voie praticable à proximité", id ),
+        }
+    }
+}
+
+impl From<std::io::Error> for VrpError {
+    fn from(e: std::io::Error) -> Self {
+        VrpError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for VrpError {
+    fn from(e: serde_json::Error) -> Self {
+        VrpError::Json(e)
+    }
+}
+
+impl Problem {
+    ///
+    /// lecture d'un problème à partir d'un fichier json
+    ///
+    pub fn from_file( filename: &str ) -> Result<Self, VrpError> {
+        let content = fs::read_to_string( filename )?;
+        let p: Problem = serde_json::from_str( content.as_str() )?;
+        Ok(p)
+    }
+}
+
+
+///
+/// un arrêt dans la tournée d'un véhicule (heures en secondes depuis minuit)
+///
+#[derive(Serialize, Debug, Clone)]
+pub struct Stop {
+    pub job: String,
+    pub node: i64,
+    pub arrival: f64,
+    pub start: f64,
+    pub departure: f64,
+    pub load: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct VehicleRoute {
+    pub vehicle: String,
+    pub stops: Vec<Stop>,
+    pub distance: f64,
+    pub duration: f64,
+    pub load: u32,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Solution {
+    pub routes: Vec<VehicleRoute>,
+    pub unassigned: Vec<String>,
+    pub cost: f64,
+}
+
+impl fmt::Display for Solution {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        for r in self.routes.iter() {
+            writeln!( f, "{} : {} arrêts, charge {}, {:.2} m, {:.0} s",
+                r.vehicle, r.stops.len(), r.load, r.distance, r.duration )?;
+            for s in r.stops.iter() {
+                writeln!( f, "\t{:>8.0} s : {} ({})", s.start, s.job, s.node )?;
+            }
+        }
+        if !self.unassigned.is_empty() {
+            writeln!( f, "non affectés : {}", self.unassigned.join( ", " ) )?;
+        }
+        write!( f, "coût : {:.0}", self.cost )
+    }
+}
+
+
+struct JobData {
+    loc: usize,
+    demand: u32,
+    service: f64,
+    tw: (f64, f64),
+}
+
+struct VehicleData {
+    loc: usize,
+    capacity: u32,
+    shift: (f64, f64),
+}
+
+///
+/// modèle interne : matrices de temps/distances entre lieux (dépôts puis clients)
+///
+struct Model {
+    time: Vec<Vec<f64>>,
+    dist: Vec<Vec<f64>>,
+    jobs: Vec<JobData>,
+    vehicles: Vec<VehicleData>,
+}
+
+#[derive(Clone)]
+struct Plan {
+    routes: Vec<Vec<usize>>,
+    unassigned: Vec<usize>,
+    cost: f64,
+}
+
+impl Model {
+    ///
+    /// horaires (arrivée, début de service) de chaque client de la séquence,
+    /// None si la tournée viole la capacité ou une fenêtre horaire
+    ///
+    fn schedule( &self, v: usize, seq: &[usize] ) -> Option<Vec<(f64, f64)>> {
+        let veh = &self.vehicles[v];
+        let load: u32 = seq.iter().map( |j| self.jobs[*j].demand ).sum();
+        if load > veh.capacity { return None; }
+
+        let mut visits = Vec::with_capacity( seq.len() );
+        let mut t = veh.shift.0;
+        let mut cur = veh.loc;
+        for j in seq.iter() {
+            let job = &self.jobs[*j];
+            let arrival = t + self.time[cur][job.loc];
+            if !arrival.is_finite() { return None; }
+            let start = arrival.max( job.tw.0 );
+            if start > job.tw.1 { return None; }
+            visits.push( (arrival, start) );
+            t = start + job.service;
+            cur = job.loc;
+        }
+        let back = t + self.time[cur][veh.loc];
+        if !back.is_finite() || back > veh.shift.1 { return None; }
+        Some(visits)
+    }
+
+    ///
+    /// temps de conduite d'une tournée
+    ///
+    fn travel( &self, v: usize, seq: &[usize] ) -> f64 {
+        let mut cur = self.vehicles[v].loc;
+        let mut t = 0.0;
+        for j in seq.iter() {
+            t += self.time[cur][self.jobs[*j].loc];
+            cur = self.jobs[*j].loc;
+        }
+        t + self.time[cur][self.vehicles[v].loc]
+    }
+
+    fn cost( &self, plan: &Plan ) -> f64 {
+        let mut c = UNASSIGNED_PENALTY * plan.unassigned.len() as f64;
+        for (v, seq) in plan.routes.iter().enumerate() {
+            c += self.travel( v, seq );
+        }
+        c
+    }
+
+    ///
+    /// insertion au moindre coût de chaque client de la liste, ceux qui ne trouvent
+    /// pas de place restent non affectés
+    ///
+    fn insert_all( &self, plan: &mut Plan, jobs: &[usize] ) {
+        for j in jobs.iter() {
+            let mut best: Option<(f64, usize, usize)> = None;
+            for (v, seq) in plan.routes.iter().enumerate() {
+                let before = self.travel( v, seq );
+                for pos in 0..=seq.len() {
+                    let mut candidate = seq.clone();
+                    candidate.insert( pos, *j );
+                    if self.schedule( v, &candidate ).is_none() { continue; }
+                    let delta = self.travel( v, &candidate ) - before;
+                    match best {
+                        Some((d, _, _)) if d <= delta => {},
+                        _ => { best = Some( (delta, v, pos) ); },
+                    }
+                }
+            }
+            match best {
+                Some((_, v, pos)) => { plan.routes[v].insert( pos, *j ); },
+                None => { plan.unassigned.push( *j ); },
+            }
+        }
+        plan.cost = self.cost( plan );
+    }
+
+    ///
+    /// construction d'une première solution : insertion par fin de fenêtre croissante
+    ///
+    fn construct( &self ) -> Plan {
+        let mut plan = Plan { routes: vec![Vec::new(); self.vehicles.len()], unassigned: Vec::new(), cost: 0.0 };
+        let mut order: Vec<usize> = (0..self.jobs.len()).collect();
+        order.sort_by( |a, b| self.jobs[*a].tw.1.total_cmp( &self.jobs[*b].tw.1 ) );
+        self.insert_all( &mut plan, &order );
+        plan
+    }
+
+    ///
+    /// recherche locale "ruin & recreate" avec acceptation de type recuit simulé
+    ///
+    fn improve( &self, initial: Plan, options: &Options ) -> Plan {
+        let mut rng = StdRng::seed_from_u64( options.seed );
+        let mut best = initial.clone();
+        let mut current = initial;
+        let n = self.jobs.len();
+        // sans véhicule, tous les clients restent non affectés
+        if n == 0 || self.vehicles.is_empty() { return best; }
+        let t0 = 0.05 * self.cost( &current ).min( UNASSIGNED_PENALTY );
+
+        for it in 0..options.iterations {
+            let mut candidate = current.clone();
+
+            // destruction : on retire quelques clients au hasard
+            let k = rng.gen_range( 1..=(n / 4 + 1).min( n ) );
+            let mut removed: Vec<usize> = std::mem::take( &mut candidate.unassigned );
+            for _ in 0..k {
+                let v = rng.gen_range( 0..candidate.routes.len() );
+                if candidate.routes[v].is_empty() { continue; }
+                let pos = rng.gen_range( 0..candidate.routes[v].len() );
+                removed.push( candidate.routes[v].remove( pos ) );
+            }
+
+            // reconstruction dans un ordre aléatoire
+            removed.shuffle( &mut rng );
+            self.insert_all( &mut candidate, &removed );
+
+            let temperature = t0 * (1.0 - it as f64 / options.iterations as f64);
+            let delta = candidate.cost - current.cost;
+            if delta < 0.0 || ( temperature > 0.0 && rng.gen::<f64>() < (-delta / temperature).exp() ) {
+                current = candidate;
+                if current.cost < best.cost { best = current.clone(); }
+            }
+        }
+        best
+    }
+}
+
+
+///
+/// récupération du noeud du graphe orienté associé à un lieu : node explicite, qui doit être
+/// praticable, ou noeud rattaché aux coordonnées par `snap`
+///
+fn locate<S>( id: &str, node: Option<i64>, lat: Option<f64>, lon: Option<f64>, adj: &HashMap<i64, Vec<Edge>>, snap: &S ) -> Result<i64, VrpError>
+where S: Fn( f64, f64 ) -> Option<i64> {
+    match (node, lat, lon) {
+        (Some(n), _, _) if adj.contains_key( &n ) => Ok(n),
+        (Some(n), _, _) => Err( VrpError::NotRoutable( id.to_string(), n ) ),
+        (None, Some(lat), Some(lon)) => snap( lat, lon ).ok_or_else( || VrpError::NoSnap( id.to_string() ) ),
+        _ => Err( VrpError::NoLocation( id.to_string() ) ),
+    }
+}
+
+///
+/// résolution du problème de tournées sur le graphe orienté (temps de parcours), les lieux donnés
+/// par leurs coordonnées étant rattachés au réseau par `snap` (cf. RoutingEngine::nearest)
+///
+pub fn solve<S>( p: &Problem, adj: &HashMap<i64, Vec<Edge>>, snap: S ) -> Result<Solution, VrpError>
+where S: Fn( f64, f64 ) -> Option<i64> {
+    // les lieux : les dépôts en premier, puis les clients
+    let mut nodes: Vec<i64> = Vec::new();
+    let mut depots: HashMap<&str, usize> = HashMap::new();
+    for d in p.depots.iter() {
+        depots.insert( d.id.as_str(), nodes.len() );
+        nodes.push( locate( &d.id, d.node, d.lat, d.lon, adj, &snap )? );
+    }
+    let mut jobs: Vec<JobData> = Vec::new();
+    for j in p.jobs.iter() {
+        jobs.push( JobData { loc: nodes.len(), demand: j.demand, service: j.service, tw: j.time_window } );
+        nodes.push( locate( &j.id, j.node, j.lat, j.lon, adj, &snap )? );
+    }
+    let mut vehicles: Vec<VehicleData> = Vec::new();
+    for v in p.vehicles.iter() {
+        let Some(loc) = depots.get( v.depot.as_str() ) else { return Err( VrpError::UnknownDepot( v.depot.clone() ) ) };
+        vehicles.push( VehicleData { loc: *loc, capacity: v.capacity, shift: v.shift } );
+    }
+
+    let (time, dist) = matrix( &nodes, adj );
+    let model = Model { time, dist, jobs, vehicles };
+    Ok( solution( p, &nodes, &model, model.improve( model.construct(), &p.options ) ) )
+}
+
+///
+/// matrices des temps et des distances entre tous les lieux (infini si inaccessible)
+///
+fn matrix( nodes: &[i64], adj: &HashMap<i64, Vec<Edge>> ) -> (Vec<Vec<f64>>, Vec<Vec<f64>>) {
+    let mut time = vec![vec![f64::INFINITY; nodes.len()]; nodes.len()];
+    let mut dist = vec![vec![f64::INFINITY; nodes.len()]; nodes.len()];
+    for (i, from) in nodes.iter().enumerate() {
+        let costs = one_to_all( "time", adj, *from, f64::INFINITY );
+        for (j, to) in nodes.iter().enumerate() {
+            if let Some((t, d)) = costs.get( to ) {
+                time[i][j] = *t;
+                dist[i][j] = *d;
+            }
+        }
+    }
+    (time, dist)
+}
+
+fn solution( p: &Problem, nodes: &[i64], model: &Model, plan: Plan ) -> Solution {
+    let mut routes: Vec<VehicleRoute> = Vec::new();
+    for (v, seq) in plan.routes.iter().enumerate() {
+        if seq.is_empty() { continue; }
+        let visits = model.schedule( v, seq ).expect( "plan must be feasible" );
+        let veh = &model.vehicles[v];
+        let mut stops: Vec<Stop> = Vec::new();
+        let mut load = 0;
+        let mut distance = 0.0;
+        let mut cur = veh.loc;
+        for (j, (arrival, start)) in seq.iter().zip( visits.iter() ) {
+            let job = &model.jobs[*j];
+            load += job.demand;
+            distance += model.dist[cur][job.loc];
+            cur = job.loc;
+            stops.push( Stop {
+                job: p.jobs[*j].id.clone(), node: nodes[job.loc],
+                arrival: *arrival, start: *start, departure: start + job.service, load,
+            } );
+        }
+        distance += model.dist[cur][veh.loc];
+        let last = stops.last().map( |s| s.departure ).unwrap_or( veh.shift.0 );
+        let duration = last + model.time[cur][veh.loc] - veh.shift.0;
+        routes.push( VehicleRoute { vehicle: p.vehicles[v].id.clone(), stops, distance, duration, load } );
+    }
+    let unassigned = plan.unassigned.iter().map( |j| p.jobs[*j].id.clone() ).collect();
+    Solution { routes, unassigned, cost: plan.cost }
+}
+
+
+#[cfg(test)]
+mod vrp_tests {
+    use super::*;
+
+    fn edge( node: i64, time: f64 ) -> Edge {
+        Edge { node, distance: time * 10.0, time, cost: 0.0, penalty: 0.0, way: 0 }
+    }
+
+    //  dépôt 0 au centre d'une étoile, clients 1, 2 et 3 à 100 s aller et retour
+    fn star() -> HashMap<i64, Vec<Edge>> {
+        let mut adj: HashMap<i64, Vec<Edge>> = HashMap::new();
+        adj.insert( 0, vec![edge( 1, 100.0 ), edge( 2, 100.0 ), edge( 3, 100.0 )] );
+        for n in 1..4 { adj.insert( n, vec![edge( 0, 100.0 )] ); }
+        adj
+    }
+
+    // rattachement des coordonnées : le client 2 est en (2, 2), rien ailleurs
+    fn snap( lat: f64, lon: f64 ) -> Option<i64> {
+        if lat == 2.0 && lon == 2.0 { Some(2) } else { None }
+    }
+
+    fn problem( json: &str ) -> Problem {
+        serde_json::from_str( json ).unwrap()
+    }
+
+    #[test]
+    fn capacity_split() {
+        let adj = star();
+        let p = problem( r#"{
+            "depots": [ { "id": "d", "node": 0 } ],
+            "vehicles": [ { "id": "v1", "depot": "d", "capacity": 2 }, { "id": "v2", "depot": "d", "capacity": 2 } ],
+            "jobs": [ { "id": "a", "node": 1, "demand": 1 }, { "id": "b", "node": 2, "demand": 1 }, { "id": "c", "node": 3, "demand": 1 } ]
+        }"# );
+        let s = solve( &p, &adj, snap ).unwrap();
+
+        assert!( s.unassigned.is_empty() );
+        assert_eq!( 2, s.routes.len() );
+        assert!( s.routes.iter().all( |r| r.load <= 2 ) );
+        assert_eq!( 600.0, s.cost );
+    }
+
+    #[test]
+    fn time_windows() {
+        let adj = star();
+        let p = problem( r#"{
+            "depots": [ { "id": "d", "node": 0 } ],
+            "vehicles": [ { "id": "v1", "depot": "d", "capacity": 10, "shift": [0, 1000] } ],
+            "jobs": [ { "id": "late", "node": 1, "service": 10, "time_window": [500, 600] },
+                      { "id": "early", "node": 2, "service": 10, "time_window": [0, 150] },
+                      { "id": "never", "node": 3, "time_window": [0, 50] } ]
+        }"# );
+        let s = solve( &p, &adj, snap ).unwrap();
+
+        assert_eq!( vec!["never".to_string()], s.unassigned );
+        let stops: Vec<&str> = s.routes[0].stops.iter().map( |s| s.job.as_str() ).collect();
+        assert_eq!( vec!["early", "late"], stops );
+        assert_eq!( 500.0, s.routes[0].stops[1].start );
+        assert_eq!( 4000.0, s.routes[0].distance );
+    }
+
+    #[test]
+    fn unknown_depot() {
+        let adj = star();
+        let p = problem( r#"{ "depots": [], "vehicles": [ { "id": "v1", "depot": "x", "capacity": 1 } ], "jobs": [] }"# );
+        assert!( matches!( solve( &p, &adj, snap ), Err(VrpError::UnknownDepot(_)) ) );
+    }
+
+    #[test]
+    fn no_vehicle() {
+        let adj = star();
+        let p = problem( r#"{ "depots": [ { "id": "d", "node": 0 } ], "vehicles": [],
+            "jobs": [ { "id": "a", "node": 1 }, { "id": "b", "node": 2 } ] }"# );
+        let s = solve( &p, &adj, snap ).unwrap();
+        assert!( s.routes.is_empty() );
+        assert_eq!( vec!["a".to_string(), "b".to_string()], s.unassigned );
+    }
+
+    #[test]
+    fn locations() {
+        let adj = star();
+        let p = problem( r#"{ "depots": [ { "id": "d", "node": 0 } ], "vehicles": [ { "id": "v1", "depot": "d", "capacity": 1 } ],
+            "jobs": [ { "id": "a", "lat": 2.0, "lon": 2.0 } ] }"# );
+        let s = solve( &p, &adj, snap ).unwrap();
+        assert_eq!( 2, s.routes[0].stops[0].node );
+
+        let p = problem( r#"{ "depots": [ { "id": "d", "node": 0 } ], "vehicles": [], "jobs": [ { "id": "poi", "node": 42 } ] }"# );
+        assert!( matches!( solve( &p, &adj, snap ), Err(VrpError::NotRoutable(_, 42)) ) );
+        let p = problem( r#"{ "depots": [ { "id": "d", "lat": 5.0, "lon": 5.0 } ], "vehicles": [], "jobs": [] }"# );
+        assert!( matches!( solve( &p, &adj, snap ), Err(VrpError::NoSnap(_)) ) );
+    }
+}
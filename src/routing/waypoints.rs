@@ -0,0 +1,265 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use gpx::errors::GpxError;
+use serde_json::Value;
+
+use crate::engine::{EngineError, Itinerary, Metric, RouteOptions, RoutingEngine};
+use crate::routing::profile::Profile;
+
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(std::io::Error),
+    Gpx(GpxError),
+    Json(serde_json::Error),
+    /// ligne csv illisible : numéro (à partir de 1) et contenu
+    Csv(usize, String),
+    /// extension autre que .gpx, .geojson, .json ou .csv
+    Format(String),
+    NoPoint,
+    /// moins de deux points rattachés au graphe : erreur de chaque point écarté
+    NotEnough(Vec<(usize, EngineError)>),
+    Engine(EngineError),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match self {
+            ImportError::Io(e) => write!( f, "lecture impossible : {}", e ),
+            ImportError::Gpx(e) => write!( f, "gpx invalide : {}", e ),
+            ImportError::Json(e) => write!( f, "geojson invalide : {}", e ),
+            ImportError::Csv(n, line) => write!( f, "ligne {} invalide : {} (attendu lat,lon[,nom])", n, line ),
+            ImportError::Format(s) => write!( f, "format non géré : {} (.gpx, .geojson ou .csv)", s ),
+            ImportError::NoPoint => write!( f, "aucun point dans le fichier" ),
+            ImportError::NotEnough(errors) => {
+                write!( f, "moins de deux points sur le réseau" )?;
+                for (i, e) in errors.iter() { write!( f, "\n\tpoint {} : {}", i + 1, e )?; }
+                Ok(())
+            },
+            ImportError::Engine(e) => write!( f, "{}", e ),
+        }
+    }
+}
+
+impl From<std::io::Error> for ImportError {
+    fn from(e: std::io::Error) -> Self {
+        ImportError::Io(e)
+    }
+}
+
+impl From<GpxError> for ImportError {
+    fn from(e: GpxError) -> Self {
+        ImportError::Gpx(e)
+    }
+}
+
+impl From<serde_json::Error> for ImportError {
+    fn from(e: serde_json::Error) -> Self {
+        ImportError::Json(e)
+    }
+}
+
+impl From<EngineError> for ImportError {
+    fn from(e: EngineError) -> Self {
+        ImportError::Engine(e)
+    }
+}
+
+
+///
+/// point de passage importé, avec son nom s'il en a un
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Waypoint {
+    pub lat: f64,
+    pub lon: f64,
+    pub name: Option<String>,
+}
+
+impl Waypoint {
+    pub fn new( lat: f64, lon: f64, name: Option<String> ) -> Self {
+        Self { lat, lon, name }
+    }
+}
+
+impl fmt::Display for Waypoint {
+    fn fmt(&self, f: &mut fmt::Formatter ) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!( f, "{} ({},{})", name, self.lat, self.lon ),
+            None => write!( f, "{},{}", self.lat, self.lon ),
+        }
+    }
+}
+
+
+///
+/// lecture des points de passage d'un fichier, au format déduit de son extension
+///
+pub fn read_waypoints( path: &Path ) -> Result<Vec<Waypoint>, ImportError> {
+    let content = fs::read_to_string( path )?;
+    let points = match path.extension().and_then( |e| e.to_str() ).map( |e| e.to_lowercase() ).as_deref() {
+        Some("gpx") => from_gpx( &content )?,
+        Some("geojson") | Some("json") => from_geojson( &content )?,
+        Some("csv") | Some("txt") => from_csv( &content )?,
+        _ => return Err( ImportError::Format( path.display().to_string() ) ),
+    };
+    if points.is_empty() { return Err( ImportError::NoPoint ); }
+    Ok( points )
+}
+
+///
+/// points d'un gpx : les <rtept> des routes, dans l'ordre, ou à défaut les <wpt>
+///
+pub fn from_gpx( content: &str ) -> Result<Vec<Waypoint>, ImportError> {
+    let data = gpx::read( content.as_bytes() )?;
+    let point = |wp: &gpx::Waypoint| Waypoint::new( wp.point().y(), wp.point().x(), wp.name.clone() );
+    let routes: Vec<Waypoint> = data.routes.iter().flat_map( |r| r.points.iter() ).map( point ).collect();
+    Ok( if routes.is_empty() { data.waypoints.iter().map( point ).collect() } else { routes } )
+}
+
+///
+/// points d'un GeoJSON : features Point (ou MultiPoint) d'une FeatureCollection, d'une Feature ou
+/// géométrie seule, nommés par la propriété "name" ; les autres géométries sont ignorées
+///
+pub fn from_geojson( content: &str ) -> Result<Vec<Waypoint>, ImportError> {
+    let v: Value = serde_json::from_str( content )?;
+    let mut points = Vec::new();
+    let features: Vec<&Value> = match v["type"].as_str() {
+        Some("FeatureCollection") => v["features"].as_array().map( |a| a.iter().collect() ).unwrap_or_default(),
+        _ => vec![&v],
+    };
+    for feature in features {
+        let (geometry, name) = match feature["type"].as_str() {
+            Some("Feature") => ( &feature["geometry"], feature["properties"]["name"].as_str().map( |s| s.to_string() ) ),
+            _ => ( feature, None ),
+        };
+        let position = |c: &Value| match ( c[1].as_f64(), c[0].as_f64() ) {
+            ( Some(lat), Some(lon) ) => Some( Waypoint::new( lat, lon, name.clone() ) ),
+            _ => None,
+        };
+        match geometry["type"].as_str() {
+            Some("Point") => points.extend( position( &geometry["coordinates"] ) ),
+            Some("MultiPoint") => points.extend( geometry["coordinates"].as_array().into_iter().flatten().filter_map( position ) ),
+            _ => {},
+        }
+    }
+    Ok( points )
+}
+
+///
+/// points d'un csv "lat,lon[,nom]" (séparateur ',' ou ';') ; une ligne d'entête nommant les colonnes
+/// lat / latitude, lon / lng / longitude et name / nom fixe leur position
+///
+pub fn from_csv( content: &str ) -> Result<Vec<Waypoint>, ImportError> {
+    let mut columns = ( 0, 1, Some(2) );
+    let mut points = Vec::new();
+    for (n, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with( '#' ) { continue; }
+        let separator = if line.contains( ';' ) { ';' } else { ',' };
+        let fields: Vec<&str> = line.split( separator ).map( |f| f.trim().trim_matches( '"' ) ).collect();
+        let header = |names: &[&str]| fields.iter().position( |f| names.contains( &f.to_lowercase().as_str() ) );
+        if let ( Some(lat), Some(lon) ) = ( header( &["lat", "latitude"] ), header( &["lon", "lng", "long", "longitude"] ) ) {
+            columns = ( lat, lon, header( &["name", "nom", "label"] ) );
+            continue;
+        }
+        let value = |i: usize| fields.get( i ).and_then( |f| f.parse::<f64>().ok() );
+        match ( value( columns.0 ), value( columns.1 ) ) {
+            ( Some(lat), Some(lon) ) if (-90.0..=90.0).contains( &lat ) && (-180.0..=180.0).contains( &lon ) => {
+                let name = columns.2.and_then( |i| fields.get( i ) ).filter( |s| !s.is_empty() ).map( |s| s.to_string() );
+                points.push( Waypoint::new( lat, lon, name ) );
+            },
+            _ => return Err( ImportError::Csv( n + 1, line.to_string() ) ),
+        }
+    }
+    Ok( points )
+}
+
+
+///
+/// itinéraire par des points importés et points écartés faute de voie praticable à proximité
+///
+#[derive(Debug)]
+pub struct Tour {
+    pub itinerary: Itinerary,
+    /// indice dans la liste des points de ceux qui ont été rattachés, dans l'ordre des étapes
+    pub kept: Vec<usize>,
+    /// indice et erreur des points écartés
+    pub errors: Vec<(usize, EngineError)>,
+}
+
+///
+/// itinéraire passant dans l'ordre par les points rattachés au graphe du profil ; un point hors du
+/// réseau est écarté avec son erreur, il faut au moins deux points rattachés
+///
+pub fn route_waypoints( engine: &RoutingEngine, profile: Profile, metric: Metric, points: &[Waypoint], options: &RouteOptions ) -> Result<Tour, ImportError> {
+    let coords: Vec<(f64, f64)> = points.iter().map( |p| (p.lat, p.lon) ).collect();
+    let mut snaps = Vec::new();
+    let mut kept = Vec::new();
+    let mut errors = Vec::new();
+    for (i, s) in engine.snap_each( profile, &coords ).into_iter().enumerate() {
+        match s {
+            Ok(snap) => { snaps.push( snap ); kept.push( i ); },
+            Err(e) => errors.push( (i, e) ),
+        }
+    }
+    if snaps.len() < 2 { return Err( ImportError::NotEnough( errors ) ); }
+    let itinerary = engine.route_snapped( profile, metric, snaps, options )?;
+    Ok( Tour { itinerary, kept, errors } )
+}
+
+
+#[cfg(test)]
+mod waypoints_tests {
+    use super::*;
+    use crate::graphe::testing::test_graph;
+
+    #[test]
+    fn read_formats() {
+        let gpx = r#"<?xml version="1.0"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="48.1" lon="-2.1"><name>Dépôt</name></wpt>
+  <wpt lat="48.2" lon="-2.2"></wpt>
+</gpx>"#;
+        assert_eq!( vec![Waypoint::new( 48.1, -2.1, Some( "Dépôt".to_string() ) ), Waypoint::new( 48.2, -2.2, None )], from_gpx( gpx ).unwrap() );
+        // les points de route sont préférés aux waypoints
+        let rte = gpx.replace( "</gpx>", r#"<rte><rtept lat="48.3" lon="-2.3"/><rtept lat="48.4" lon="-2.4"/></rte></gpx>"# );
+        assert_eq!( vec![(48.3, -2.3), (48.4, -2.4)], from_gpx( &rte ).unwrap().iter().map( |p| (p.lat, p.lon) ).collect::<Vec<(f64, f64)>>() );
+
+        let geojson = r#"{ "type": "FeatureCollection", "features": [
+            { "type": "Feature", "geometry": { "type": "Point", "coordinates": [-2.1, 48.1] }, "properties": { "name": "A" } },
+            { "type": "Feature", "geometry": { "type": "LineString", "coordinates": [[-2.0, 48.0], [-2.1, 48.1]] }, "properties": {} },
+            { "type": "Feature", "geometry": { "type": "MultiPoint", "coordinates": [[-2.2, 48.2], [-2.3, 48.3]] }, "properties": null } ] }"#;
+        let points = from_geojson( geojson ).unwrap();
+        assert_eq!( 3, points.len() );
+        assert_eq!( Waypoint::new( 48.1, -2.1, Some( "A".to_string() ) ), points[0] );
+        assert_eq!( Waypoint::new( 48.3, -2.3, None ), points[2] );
+        assert!( from_geojson( "{ \"type\": " ).is_err() );
+
+        assert_eq!( vec![Waypoint::new( 48.1, -2.1, Some( "A".to_string() ) ), Waypoint::new( 48.2, -2.2, None )],
+            from_csv( "# tournée\n48.1,-2.1,A\n\n48.2 , -2.2\n" ).unwrap() );
+        assert_eq!( vec![Waypoint::new( 48.1, -2.1, Some( "Client".to_string() ) )], from_csv( "nom;longitude;latitude\n\"Client\";-2.1;48.1\n" ).unwrap() );
+        assert!( matches!( from_csv( "48.1,-2.1\n48.2;x\n" ), Err(ImportError::Csv(2, _)) ) );
+        assert!( matches!( from_csv( "148.1,-2.1\n" ), Err(ImportError::Csv(1, _)) ) );
+    }
+
+    #[test]
+    fn route_imported() {
+        let engine = RoutingEngine::new( test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99)],
+                                                     &[(10, &[1, 2, 3], &[("highway", "residential")])] ) );
+
+        let points = vec![Waypoint::new( 48.0, -2.0, None ), Waypoint::new( 40.0, 10.0, Some( "Loin".to_string() ) ),
+            Waypoint::new( 48.0001, -1.995, None ), Waypoint::new( 48.0, -1.99, None )];
+        let tour = route_waypoints( &engine, Profile::Car, Metric::Distance, &points, &RouteOptions::default() ).unwrap();
+        assert_eq!( vec![0, 2, 3], tour.kept );
+        assert_eq!( 1, tour.errors.len() );
+        assert!( matches!( tour.errors[0], (1, EngineError::NoSegment(..)) ) );
+        assert_eq!( 2, tour.itinerary.legs.len() );
+        assert_eq!( vec![1, 2, 3], tour.itinerary.route().nodes );
+
+        let lost = route_waypoints( &engine, Profile::Car, Metric::Distance, &points[..2], &RouteOptions::default() );
+        assert!( matches!( lost, Err(ImportError::NotEnough(ref e)) if e.len() == 1 ) );
+    }
+}
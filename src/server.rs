@@ -0,0 +1,495 @@
+use std::collections::HashMap;
+use std::thread;
+
+use serde_json::{json, Value};
+use tiny_http::{Header, Response, Server};
+
+use crate::engine::{EngineError, Itinerary, Metric, RouteOptions, RoutingEngine, Snap};
+use crate::export::polyline;
+use crate::graphe::Graph;
+use crate::graphe::index::Projection;
+use crate::routing::avoid::Avoid;
+use crate::routing::instructions::{instructions, Instruction};
+use crate::routing::profile::Profile;
+use crate::routing::route::Route;
+
+
+// nombre maximal de points par requête
+const MAX_POINTS: usize = 500;
+// nombre maximal de cases (sources x destinations) d'une table
+const MAX_TABLE: usize = 10_000;
+
+
+///
+/// données partagées en lecture seule par les threads du serveur : le moteur de calcul, dont
+/// les graphes orientés et l'index servent aussi au map matching
+///
+pub struct State<'a> {
+    engine: &'a RoutingEngine,
+}
+
+// erreur au format OSRM : code HTTP, code OSRM et message
+type Failure = (u16, &'static str, String);
+
+impl From<EngineError> for Failure {
+    fn from(e: EngineError) -> Self {
+        let code = match e {
+            EngineError::NoRoute(..) => "NoRoute",
+            EngineError::NoSegment(..) | EngineError::UnknownNode(_) => "NoSegment",
+            EngineError::Load(_) => "InvalidValue",
+        };
+        (400, code, e.to_string())
+    }
+}
+
+impl<'a> State<'a> {
+    pub fn new( engine: &'a RoutingEngine ) -> Self {
+        Self { engine }
+    }
+
+    fn profile( &self, name: &str ) -> Result<Profile, Failure> {
+        Profile::from_name( name ).ok_or( (400, "InvalidValue", format!( "profil inconnu : {}", name )) )
+    }
+}
+
+
+///
+/// service des requêtes reçues par le serveur (tiny_http::Server::http( "127.0.0.1:5000" )), bloquant
+///
+pub fn serve( engine: &RoutingEngine, server: &Server, threads: usize ) {
+    run( &State::new( engine ), server, threads );
+}
+
+///
+/// traitement des requêtes par `threads` threads jusqu'à ce que le serveur soit débloqué (Server::unblock)
+///
+pub fn run( state: &State, server: &Server, threads: usize ) {
+    thread::scope( |s| {
+        for _ in 0..threads.max( 1 ) {
+            s.spawn( || {
+                for rq in server.incoming_requests() {
+                    let (status, body) = handle( state, rq.url() );
+                    let response = Response::from_string( body.to_string() )
+                        .with_status_code( status )
+                        .with_header( Header::from_bytes( "Content-Type", "application/json; charset=utf-8" ).unwrap() )
+                        .with_header( Header::from_bytes( "Access-Control-Allow-Origin", "*" ).unwrap() );
+                    let _ = rq.respond( response );
+                }
+            } );
+        }
+    } );
+}
+
+///
+/// réponse JSON (code HTTP, corps) à une requête OSRM :
+/// /route/v1/{profile}/{lon,lat;lon,lat...}, /nearest/v1/..., /table/v1/..., /match/v1/...
+///
+pub fn handle( state: &State, url: &str ) -> (u16, Value) {
+    let (path, query) = url.split_once( '?' ).unwrap_or( (url, "") );
+    let params: HashMap<String, String> = query.split( '&' )
+        .filter_map( |kv| kv.split_once( '=' ) )
+        .map( |(k, v)| (decode( k ), decode( v )) )
+        .collect();
+    let parts: Vec<String> = path.trim_matches( '/' ).split( '/' ).map( decode ).collect();
+
+    let result = match parts.iter().map( |s| s.as_str() ).collect::<Vec<&str>>().as_slice() {
+        [service, "v1", profile, coords] => {
+            let coords = coords.trim_end_matches( ".json" );
+            match *service {
+                "route" => route( state, profile, coords, &params ),
+                "nearest" => nearest( state, profile, coords, &params ),
+                "table" => table( state, profile, coords, &params ),
+                "match" => matching( state, profile, coords, &params ),
+                _ => Err( (400, "InvalidService", format!( "service inconnu : {}", service )) ),
+            }
+        },
+        _ => Err( (400, "InvalidUrl", format!( "url invalide : {}", path )) ),
+    };
+    match result {
+        Ok(v) => (200, v),
+        Err((status, code, message)) => (status, json!( { "code": code, "message": message } )),
+    }
+}
+
+// décodage des %XX d'une url
+fn decode( s: &str ) -> String {
+    let b = s.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity( b.len() );
+    let mut i = 0;
+    while i < b.len() {
+        let hex = b.get( i+1..i+3 ).and_then( |h| std::str::from_utf8( h ).ok() ).and_then( |h| u8::from_str_radix( h, 16 ).ok() );
+        match (b[i], hex) {
+            (b'%', Some(c)) => { out.push( c ); i += 3; },
+            (b'+', _) => { out.push( b' ' ); i += 1; },
+            (c, _) => { out.push( c ); i += 1; },
+        }
+    }
+    String::from_utf8_lossy( &out ).to_string()
+}
+
+// liste "lon,lat;lon,lat" -> [(lat, lon)]
+fn coordinates( s: &str ) -> Result<Vec<(f64, f64)>, Failure> {
+    let invalid = || (400, "InvalidValue", format!( "coordonnées invalides : {}", s ));
+    let coords = s.split( ';' ).map( |c| {
+        let (lon, lat) = c.split_once( ',' ).ok_or_else( invalid )?;
+        let (lon, lat) = ( lon.trim().parse::<f64>().map_err( |_| invalid() )?, lat.trim().parse::<f64>().map_err( |_| invalid() )? );
+        if !( -90.0..=90.0 ).contains( &lat ) || !( -180.0..=180.0 ).contains( &lon ) { return Err( invalid() ); }
+        Ok( (lat, lon) )
+    } ).collect::<Result<Vec<(f64, f64)>, Failure>>()?;
+    if coords.len() > MAX_POINTS { return Err( (400, "TooBig", format!( "plus de {} points", MAX_POINTS )) ); }
+    Ok( coords )
+}
+
+// géométrie suivant le paramètre geometries : polyline (défaut), polyline6 ou geojson
+fn geometry( coords: &[(f64, f64)], params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    match params.get( "geometries" ).map( |s| s.as_str() ).unwrap_or( "polyline" ) {
+        "polyline" => Ok( json!( polyline::encode( coords, 5 ) ) ),
+        "polyline6" => Ok( json!( polyline::encode( coords, 6 ) ) ),
+        "geojson" => Ok( json!( {
+            "type": "LineString",
+            "coordinates": coords.iter().map( |(lat, lon)| json!( [lon, lat] ) ).collect::<Vec<Value>>(),
+        } ) ),
+        other => Err( (400, "InvalidOptions", format!( "geometries inconnu : {}", other )) ),
+    }
+}
+
+fn flag( params: &HashMap<String, String>, k: &str, default: bool ) -> bool {
+    params.get( k ).map( |v| v == "true" ).unwrap_or( default )
+}
+
+fn mode( p: &Profile ) -> &'static str {
+    match p {
+        Profile::Car => "driving",
+        Profile::Bicycle => "cycling",
+        Profile::Foot => "walking",
+    }
+}
+
+// poids utilisé par le calcul : le temps en voiture, la distance pondérée par la préférence sinon
+fn weight( p: &Profile ) -> Metric {
+    if *p == Profile::Car { Metric::Time } else { Metric::Cost }
+}
+
+fn waypoint( s: &Snap ) -> Value {
+    json!( { "hint": "", "name": s.name, "location": [s.lon, s.lat], "distance": s.distance } )
+}
+
+fn step( r: &Route, i: &Instruction, p: &Profile, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let location = json!( [i.location.1, i.location.0] );
+    let mut maneuver = json!( {
+        "type": i.maneuver,
+        "location": location,
+        "bearing_before": i.bearing_before.round(),
+        "bearing_after": i.bearing_after.round(),
+        "instruction": i.text(),
+    } );
+    if let Some(m) = i.modifier { maneuver["modifier"] = json!( m ); }
+    Ok( json!( {
+        "distance": i.distance,
+        "duration": i.duration,
+        "weight": i.duration,
+        "name": i.name,
+        "mode": if i.ferry { "ferry" } else { mode( p ) },
+        "driving_side": "right",
+        "geometry": geometry( &r.coords[i.from..=i.to], params )?,
+        "maneuver": maneuver,
+        "intersections": [ { "location": location } ],
+    } ) )
+}
+
+// étape entre deux points : distance, durée, poids, noms des voies principales et manœuvres si steps=true
+fn leg( g: &Graph, r: &Route, p: &Profile, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let w: f64 = r.edges.iter().map( |e| weight( p ).weight( e ) ).sum();
+    let names: Vec<String> = r.ways.iter().filter_map( |w| Route::way_name( g, *w ) ).take( 2 ).collect();
+    let steps = if flag( params, "steps", false ) {
+        instructions( g, r ).iter().map( |i| step( r, i, p, params ) ).collect::<Result<Vec<Value>, Failure>>()?
+    } else { Vec::new() };
+    Ok( json!( { "distance": r.distance, "duration": r.time, "weight": w, "summary": names.join( ", " ), "steps": steps } ) )
+}
+
+fn route( state: &State, profile: &str, coords: &str, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let p = state.profile( profile )?;
+    let points = coordinates( coords )?;
+    if points.len() < 2 { return Err( (400, "InvalidValue", "au moins deux points".to_string()) ); }
+    // exclude=toll,motorway,ferry : types de voies à éviter (cf. RouteOptions)
+    let avoid = match params.get( "exclude" ) {
+        Some(list) => Avoid::parse_list( list ).map_err( |e| (400, "InvalidValue", e) )?,
+        None => Vec::new(),
+    };
+    let it = state.engine.route_with( p, weight( &p ), &points, &RouteOptions { avoid, ..Default::default() } )?;
+    let g = state.engine.graph();
+
+    let legs = it.legs.iter().map( |r| leg( g, r, &p, params ) ).collect::<Result<Vec<Value>, Failure>>()?;
+
+    let all = it.route();
+    let weight_total: f64 = legs.iter().map( |l| l["weight"].as_f64().unwrap_or( 0.0 ) ).sum();
+    let mut result = json!( {
+        "distance": all.distance,
+        "duration": all.time,
+        "weight": weight_total,
+        "weight_name": if p == Profile::Car { "duration" } else { "routability" },
+        "legs": legs,
+    } );
+    if params.get( "overview" ).map( |s| s.as_str() ) != Some( "false" ) {
+        result["geometry"] = geometry( &all.coords, params )?;
+    }
+    if !it.unsatisfied.is_empty() {
+        result["unsatisfied"] = json!( it.unsatisfied.iter().map( |a| a.to_string() ).collect::<Vec<String>>() );
+    }
+    Ok( json!( { "code": "Ok", "routes": [result], "waypoints": it.waypoints.iter().map( waypoint ).collect::<Vec<Value>>() } ) )
+}
+
+fn nearest( state: &State, profile: &str, coords: &str, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let p = state.profile( profile )?;
+    let points = coordinates( coords )?;
+    let [(lat, lon)] = points.as_slice() else { return Err( (400, "InvalidValue", "un seul point attendu".to_string()) ) };
+    let number = params.get( "number" ).and_then( |n| n.parse::<usize>().ok() ).unwrap_or( 1 ).max( 1 );
+
+    let found = state.engine.nearest_segments( p, *lat, *lon, number );
+    if found.is_empty() { return Err( EngineError::NoSegment( *lat, *lon ).into() ); }
+    let waypoints: Vec<Value> = found.iter().map( |p| json!( {
+        "hint": "",
+        "nodes": [p.segment.from, p.segment.to],
+        "name": Route::way_name( state.engine.graph(), p.segment.way ).unwrap_or_default(),
+        "location": [p.lon, p.lat],
+        "distance": p.distance,
+    } ) ).collect();
+    Ok( json!( { "code": "Ok", "waypoints": waypoints } ) )
+}
+
+// indices "0;2" ou "all"
+fn indices( params: &HashMap<String, String>, k: &str, n: usize ) -> Result<Vec<usize>, Failure> {
+    match params.get( k ).map( |s| s.as_str() ) {
+        None | Some("all") => Ok( (0..n).collect() ),
+        Some(list) => list.split( ';' ).map( |i| match i.parse::<usize>() {
+            Ok(i) if i < n => Ok(i),
+            _ => Err( (400, "InvalidOptions", format!( "{} invalide : {}", k, list )) ),
+        } ).collect(),
+    }
+}
+
+fn table( state: &State, profile: &str, coords: &str, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let p = state.profile( profile )?;
+    let points = coordinates( coords )?;
+    let sources = indices( params, "sources", points.len() )?;
+    let destinations = indices( params, "destinations", points.len() )?;
+    if sources.len() * destinations.len() > MAX_TABLE {
+        return Err( (400, "TooBig", format!( "{} x {} : plus de {} durées", sources.len(), destinations.len(), MAX_TABLE )) );
+    }
+    let annotations = params.get( "annotations" ).map( |s| s.as_str() ).unwrap_or( "duration" );
+    let pick = |idx: &[usize]| idx.iter().map( |i| points[*i] ).collect::<Vec<(f64, f64)>>();
+    let m = state.engine.matrix( p, &pick( &sources ), &pick( &destinations ) )?;
+
+    let mut result = json!( {
+        "code": "Ok",
+        "sources": m.sources.iter().map( waypoint ).collect::<Vec<Value>>(),
+        "destinations": m.destinations.iter().map( waypoint ).collect::<Vec<Value>>(),
+    } );
+    if annotations.contains( "duration" ) { result["durations"] = json!( m.durations ); }
+    if annotations.contains( "distance" ) { result["distances"] = json!( m.distances ); }
+    Ok( result )
+}
+
+// noeud du graphe orienté le plus proche d'un point rattaché à un segment
+fn matched_snap( g: &Graph, adj: &HashMap<i64, Vec<crate::graphe::elements::Edge>>, proj: &Projection ) -> Snap {
+    let s = proj.segment;
+    let (near, far) = if proj.frac < 0.5 { (s.from, s.to) } else { (s.to, s.from) };
+    let node = if adj.contains_key( &near ) { near } else { far };
+    Snap { node, lat: proj.lat, lon: proj.lon, distance: proj.distance, way: s.way, name: Route::way_name( g, s.way ).unwrap_or_default() }
+}
+
+// une correspondance OSRM : points rattachés successifs reliés par un itinéraire, avec leur confiance
+struct Matching {
+    tracepoints: Vec<usize>,
+    confidence: Vec<f64>,
+    it: Itinerary,
+}
+
+fn matching( state: &State, profile: &str, coords: &str, params: &HashMap<String, String> ) -> Result<Value, Failure> {
+    let p = state.profile( profile )?;
+    let points = coordinates( coords )?;
+    let m = state.engine.matcher( p ).match_points( &points );
+    let g = state.engine.graph();
+    let adj = state.engine.directed( p );
+    if m.points.iter().all( |mp| mp.matched.is_none() ) { return Err( (400, "NoMatch", "aucun point rattaché".to_string()) ); }
+
+    // itinéraire entre points rattachés successifs ; une nouvelle correspondance commence
+    // quand le point suivant n'est pas accessible depuis le précédent
+    let mut matchings: Vec<Matching> = Vec::new();
+    let mut current: Option<Matching> = None;
+    for (i, mp) in m.points.iter().enumerate() {
+        let Some(proj) = &mp.matched else { continue };
+        let snap = matched_snap( g, adj, proj );
+        if let Some(c) = current.as_mut() {
+            let from = c.it.waypoints.last().unwrap().node;
+            match state.engine.route_nodes( p, weight( &p ), from, snap.node ) {
+                Ok(r) => {
+                    c.it.legs.push( r );
+                    c.it.waypoints.push( snap );
+                    c.tracepoints.push( i );
+                    c.confidence.push( mp.confidence );
+                    continue;
+                },
+                Err(EngineError::NoRoute(..)) => { matchings.extend( current.take() ); },
+                Err(e) => { return Err( e.into() ); },
+            }
+        }
+        current = Some( Matching { tracepoints: vec![i], confidence: vec![mp.confidence], it: Itinerary { waypoints: vec![snap], ..Default::default() } } );
+    }
+    matchings.extend( current );
+    // comme OSRM, une correspondance compte au moins deux points
+    matchings.retain( |c| c.tracepoints.len() > 1 );
+    if matchings.is_empty() { return Err( (400, "NoMatch", "aucun couple de points reliés".to_string()) ); }
+
+    let mut tracepoints: Vec<Value> = vec![Value::Null; points.len()];
+    let mut result: Vec<Value> = Vec::new();
+    for (k, c) in matchings.iter().enumerate() {
+        for (w, (i, snap)) in c.tracepoints.iter().zip( c.it.waypoints.iter() ).enumerate() {
+            tracepoints[*i] = json!( {
+                "hint": "",
+                "location": [snap.lon, snap.lat],
+                "distance": snap.distance,
+                "name": snap.name,
+                "matchings_index": k,
+                "waypoint_index": w,
+                "alternatives_count": 0,
+            } );
+        }
+        let legs = c.it.legs.iter().map( |r| leg( g, r, &p, params ) ).collect::<Result<Vec<Value>, Failure>>()?;
+        let all = c.it.route();
+        let mut matching = json!( {
+            "confidence": c.confidence.iter().sum::<f64>() / c.confidence.len() as f64,
+            "distance": all.distance,
+            "duration": all.time,
+            "weight": legs.iter().map( |l| l["weight"].as_f64().unwrap_or( 0.0 ) ).sum::<f64>(),
+            "weight_name": if p == Profile::Car { "duration" } else { "routability" },
+            "legs": legs,
+        } );
+        if params.get( "overview" ).map( |s| s.as_str() ) != Some( "false" ) {
+            matching["geometry"] = geometry( &all.coords, params )?;
+        }
+        result.push( matching );
+    }
+    Ok( json!( { "code": "Ok", "matchings": result, "tracepoints": tracepoints } ) )
+}
+
+
+#[cfg(test)]
+mod server_tests {
+    use super::*;
+    use crate::graphe::Graph;
+    use crate::graphe::testing::test_graph;
+
+    // rue A vers l'est (1 -> 2 -> 3) puis rue B vers le nord (3 -> 4), et un chemin piéton 4 -> 5
+    fn town() -> Graph {
+        test_graph( &[(1, 48.0, -2.0), (2, 48.0, -1.995), (3, 48.0, -1.99), (4, 48.005, -1.99), (5, 48.01, -1.99)],
+                    &[(10, &[1, 2, 3], &[("highway", "residential"), ("name", "Rue A")]),
+                      (11, &[3, 4], &[("highway", "residential"), ("name", "Rue B")]),
+                      (12, &[4, 5], &[("highway", "footway")])] )
+    }
+
+    #[test]
+    fn route_with_steps() {
+        let e = RoutingEngine::new( town() );
+        let g = e.graph();
+        let state = State::new( &e );
+
+        let (status, v) = handle( &state, "/route/v1/driving/-2.0001,48.0001;-1.9899,48.0049?steps=true&geometries=geojson" );
+        assert_eq!( 200, status );
+        assert_eq!( "Ok", v["code"] );
+        let r = &v["routes"][0];
+        let expected = g.tways[&10].len() + g.tways[&11].len();
+        assert!( ( r["distance"].as_f64().unwrap() - expected ).abs() < 1e-6 );
+        assert_eq!( 4, r["geometry"]["coordinates"].as_array().unwrap().len() );
+        assert_eq!( json!( [-1.99, 48.005] ), r["geometry"]["coordinates"][3] );
+        let steps = r["legs"][0]["steps"].as_array().unwrap();
+        assert_eq!( 3, steps.len() );
+        assert_eq!( "depart", steps[0]["maneuver"]["type"] );
+        assert_eq!( "left", steps[1]["maneuver"]["modifier"] );
+        assert_eq!( "Rue B", steps[1]["name"] );
+        assert_eq!( "arrive", steps[2]["maneuver"]["type"] );
+        assert_eq!( json!( [-2.0, 48.0] ), v["waypoints"][0]["location"] );
+
+        // polyline par défaut, sans étapes ; le noeud 5 n'est accessible qu'à pied
+        let (_, v) = handle( &state, "/route/v1/car/-2.0,48.0;-1.99,48.01" );
+        let line = polyline::decode( v["routes"][0]["geometry"].as_str().unwrap(), 5 ).unwrap();
+        assert_eq!( (48.005, -1.99), *line.last().unwrap() );
+        assert!( v["routes"][0]["legs"][0]["steps"].as_array().unwrap().is_empty() );
+        let (_, v) = handle( &state, "/route/v1/foot/-2.0,48.0;-1.99,48.01;-2.0,48.0?geometries=polyline6" );
+        assert_eq!( 2, v["routes"][0]["legs"].as_array().unwrap().len() );
+        assert_eq!( json!( [-1.99, 48.01] ), v["waypoints"][1]["location"] );
+    }
+
+    #[test]
+    fn nearest_table_match() {
+        let e = RoutingEngine::new( town() );
+        let g = e.graph();
+        let state = State::new( &e );
+
+        let (_, v) = handle( &state, "/nearest/v1/driving/-1.9975,48.0001?number=2" );
+        assert_eq!( "Rue A", v["waypoints"][0]["name"] );
+        assert_eq!( json!( [1, 2] ), v["waypoints"][0]["nodes"] );
+        assert!( ( v["waypoints"][0]["distance"].as_f64().unwrap() - 11.12 ).abs() < 0.01 );
+
+        let (_, v) = handle( &state, "/table/v1/driving/-2.0,48.0;-1.99,48.0;-1.99,48.005?sources=0&annotations=duration,distance" );
+        assert_eq!( 1, v["durations"].as_array().unwrap().len() );
+        assert_eq!( 0.0, v["durations"][0][0] );
+        let d = g.tways[&10].len();
+        assert!( ( v["distances"][0][1].as_f64().unwrap() - d ).abs() < 1e-6 );
+        assert!( ( v["durations"][0][1].as_f64().unwrap() - d / (50.0 / 3.6) ).abs() < 1e-6 );
+
+        let (_, v) = handle( &state, "/match/v1/driving/-2.0,48.0001;-1.995,47.9999;-1.99,48.0002;-1.9901,48.004?geometries=geojson" );
+        assert_eq!( "Ok", v["code"] );
+        assert_eq!( 4, v["tracepoints"].as_array().unwrap().len() );
+        assert_eq!( "Rue B", v["tracepoints"][3]["name"] );
+        assert!( v["matchings"][0]["confidence"].as_f64().unwrap() > 0.5 );
+        // une étape par couple de points rattachés, avec une vraie durée d'itinéraire
+        let m = &v["matchings"][0];
+        let legs = m["legs"].as_array().unwrap();
+        assert_eq!( 3, legs.len() );
+        assert_eq!( 3, v["tracepoints"][3]["waypoint_index"] );
+        let duration: f64 = legs.iter().map( |l| l["duration"].as_f64().unwrap() ).sum();
+        assert!( duration > 0.0 );
+        assert!( ( m["duration"].as_f64().unwrap() - duration ).abs() < 1e-6 );
+        assert!( ( m["distance"].as_f64().unwrap() - legs.iter().map( |l| l["distance"].as_f64().unwrap() ).sum::<f64>() ).abs() < 1e-6 );
+    }
+
+    #[test]
+    fn errors() {
+        let e = RoutingEngine::new( town() );
+        let state = State::new( &e );
+        let code = |url: &str| { let (s, v) = handle( &state, url ); (s, v["code"].as_str().unwrap().to_string()) };
+
+        assert_eq!( (400, "InvalidUrl".to_string()), code( "/route" ) );
+        assert_eq!( (400, "InvalidService".to_string()), code( "/trip/v1/driving/-2.0,48.0;-1.99,48.0" ) );
+        assert_eq!( (400, "InvalidValue".to_string()), code( "/route/v1/plane/-2.0,48.0;-1.99,48.0" ) );
+        assert_eq!( (400, "InvalidValue".to_string()), code( "/route/v1/driving/-2.0,48.0;abc" ) );
+        assert_eq!( (400, "InvalidOptions".to_string()), code( "/route/v1/driving/-2.0,48.0;-1.99,48.0?geometries=kml" ) );
+        assert_eq!( (400, "InvalidValue".to_string()), code( "/route/v1/driving/-2.0,48.0;-1.99,48.0?exclude=cows" ) );
+        assert_eq!( (400, "NoSegment".to_string()), code( "/route/v1/driving/-2.0,48.0;10.0,40.0" ) );
+        assert_eq!( (200, "Ok".to_string()), code( "/route/v1/driving/-2.0,48.0%3B-1.99,48.0" ) );
+        let many = vec!["-2.0,48.0"; 101].join( ";" );
+        assert_eq!( (400, "TooBig".to_string()), code( &format!( "/table/v1/driving/{}", many ) ) );
+    }
+
+    #[test]
+    fn concurrent_requests_on_localhost() {
+        let e = RoutingEngine::new( town() );
+        let state = State::new( &e );
+        let server = Server::http( "127.0.0.1:0" ).unwrap();
+        let url = format!( "http://{}/route/v1/driving/-2.0,48.0;-1.99,48.005", server.server_addr() );
+
+        thread::scope( |s| {
+            s.spawn( || run( &state, &server, 2 ) );
+            let clients: Vec<_> = (0..4).map( |_| s.spawn( || {
+                let res = reqwest::blocking::get( &url ).unwrap();
+                assert_eq!( 200, res.status().as_u16() );
+                let v: Value = res.json().unwrap();
+                v["routes"][0]["distance"].as_f64().unwrap()
+            } ) ).collect();
+            let distances: Vec<f64> = clients.into_iter().map( |c| c.join().unwrap() ).collect();
+            assert!( distances.iter().all( |d| *d == distances[0] && *d > 0.0 ) );
+            for _ in 0..2 { server.unblock(); }
+        } );
+    }
+}